@@ -0,0 +1,12 @@
+// Fires the events the wallet announces, like `beesv-sent`, on the page. The
+// wallet only sends them to the tabs of the origin chosen in its settings, and
+// the origin is checked again here in case the tab navigated away meanwhile.
+chrome.runtime.onMessage.addListener((message, sender) => {
+    if (sender.id !== chrome.runtime.id || typeof message.event !== 'string') {
+        return;
+    }
+    if (message.origin !== window.location.origin) {
+        return;
+    }
+    window.dispatchEvent(new CustomEvent(message.event, { detail: message.detail }));
+});
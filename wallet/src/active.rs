@@ -1,25 +1,64 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-use gloo_dialogs::alert;
+use gloo_dialogs::{alert, confirm};
 use secp256k1::PublicKey;
 use secp256k1::SecretKey;
 use web_sys::HtmlInputElement;
+use web_sys::HtmlSelectElement;
+use web_sys::HtmlTextAreaElement;
+use web_sys::HtmlVideoElement;
 use yew::platform::spawn_local;
 use yew::prelude::*;
 use yew_hooks::use_interval;
 
+use crate::backup;
 use crate::bip32::DerivePath;
+use crate::bip32::WalletKey;
 use crate::bip32::XPrv;
+use crate::camera;
+use crate::encryption;
+use crate::fees;
+use crate::fees::FeeQuote;
+use crate::fees::FeeQuoteCache;
+use crate::fees::FeeTier;
+use crate::psbt::PartialTransaction;
+use crate::qr;
 use crate::ratelimit::RateLimiter;
 use crate::recover::open_settings;
-use crate::sending::Input;
-use crate::sending::Output;
+use crate::script;
+use crate::sending;
+use crate::sending::SendOptions;
+use crate::sending::SendSummary;
+use crate::sending::SendWarning;
+use crate::sending::SigHashBase;
+use crate::sending::SigHashType;
+use crate::sending::SigningSummary;
 use crate::sending::Transaction;
+use crate::settings::default_endpoint;
+use crate::settings::Settings;
+use crate::settings::WalletConfig;
+use crate::settings::CONFIG_FILE_NAME;
+use crate::sync::SyncTracker;
 use crate::transactions;
+use crate::transactions::ChainKeys;
 use crate::transactions::RichOutput;
+use crate::transactions::SendSnapshot;
 use crate::transactions::WalletState;
+use crate::util;
 use crate::util::log;
-use crate::util::SATOSHIS_PER_BSV;
+use crate::util::Network;
+use crate::util::PaymentRequest;
+use crate::util::Satoshis;
+use crate::util::Unit;
+use crate::wif::PrivateKey;
+
+/// Receive addresses listed at once.
+const RECEIVE_LIST_SIZE: u32 = 5;
+/// Requests per second to the API, shared by syncs and fee quotes.
+const API_REQUESTS_PER_SECOND: u32 = 3;
 
 #[function_component(Popup)]
 pub fn popup() -> Html {
@@ -35,194 +74,2068 @@ pub fn popup() -> Html {
 
 #[derive(Properties, PartialEq)]
 pub struct FullscreenProps {
-    pub xprv: XPrv,
+    /// The master key, or for watch-only wallets such as the demo the account
+    /// key at `m/0'`, which can't be derived without the private key.
+    pub wallet_key: WalletKey,
+}
+
+/// Whether the wallet can send, sign or save anything. Everything doing so
+/// is hidden for watch-only wallets.
+pub fn can_send(wallet_key: &WalletKey) -> bool {
+    wallet_key.private().is_some()
 }
 
 #[function_component(Fullscreen)]
-pub fn fullscreen(FullscreenProps { xprv }: &FullscreenProps) -> Html {
-    let syncing = use_state(|| false);
-    let state = use_state(WalletState::default);
+pub fn fullscreen(FullscreenProps { wallet_key }: &FullscreenProps) -> Html {
+    // Addresses scanned by the running sync, if one is running
+    let sync_progress = use_state(|| None::<usize>);
+    let read_only = !can_send(wallet_key);
+    let derived_key = match wallet_key {
+        WalletKey::Private(xprv) => {
+            WalletKey::Private(xprv.derive_path("m/0'").expect("Should derive key"))
+        }
+        WalletKey::WatchOnly(xpub) => WalletKey::WatchOnly(xpub.clone()),
+    };
+    let state = use_state(|| {
+        WalletState::new(&derived_key, Network::default()).expect("Should derive chain keys")
+    });
 
-    let derived_key = xprv.derive_path("m/0'").expect("Should derive key");
+    let tracker = use_mut_ref(SyncTracker::default);
+    let fee_cache = use_mut_ref(FeeQuoteCache::default);
+    // Syncs and fee quotes run concurrently but share the API's request budget
+    let rate_limiter = use_memo(|_| RateLimiter::new(API_REQUESTS_PER_SECOND), ());
+    let fee_quote = use_state(FeeQuote::default);
 
-    let loader = syncing.clone();
-    let mutable_state = state.clone();
-    use_interval(
-        move || trigger_sync(derived_key.clone(), loader.clone(), mutable_state.clone()),
-        5000,
-    );
+    let settings = use_state(Settings::default);
+    {
+        let settings = settings.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    match Settings::load().await {
+                        Ok(loaded) => settings.set(loaded),
+                        Err(error) => log(&format!("Unable to load settings: {error:?}")),
+                    }
+                });
+                || ()
+            },
+            (),
+        );
+    }
+    let on_settings_saved = {
+        let settings = settings.clone();
+        Callback::from(move |saved| settings.set(saved))
+    };
+    {
+        // Coins and addresses of one network mean nothing on the other
+        let state = state.clone();
+        let derived_key = derived_key.clone();
+        let tracker = tracker.clone();
+        use_effect_with_deps(
+            move |&network| {
+                tracker.borrow_mut().set_network(network);
+                if state.network != network {
+                    let fresh = WalletState::new(&derived_key, network);
+                    state.set(fresh.expect("Should derive chain keys"));
+                }
+                || ()
+            },
+            settings.network,
+        );
+    }
+
+    let sync = {
+        let sync_progress = sync_progress.clone();
+        let state = state.clone();
+        let fee_quote = fee_quote.clone();
+        let settings = (*settings).clone();
+        move |force| {
+            trigger_sync(
+                derived_key.clone(),
+                settings.clone(),
+                rate_limiter.clone(),
+                tracker.clone(),
+                sync_progress.clone(),
+                state.clone(),
+                force,
+            );
+            refresh_fee_quote(
+                settings.clone(),
+                rate_limiter.clone(),
+                fee_cache.clone(),
+                fee_quote.clone(),
+            );
+        }
+    };
+    let sync_now = {
+        let sync = sync.clone();
+        move |_| sync(true)
+    };
+    use_interval(move || sync(false), settings.sync_interval_ms);
+
+    let selected = use_state(Vec::<RichOutput>::new);
+    let min_confirmations = use_state(|| 0);
+    let on_min_confirmations = {
+        let min_confirmations = min_confirmations.clone();
+        move |value| min_confirmations.set(value)
+    };
+    // Coins spent since they were selected are dropped from the selection,
+    // the others are taken from the latest sync as they may have confirmed
+    let selected_outputs: Vec<_> = state
+        .unspent_outputs
+        .iter()
+        .filter(|o| selected.iter().any(|s| s.same_outpoint(o)))
+        .cloned()
+        .collect();
+    let on_toggle = {
+        let selected = selected.clone();
+        move |output: RichOutput| {
+            let mut value: Vec<_> = selected.iter().cloned().collect();
+            match value.iter().position(|o| o.same_outpoint(&output)) {
+                Some(index) => {
+                    value.remove(index);
+                }
+                None => value.push(output),
+            }
+            selected.set(value);
+        }
+    };
+
+    // Addresses shown by the receive list this session, skipped when it moves on
+    let handed_out = use_state(|| 0u32);
+    let show_more_addresses = {
+        let handed_out = handed_out.clone();
+        let gap_limit = settings.gap_limit as u32;
+        Callback::from(move |_| handed_out.set((*handed_out + RECEIVE_LIST_SIZE).min(gap_limit)))
+    };
+
+    let address_index = use_state(|| 0u32);
+    let on_address_index = {
+        let address_index = address_index.clone();
+        Callback::from(move |index| address_index.set(index))
+    };
+    let describe = |address: anyhow::Result<String>| match address {
+        Ok(address) => address,
+        Err(error) => error.to_string(),
+    };
+
+    let locate_address = {
+        let state = state.clone();
+        Callback::from(
+            move |address: String| match state.locate_address(address.trim()) {
+                Some((chain, index)) => format!("Yes, index {index} on the {chain} chain"),
+                None if state.is_own_address(address.trim()) => "Yes".to_owned(),
+                None => "Not found".to_owned(),
+            },
+        )
+    };
+
+    let can_afford = {
+        let state = state.clone();
+        let fee_rate = fee_quote.rate(FeeTier::default());
+        Callback::from(move |amount: Satoshis| state.can_afford(amount.0, fee_rate))
+    };
+
+    let signed = use_state(|| None::<SignedTransaction>);
+    let on_signed = {
+        let signed = signed.clone();
+        let state = state.clone();
+        let settings = settings.clone();
+        Callback::from(move |transaction: SignedTransaction| {
+            if transaction.status == BroadcastStatus::Published {
+                if let Ok(sent) = Transaction::from_hex(&transaction.raw) {
+                    if let Some(origin) = &settings.announce_origin {
+                        announce_send(&sent, &state, origin.clone());
+                    }
+                    let mut updated = (*state).clone();
+                    if let Some(replaced) = &transaction.replaces {
+                        updated.forget_sent(replaced);
+                    }
+                    updated.apply_sent(&sent);
+                    state.set(updated);
+                }
+            }
+            signed.set(Some(transaction))
+        })
+    };
+
+    let bump_fee = {
+        let state = state.clone();
+        let settings = settings.clone();
+        let on_signed = on_signed.clone();
+        Callback::from(move |(signed, fee_rate): (SignedTransaction, u64)| {
+            let message = "Nodes don't replace transactions, the new one only wins if it \
+                reaches miners first. The payee may see the payment vanish and arrive \
+                again under a new txid. Bump the fee anyway?";
+            if !confirm(message) {
+                return;
+            }
+            let keys = state.signing_keys(&signed.coins);
+            let bumped = Transaction::from_hex(&signed.raw)
+                .map_err(anyhow::Error::from)
+                .and_then(|transaction| {
+                    let previous_outputs = sending::previous_outputs(&signed.coins)?;
+                    Ok(transaction.bump_fee(
+                        &previous_outputs,
+                        &keys,
+                        |hash| state.is_change(hash),
+                        fee_rate,
+                    )?)
+                });
+            match bumped {
+                Ok(bumped) => publish(
+                    bumped,
+                    &settings,
+                    &signed.coins,
+                    Some(signed.txid.clone()),
+                    &on_signed,
+                ),
+                Err(error) => alert(&format!("Unable to bump fee: {error}")),
+            }
+        })
+    };
+
+    // Kept until the next sync replaces the state, meant for offline use
+    let import_coins = {
+        let state = state.clone();
+        let settings = settings.clone();
+        Callback::from(move |json: String| {
+            let mut updated = (*state).clone();
+            match updated.import_utxos(&json, &settings) {
+                Ok(count) => {
+                    state.set(updated);
+                    alert(&format!("Imported {count} coins"));
+                }
+                Err(error) => alert(&format!("Unable to import coins: {error}")),
+            }
+        })
+    };
+
+    let keys_for_hashes = {
+        let state = state.clone();
+        Callback::from(move |hashes: Vec<[u8; 20]>| state.keys_for_hashes(&hashes))
+    };
+
+    let refresh_address = {
+        let state = state.clone();
+        let settings = settings.clone();
+        Callback::from(move |address: String| {
+            let state = state.clone();
+            let settings = (*settings).clone();
+            spawn_local(async move {
+                let mut updated = (*state).clone();
+                match updated
+                    .refresh_address(&address, &settings, settings.request_timeout_ms)
+                    .await
+                {
+                    Ok(()) => state.set(updated),
+                    Err(error) => alert(&format!("Unable to refresh {address}: {error}")),
+                }
+            });
+        })
+    };
 
     html! {
         <>
             <header><h1>{"Welcome to BeeSV"}</h1></header>
-            <p>{"Balance: "}{format!("{:.08}", state.balance as f32 / SATOSHIS_PER_BSV as f32)}{"₿"}</p>
-            if *syncing {
-                <p>{"Syncing..."}</p>
+            if state.needs_funding() {
+                <FundWallet address={state.receive_address()} />
+            } else {
+                <p>{"Balance: "}{state.balance.display_in(settings.unit)}</p>
+                <p>{"Available to send: "}{state.max_sendable(fee_quote.rate(FeeTier::default())).display_in(settings.unit)}</p>
+                if !state.receive_address().is_empty() {
+                    <p>{format!("Receive at: {}", state.receive_address())}</p>
+                }
+            }
+            if !state.pending_txids.is_empty() {
+                <p>{format!("{} sent transactions pending", state.pending_txids.len())}</p>
+            }
+            <p>{format!("{} of {} outputs spendable", state.spendable_output_count(*min_confirmations), state.output_count())}</p>
+            <p>{format!("{} transactions", state.transaction_count())}</p>
+            if let Some(scanned) = *sync_progress {
+                <p>{format!("Syncing... {scanned} addresses scanned")}</p>
             } else {
                 <p>{"Synced"}</p>
             }
-            <p>{"Send BSV"}</p>
-            <SendToAddress outputs={state.unspent_outputs.to_vec()} change_address={state.change_address()} key_fetcher={state.address_keys()} />
+            <button onclick={sync_now}>{"Sync now"}</button>
+            <details>
+                <summary>{"Sync details"}</summary>
+                { for state.gap_report().into_iter().map(|(chain, report)| html! {
+                    <p>{format!(
+                        "{chain}: highest used index {}, next unused {}, {} addresses scanned",
+                        report.highest_used.map_or("none".to_owned(), |i| i.to_string()),
+                        report.next_unused,
+                        report.scanned,
+                    )}</p>
+                }) }
+            </details>
+            <details>
+                <summary>{"History"}</summary>
+                <table>
+                    <tr>
+                        <th>{"Transaction"}</th>
+                        <th>{"Confirmations"}</th>
+                    </tr>
+                    { for state.history().into_iter().map(|(txid, confirmations)| html! {
+                        <tr>
+                            <td>{txid}</td>
+                            <td>{confirmations_text(confirmations)}</td>
+                        </tr>
+                    }) }
+                </table>
+            </details>
+            if read_only {
+                <p>{"Watch-only wallet, sending is disabled"}</p>
+            } else if !state.needs_funding() {
+                <p>{"Send BSV"}</p>
+                <SendToAddress settings={(*settings).clone()} fee_quote={(*fee_quote).clone()} {can_afford} wallet={state.send_snapshot(&selected_outputs)} on_signed={on_signed.clone()} />
+            }
+            if let Some(signed) = &*signed {
+                <BroadcastResult signed={signed.clone()} on_bump={bump_fee} />
+            }
+            if !state.receive_address().is_empty() {
+                <ReceiveAddresses
+                    addresses={state.unused_receive_addresses(*handed_out, RECEIVE_LIST_SIZE, settings.gap_limit as u32).unwrap_or_default()}
+                    at_gap_limit={*handed_out + RECEIVE_LIST_SIZE >= settings.gap_limit as u32}
+                    on_more={show_more_addresses} />
+            }
+            <AddressLookup
+                index={*address_index}
+                receive={describe(state.receive_address_at(*address_index))}
+                receive_key={describe(state.receive_key_at(*address_index))}
+                change={describe(state.change_address_at(*address_index))}
+                on_index={on_address_index} />
+            <OwnAddress locate={locate_address} on_refresh={refresh_address} />
+            <ImportCoins on_import={import_coins} />
+            <Coins outputs={state.unspent_outputs.to_vec()} selected={selected_outputs} main_balance={state.main_balance()} change_balance={state.change_balance()} tip_height={state.tip_height} network={settings.network} unit={settings.unit} min_confirmations={*min_confirmations} {on_min_confirmations} {on_toggle} />
+            if !read_only {
+                <Consolidate settings={(*settings).clone()} fee_quote={(*fee_quote).clone()} outputs={state.unspent_outputs.to_vec()} change_address={state.change_address()} keys={state.chain_keys()} {on_signed} />
+                { faucet(&settings) }
+                <SignRaw settings={(*settings).clone()} outputs={state.unspent_outputs.to_vec()} keys={state.chain_keys()} />
+                <CoSign settings={(*settings).clone()} outputs={state.unspent_outputs.to_vec()} key_fetcher={keys_for_hashes} />
+                <BroadcastChunks settings={(*settings).clone()} />
+            }
+            <WifAddress network={settings.network} />
+            <ScriptInspector />
+            if let Some(xprv) = wallet_key.private() {
+                <ExportBackup xprv={xprv.clone()} />
+            }
+            if !read_only {
+                <SettingsForm settings={(*settings).clone()} on_save={on_settings_saved} />
+            }
         </>
     }
 }
 
-fn trigger_sync(xprv: XPrv, loader: UseStateHandle<bool>, state: UseStateHandle<WalletState>) {
-    if *loader {
-        return;
+#[derive(Properties, PartialEq)]
+struct FundWalletProps {
+    address: String,
+}
+
+/// Shown instead of the balance and send form until the first coins arrive.
+#[function_component(FundWallet)]
+fn fund_wallet(FundWalletProps { address }: &FundWalletProps) -> Html {
+    let copy = {
+        let address = address.clone();
+        move |_| {
+            let address = address.clone();
+            spawn_local(async move {
+                if let Err(error) = util::copy_to_clipboard(&address).await {
+                    alert(&format!("Unable to copy: {error}"));
+                }
+            })
+        }
+    };
+
+    html! {
+        <div>
+            <p>{"This wallet is empty"}</p>
+            <p>{"Send some BSV to the address below to get started, it will show up after the next sync."}</p>
+            <p>{address}</p>
+            <button onclick={copy}>{"Copy"}</button>
+            { qr_codes(address) }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ReceiveAddressesProps {
+    addresses: Vec<(u32, String)>,
+    /// Whether the next addresses would lie past the gap limit.
+    at_gap_limit: bool,
+    on_more: Callback<()>,
+}
+
+/// Unused receive addresses, so each payer can get a distinct one.
+#[function_component(ReceiveAddresses)]
+fn receive_addresses(
+    ReceiveAddressesProps {
+        addresses,
+        at_gap_limit,
+        on_more,
+    }: &ReceiveAddressesProps,
+) -> Html {
+    let copy = |text: &str| {
+        let text = text.to_owned();
+        move |_| {
+            let text = text.clone();
+            spawn_local(async move {
+                if let Err(error) = util::copy_to_clipboard(&text).await {
+                    alert(&format!("Unable to copy: {error}"));
+                }
+            })
+        }
+    };
+    let more = {
+        let on_more = on_more.clone();
+        move |_| on_more.emit(())
+    };
+
+    html! {
+        <details>
+            <summary>{"More receive addresses"}</summary>
+            { for addresses.iter().map(|(index, address)| html! {
+                <div>
+                    <p>{format!("{index}: {address}")}</p>
+                    <button onclick={copy(address)}>{"Copy"}</button>
+                    { qr_codes(address) }
+                </div>
+            }) }
+            if *at_gap_limit {
+                <p>{"Use some of these addresses before asking for more, a scan stops at the gap limit"}</p>
+            }
+            <button onclick={more} disabled={*at_gap_limit}>{"Show next addresses"}</button>
+        </details>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct AddressLookupProps {
+    index: u32,
+    receive: String,
+    receive_key: String,
+    change: String,
+    on_index: Callback<u32>,
+}
+
+/// Shows the addresses at a given index, e.g. to verify one handed out before.
+#[function_component(AddressLookup)]
+fn address_lookup(
+    AddressLookupProps {
+        index,
+        receive,
+        receive_key,
+        change,
+        on_index,
+    }: &AddressLookupProps,
+) -> Html {
+    let set_index = {
+        let on_index = on_index.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(value) = input.value().parse() {
+                on_index.emit(value);
+            }
+        }
+    };
+
+    html! {
+        <>
+            <label for="address_index">{"Address index:"}</label>
+            <input id="address_index" type="number" min="0" value={index.to_string()} oninput={set_index}/>
+            <p>{format!("Receive: {receive}")}</p>
+            <p>{format!("Receive public key: {receive_key}")}</p>
+            <p>{format!("Change: {change}")}</p>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct OwnAddressProps {
+    locate: Callback<String, String>,
+    on_refresh: Callback<String>,
+}
+
+/// Checks whether an address, e.g. one shown by a third party, belongs to
+/// this wallet.
+#[function_component(OwnAddress)]
+fn own_address(OwnAddressProps { locate, on_refresh }: &OwnAddressProps) -> Html {
+    let address = use_state(String::default);
+    let set_address = {
+        let address = address.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            address.set(input.value());
+        }
+    };
+
+    let result = if address.trim().is_empty() {
+        String::default()
+    } else {
+        locate.emit((*address).clone())
+    };
+    let refresh = {
+        let address = address.clone();
+        let on_refresh = on_refresh.clone();
+        move |_| on_refresh.emit(address.trim().to_owned())
+    };
+
+    html! {
+        <>
+            <label for="own_address">{"Is this my address?"}</label>
+            <input id="own_address" oninput={set_address}/>
+            <p>{result}</p>
+            if !address.trim().is_empty() {
+                <button onclick={refresh}>{"Refresh its coins"}</button>
+            }
+        </>
     }
+}
+
+#[derive(Properties, PartialEq)]
+struct ImportCoinsProps {
+    on_import: Callback<String>,
+}
+
+/// Takes coins as JSON instead of from a sync, to build sends offline.
+#[function_component(ImportCoins)]
+fn import_coins(ImportCoinsProps { on_import }: &ImportCoinsProps) -> Html {
+    let json = use_state(String::default);
+    let set_json = {
+        let json = json.clone();
+        move |e: InputEvent| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            json.set(input.value());
+        }
+    };
+    let import = {
+        let on_import = on_import.clone();
+        move |_| on_import.emit((*json).clone())
+    };
+
+    html! {
+        <details>
+            <summary>{"Import coins"}</summary>
+            <textarea oninput={set_json} placeholder={r#"[{"txid": "...", "vout": 0, "amount": 1000, "address": "1..."}]"#}/>
+            <button onclick={import}>{"Import"}</button>
+        </details>
+    }
+}
+
+/// Shows the address a WIF key pays to, so it can be checked against where
+/// the funds are before importing.
+#[derive(Properties, PartialEq)]
+struct WifAddressProps {
+    network: Network,
+}
+
+#[function_component(WifAddress)]
+fn wif_address(WifAddressProps { network }: &WifAddressProps) -> Html {
+    let wif = use_state(String::default);
+    let set_wif = {
+        let wif = wif.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            wif.set(input.value());
+        }
+    };
+
+    let address = match wif.parse::<PrivateKey>() {
+        _ if wif.is_empty() => String::default(),
+        Ok(key) => format!("Address: {}", key.address(*network)),
+        Err(error) => format!("Invalid key: {error}"),
+    };
+
+    html! {
+        <>
+            <label for="wif">{"WIF key:"}</label>
+            <input id="wif" type="password" oninput={set_wif}/>
+            <p>{address}</p>
+        </>
+    }
+}
+
+/// Disassembles a script given as hex, e.g. an output's scriptPubKey.
+#[function_component(ScriptInspector)]
+fn script_inspector() -> Html {
+    let script = use_state(String::default);
+    let set_script = {
+        let script = script.clone();
+        move |e: InputEvent| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            script.set(input.value());
+        }
+    };
+
+    let result = match hex::decode(script.trim()) {
+        _ if script.trim().is_empty() => html! {},
+        Ok(bytes) => html! {
+            <>
+                <p>{script::template_name(&bytes).unwrap_or("Non-standard script")}</p>
+                <p>{script::disassemble(&bytes)}</p>
+            </>
+        },
+        Err(error) => html! { <p>{format!("Invalid hex: {error}")}</p> },
+    };
+
+    html! {
+        <details>
+            <summary>{"Inspect script"}</summary>
+            <textarea oninput={set_script} placeholder="Script hex"/>
+            { result }
+        </details>
+    }
+}
+
+/// Confirmations as shown, mined ones without a count when the tip height
+/// couldn't be fetched.
+fn confirmations_text(confirmations: Option<u32>) -> String {
+    match confirmations {
+        Some(0) => "Unconfirmed".to_owned(),
+        Some(confirmations) => confirmations.to_string(),
+        None => "Confirmed".to_owned(),
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct CoinsProps {
+    outputs: Vec<RichOutput>,
+    selected: Vec<RichOutput>,
+    main_balance: Satoshis,
+    change_balance: Satoshis,
+    tip_height: Option<u32>,
+    network: Network,
+    unit: Unit,
+    /// Coins with fewer confirmations are hidden and not counted as spendable.
+    min_confirmations: u32,
+    on_min_confirmations: Callback<u32>,
+    on_toggle: Callback<RichOutput>,
+}
+
+#[function_component(Coins)]
+fn coins(
+    CoinsProps {
+        outputs,
+        selected,
+        main_balance,
+        change_balance,
+        tip_height,
+        network,
+        unit,
+        min_confirmations,
+        on_min_confirmations,
+        on_toggle,
+    }: &CoinsProps,
+) -> Html {
+    let mut outputs = outputs.clone();
+    outputs.sort_by_key(|o| Reverse(o.amount));
+    let total: Satoshis = outputs.iter().map(|o| o.amount).sum();
+
+    let set_min_confirmations = {
+        let on_min_confirmations = on_min_confirmations.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            on_min_confirmations.emit(input.value().parse().unwrap_or_default());
+        }
+    };
+
+    let rows: Vec<_> = outputs
+        .iter()
+        .filter(|output| output.has_confirmations(*tip_height, *min_confirmations))
+        .map(|output| {
+            let toggle = {
+                let on_toggle = on_toggle.clone();
+                let output = output.clone();
+                move |_| on_toggle.emit(output.clone())
+            };
+            let confirmations = confirmations_text(output.confirmations(*tip_height));
+            html! {
+                <tr>
+                    <td><input type="checkbox" checked={selected.iter().any(|s| s.same_outpoint(output))} onchange={toggle}/></td>
+                    <td>{format!("{}:{}", output.tx_hash, output.tx_pos)}</td>
+                    <td>{output.amount.display_in(*unit)}</td>
+                    <td>{util::to_address(output.address, *network)}</td>
+                    <td>{output.key_path.map(|path| path.to_string()).unwrap_or_default()}</td>
+                    <td>{confirmations}</td>
+                </tr>
+            }
+        })
+        .collect();
+
+    html! {
+        <>
+            <p>{"Coins"}</p>
+            <p>{format!("{} outputs, total {}", outputs.len(), total.display_in(*unit))}</p>
+            <p>{format!(
+                "Receive addresses: {}, change addresses: {}",
+                main_balance.display_in(*unit),
+                change_balance.display_in(*unit)
+            )}</p>
+            if !selected.is_empty() {
+                <p>{format!("Sending will spend only the {} selected outputs", selected.len())}</p>
+            }
+            <label for="min_confirmations">{"Minimum confirmations:"}</label>
+            <input id="min_confirmations" type="number" min="0" value={min_confirmations.to_string()} oninput={set_min_confirmations}/>
+            <table>
+                <tr>
+                    <th>{"Spend"}</th>
+                    <th>{"Output"}</th>
+                    <th>{"Amount"}</th>
+                    <th>{"Address"}</th>
+                    <th>{"Path"}</th>
+                    <th>{"Confirmations"}</th>
+                </tr>
+                { rows }
+            </table>
+        </>
+    }
+}
 
-    loader.set(true);
+#[cfg(feature = "testnet-faucet")]
+fn faucet(settings: &Settings) -> Html {
+    html! { <crate::faucet::Faucet settings={settings.clone()} /> }
+}
+
+#[cfg(not(feature = "testnet-faucet"))]
+fn faucet(_settings: &Settings) -> Html {
+    Html::default()
+}
+
+fn trigger_sync(
+    wallet_key: WalletKey,
+    settings: Settings,
+    rate_limiter: Rc<RateLimiter>,
+    tracker: Rc<RefCell<SyncTracker>>,
+    progress: UseStateHandle<Option<usize>>,
+    state: UseStateHandle<WalletState>,
+    force: bool,
+) {
+    let Some(generation) = tracker.borrow_mut().start(util::get_timestamp(), force) else {
+        return;
+    };
 
-    let mut rate_limiter = RateLimiter::new(3);
+    progress.set(Some(0));
+
+    let settings = Settings {
+        gap_limit: transactions::gap_limit_for(settings.gap_limit, state.transaction_count()),
+        ..settings
+    };
     spawn_local(async move {
-        let result = transactions::fetch_for_address(&xprv, &mut rate_limiter)
-            .await
-            .unwrap();
-        state.set(result);
-        loader.set(false);
+        let on_progress = |count| progress.set(Some(count));
+        let result = transactions::fetch_for_address(
+            &wallet_key,
+            &rate_limiter,
+            &settings,
+            settings.request_timeout_ms,
+            &on_progress,
+        )
+        .await;
+        // A newer sync was started or the network switched in the meantime,
+        // the result is outdated
+        if !tracker.borrow_mut().finish(generation, settings.network) {
+            return;
+        }
+        match result {
+            Ok(result) => state.set(result),
+            Err(error) => log(&format!("Sync failed: {error:?}")),
+        }
+        progress.set(None);
+    });
+}
+
+/// Fetches a new fee quote once the cached one is stale.
+fn refresh_fee_quote(
+    settings: Settings,
+    rate_limiter: Rc<RateLimiter>,
+    cache: Rc<RefCell<FeeQuoteCache>>,
+    fee_quote: UseStateHandle<FeeQuote>,
+) {
+    let now = util::get_timestamp();
+    if cache.borrow().get(now).is_some() {
+        return;
+    }
+    // Stored before fetching so the interval doesn't start another request
+    cache.borrow_mut().store((*fee_quote).clone(), now);
+
+    spawn_local(async move {
+        let quote = fees::fetch_fee_quote(&rate_limiter, &settings).await;
+        cache
+            .borrow_mut()
+            .store(quote.clone(), util::get_timestamp());
+        fee_quote.set(quote);
     });
 }
 
 #[derive(Properties, PartialEq)]
 struct SendToAddressProps {
-    outputs: Vec<RichOutput>,
-    change_address: String,
-    key_fetcher: HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    settings: Settings,
+    fee_quote: FeeQuote,
+    can_afford: Callback<Satoshis, bool>,
+    wallet: SendSnapshot,
+    on_signed: Callback<SignedTransaction>,
 }
 
 #[function_component(SendToAddress)]
 fn send_to_address(
     SendToAddressProps {
-        outputs,
-        change_address,
-        key_fetcher,
+        settings,
+        fee_quote,
+        can_afford,
+        wallet,
+        on_signed,
     }: &SendToAddressProps,
 ) -> Html {
+    let unit = settings.unit;
+    let network = settings.network;
     let address = use_state(String::default);
-    let amount = use_state(|| 0f32);
+    let amount = use_state(String::default);
+    // Whether the amount was filled in by the Max button
+    let send_max = use_state(|| false);
+
+    let fill_request = {
+        let address = address.clone();
+        let amount = amount.clone();
+        let send_max = send_max.clone();
+        Callback::from(move |request: PaymentRequest| {
+            address.set(request.address);
+            if let Some(requested) = request.amount {
+                amount.set(requested.format_in(unit));
+                send_max.set(false);
+            }
+        })
+    };
 
     let set_address = {
         let address = address.clone();
+        let fill_request = fill_request.clone();
         move |e: InputEvent| {
             let input: HtmlInputElement = e.target_unchecked_into();
-            address.set(input.value());
+            let value = input.value();
+            // A pasted payment URI fills in the amount too
+            match util::parse_payment_uri(&value, network) {
+                Ok(request) if value.contains(':') => fill_request.emit(request),
+                _ => address.set(value),
+            }
         }
     };
 
     let set_amount = {
         let amount = amount.clone();
+        let send_max = send_max.clone();
         move |e: InputEvent| {
             let input: HtmlInputElement = e.target_unchecked_into();
-            let value = input.value().parse().unwrap_or(0f32);
-            amount.set(value);
+            amount.set(input.value());
+            send_max.set(false);
+        }
+    };
+    let fill_max = {
+        let amount = amount.clone();
+        let send_max = send_max.clone();
+        let coins = if wallet.selected.is_empty() {
+            wallet.outputs.clone()
+        } else {
+            wallet.selected.clone()
+        };
+        let fee_rate = fee_quote.rate(FeeTier::default());
+        move |_| {
+            amount.set(sending::max_sendable(&coins, fee_rate).format_in(unit));
+            send_max.set(true);
         }
     };
 
+    let on_scan =
+        Callback::from(
+            move |text: String| match util::parse_payment_uri(&text, network) {
+                Ok(request) => fill_request.emit(request),
+                Err(error) => alert(&format!("Scanned code is not an address: {error}")),
+            },
+        );
+
+    let insufficient =
+        Satoshis::parse_in(&amount, unit).is_ok_and(|amount| !can_afford.emit(amount));
+
+    let pending = use_state(|| None);
+
     let send_transaction = {
-        let outputs = outputs.clone();
-        let change_address = change_address.clone();
-        let key_fetcher = key_fetcher.clone();
+        let pending = pending.clone();
+        let address = address.clone();
+        let amount = amount.clone();
+        let wallet = wallet.clone();
         move |_| {
             if address.is_empty() {
                 alert("Address was not present");
                 return;
             }
-            if *amount < 0.000_000_01f32 {
-                alert("Must send a small value");
-                return;
-            }
-            let amount = (*amount * SATOSHIS_PER_BSV as f32) as u64;
-            let mut transaction = Transaction::default();
-            let output = match Output::new(amount, &address) {
-                Ok(output) => output,
-                Err(error) => {
-                    alert(&format!("Can't send: {error:?}"));
-                    return;
-                }
-            };
-            transaction.add_output(output);
-
-            let output_map = outputs
-                .iter()
-                .cloned()
-                .map(|o| {
-                    (
-                        (hex::decode(o.tx_hash).unwrap(), o.tx_pos),
-                        Output::new_from_decoded(o.amount, o.address),
-                    )
-                })
-                .collect();
-            let mut outputs = outputs.clone();
-            let mut output_sum = 0;
-            while output_sum < amount && !outputs.is_empty() {
-                let output = outputs.remove(0);
-                output_sum += output.amount;
-                transaction.add_input(
-                    Input::new(output.tx_hash, output.tx_pos)
-                        .expect("Input tx hash should be decodable"),
-                );
-            }
-            if amount > output_sum {
-                alert(&format!(
-                    "Unable to send, insufficient balance, missing {}",
-                    amount - output_sum
-                ));
-                return;
-            }
-            let mut fee = transaction.suggested_fee();
-            while output_sum - amount < fee && !outputs.is_empty() {
-                let output = outputs.remove(0);
-                output_sum += output.amount;
-                transaction.add_input(
-                    Input::new(output.tx_hash, output.tx_pos)
-                        .expect("Input tx hash should be decodable"),
-                );
-                fee = transaction.suggested_fee();
-            }
-            if output_sum - amount < fee {
-                alert(&format!(
-                    "Unable to send transaction, insufficient BSV for transaction+fee: {}",
-                    amount + fee
-                ));
-                return;
-            }
-            let change = output_sum - amount - fee;
-            let change = match Output::new(change, &change_address) {
-                Ok(change) => change,
+            let amount = match Satoshis::parse_in(&amount, unit) {
+                Ok(amount) => amount,
                 Err(error) => {
-                    alert(&format!(
-                        "Unable to send transaction, invalid change address: {error:?}"
-                    ));
+                    alert(&format!("Can't send: {error}"));
                     return;
                 }
             };
-            transaction.add_output(change);
-            if let Err(error) = transaction.sign_inputs(&output_map, &key_fetcher) {
-                alert(&format!("Unable to sign transaction: {error:?}"));
+            if let Err(error) = sending::check_recipient(&address, amount, network) {
+                alert(&format!("Can't send: {error}"));
                 return;
             }
 
-            log(&format!(
-                "Transaction: {}, fee: {}",
-                hex::encode(Vec::from(&transaction)),
-                transaction.suggested_fee()
-            ));
-            spawn_local(async move {
-                if let Err(error) = transactions::publish_transaction(&transaction).await {
-                    alert(&format!("Unable to publish transaction: {error:?}"));
-                }
-            })
+            // Syncs finishing while the user confirms don't change what is sent
+            pending.set(Some((amount, wallet.clone())));
         }
     };
 
+    let on_close = {
+        let pending = pending.clone();
+        move |_| pending.set(None)
+    };
+
     html! {
         <>
             <label for="address">{"Address:"}</label>
-            <input id="address" oninput={set_address}/>
-            <label for="amount">{"Amount to send:"}</label>
-            <input id="amount" type="number" oninput={set_amount}/>
-            <button onclick={send_transaction}>{"Send"}</button>
+            <input id="address" value={(*address).clone()} oninput={set_address}/>
+            <ScanQr {on_scan}/>
+            <label for="amount">{format!("Amount to send ({}):", unit.name())}</label>
+            <input id="amount" inputmode="decimal" value={(*amount).clone()} oninput={set_amount}/>
+            <button onclick={fill_max}>{"Max"}</button>
+            <button onclick={send_transaction} disabled={insufficient}>{"Send"}</button>
+            if insufficient {
+                <p>{"Insufficient funds"}</p>
+            }
+            if let Some((amount, snapshot)) = &*pending {
+                <SendConfirmation
+                    settings={settings.clone()}
+                    fee_quote={fee_quote.clone()}
+                    address={(*address).clone()}
+                    amount={*amount}
+                    send_max={*send_max}
+                    snapshot={snapshot.clone()}
+                    current_outputs={wallet.outputs.clone()}
+                    on_signed={on_signed.clone()}
+                    {on_close} />
+            }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ScanQrProps {
+    on_scan: Callback<String>,
+}
+
+/// Reads a QR code with the camera, the preview is shown while scanning.
+#[function_component(ScanQr)]
+fn scan_qr(ScanQrProps { on_scan }: &ScanQrProps) -> Html {
+    let video = use_node_ref();
+    let scanning = use_state(|| false);
+
+    let start = {
+        let video = video.clone();
+        let scanning = scanning.clone();
+        let on_scan = on_scan.clone();
+        move |_| {
+            let Some(element) = video.cast::<HtmlVideoElement>() else {
+                return;
+            };
+            scanning.set(true);
+            let scanning = scanning.clone();
+            let on_scan = on_scan.clone();
+            spawn_local(async move {
+                match camera::scan_qr(&element).await {
+                    Ok(text) => on_scan.emit(text),
+                    Err(error) => alert(&format!("Unable to scan: {error}")),
+                }
+                scanning.set(false);
+            });
+        }
+    };
+
+    html! {
+        <>
+            <button onclick={start} disabled={*scanning}>{"Scan QR"}</button>
+            <video ref={video} hidden={!*scanning} muted=true playsinline=true/>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct SendConfirmationProps {
+    settings: Settings,
+    fee_quote: FeeQuote,
+    address: String,
+    amount: Satoshis,
+    /// Whether the amount is the most the wallet can send.
+    send_max: bool,
+    /// The wallet as it was when sending started.
+    snapshot: SendSnapshot,
+    /// Unspent outputs as of the latest sync.
+    current_outputs: Vec<RichOutput>,
+    on_signed: Callback<SignedTransaction>,
+    on_close: Callback<()>,
+}
+
+#[function_component(SendConfirmation)]
+fn send_confirmation(
+    SendConfirmationProps {
+        settings,
+        fee_quote,
+        address,
+        amount,
+        send_max,
+        snapshot,
+        current_outputs,
+        on_signed,
+        on_close,
+    }: &SendConfirmationProps,
+) -> Html {
+    let SendSnapshot {
+        outputs,
+        selected,
+        change_address,
+        keys,
+    } = snapshot;
+    let tier = use_state(FeeTier::default);
+    let fee_rate = fee_quote.rate(*tier);
+
+    let set_tier = {
+        let tier = tier.clone();
+        move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(value) = select.value().parse() {
+                tier.set(value);
+            }
+        }
+    };
+    let sighash_type = use_state(SigHashType::default);
+    // Drawn once so the order doesn't change while the confirmation is shown
+    let shuffle_seed = use_state(|| {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed)
+            .ok()
+            .map(|_| u64::from_le_bytes(seed))
+    });
+    let set_sighash_base = {
+        let sighash_type = sighash_type.clone();
+        move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(base) = select.value().parse() {
+                sighash_type.set(SigHashType {
+                    base,
+                    ..*sighash_type
+                });
+            }
+        }
+    };
+    let set_anyone_can_pay = {
+        let sighash_type = sighash_type.clone();
+        move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            sighash_type.set(SigHashType {
+                anyone_can_pay: input.checked(),
+                ..*sighash_type
+            });
+        }
+    };
+
+    let cancel = {
+        let on_close = on_close.clone();
+        move |_| on_close.emit(())
+    };
+
+    let recipients = vec![(address.clone(), *amount)];
+    let options = SendOptions {
+        selected: selected.clone(),
+        shuffle_seed: shuffle_seed.filter(|_| settings.shuffle_outputs),
+        sighash_type: *sighash_type,
+        ..Default::default()
+    };
+    let built = sending::prepare(
+        &recipients,
+        outputs,
+        change_address,
+        settings.network,
+        fee_rate,
+        &options,
+    );
+    let (transaction, breakdown) = match built {
+        Ok(built) => built,
+        Err(error) => {
+            return html! {
+                <>
+                    <p>{format!("Unable to send transaction: {error}")}</p>
+                    <button onclick={cancel}>{"Cancel"}</button>
+                </>
+            }
+        }
+    };
+
+    let fee = breakdown.fee;
+    let unit = settings.unit;
+    let size = transaction.size_breakdown();
+
+    let mut warnings = transaction.send_warnings(*amount, fee);
+    warnings.extend(sending::sweep_warning(
+        &transaction,
+        &breakdown,
+        outputs,
+        *send_max,
+    ));
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|warning| match warning {
+            SendWarning::HighFee(..) => {
+                html! { <p><strong>{format!("Warning: {warning}")}</strong></p> }
+            }
+            _ => html! { <p>{format!("Warning: {warning}")}</p> },
+        })
+        .collect();
+
+    // Only shared for co-signing, anyone seeing such an input broadcast could
+    // change what it pays
+    let custom_sighash = *sighash_type != SigHashType::default();
+    let export = {
+        let outputs = outputs.clone();
+        let transaction = transaction.clone();
+        let keys = keys.clone();
+        let sighash_type = *sighash_type;
+        move |_| {
+            let exported = sending::previous_outputs(&outputs)
+                .map_err(anyhow::Error::from)
+                .and_then(|previous_outputs| {
+                    let mut partial = PartialTransaction::new(&transaction, &previous_outputs)?;
+                    if custom_sighash {
+                        let key_fetcher = keys.signing_keys(&outputs);
+                        partial.sign_available_with(&key_fetcher, sighash_type)?;
+                    }
+                    Ok(partial)
+                })
+                .and_then(|partial| partial.to_json());
+            match exported {
+                Ok(json) => spawn_local(async move {
+                    if let Err(error) = util::copy_to_clipboard(&json).await {
+                        alert(&format!("Unable to copy: {error}"));
+                    }
+                }),
+                Err(error) => alert(&format!("Unable to export transaction: {error}")),
+            }
+        }
+    };
+
+    let confirm_send = {
+        let settings = settings.clone();
+        let outputs = outputs.clone();
+        let keys = keys.clone();
+        let on_signed = on_signed.clone();
+        let on_close = on_close.clone();
+        let change_address = change_address.clone();
+        let is_current = snapshot.is_current(&transaction, current_outputs);
+        move |_| {
+            if !is_current {
+                alert("Coins of this transaction were spent since it was prepared, send again");
+                on_close.emit(());
+                return;
+            }
+            let signed = sending::prepare_and_sign(
+                &recipients,
+                &outputs,
+                &change_address,
+                settings.network,
+                &keys.signing_keys(&outputs),
+                fee_rate,
+                &options,
+            );
+            match signed {
+                Ok(transaction) => publish(transaction, &settings, &outputs, None, &on_signed),
+                Err(error) => alert(&format!("Unable to sign transaction: {error}")),
+            }
+            on_close.emit(());
+        }
+    };
+
+    let sighash_bases: Vec<_> = SigHashBase::ALL
+        .iter()
+        .map(|option| {
+            html! {
+                <option value={option.name()} selected={*option == sighash_type.base}>{option.name()}</option>
+            }
+        })
+        .collect();
+
+    let tiers: Vec<_> = FeeTier::ALL
+        .iter()
+        .map(|option| {
+            html! {
+                <option value={option.name()} selected={*option == *tier}>{option.name()}</option>
+            }
+        })
+        .collect();
+
+    html! {
+        <>
+            <p>{format!("Send {} to {address}", amount.display_in(unit))}</p>
+            <label for="fee_tier">{"Fee:"}</label>
+            <select id="fee_tier" onchange={set_tier}>
+                { tiers }
+            </select>
+            <p>{format!(
+                "Fee: {} ({fee_rate} sat/byte), confirmation {}",
+                fee.display_in(unit),
+                tier.confirmation_estimate()
+            )}</p>
+            if let Some(percent) = sending::fee_percent(fee, *amount) {
+                <p>{format!("The fee is {percent:.1}% of the amount sent")}</p>
+            }
+            <p>{format!(
+                "Spending {}, change {}",
+                breakdown.inputs.display_in(unit),
+                breakdown.change.display_in(unit)
+            )}</p>
+            <p>{format!(
+                "Size before signing: {} bytes (base {}, inputs {}, outputs {})",
+                size.total, size.base, size.inputs, size.outputs
+            )}</p>
+            { warnings }
+            <details>
+                <summary>{"Advanced"}</summary>
+                <label for="sighash_base">{"Signature covers outputs:"}</label>
+                <select id="sighash_base" onchange={set_sighash_base}>
+                    { sighash_bases }
+                </select>
+                <label for="anyone_can_pay">{"ANYONECANPAY"}</label>
+                <input id="anyone_can_pay" type="checkbox" checked={sighash_type.anyone_can_pay} onchange={set_anyone_can_pay}/>
+                if let Some(warning) = sighash_type.warning() {
+                    <p>{format!("Warning: {warning}")}</p>
+                }
+            </details>
+            if custom_sighash {
+                <p>{"Signatures with these flags are only copied for co-signing, not broadcast"}</p>
+            }
+            <button onclick={confirm_send} disabled={custom_sighash}>{"Confirm"}</button>
+            <button onclick={export}>{"Copy for co-signing"}</button>
+            <button onclick={cancel}>{"Cancel"}</button>
+        </>
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum BroadcastStatus {
+    Pending,
+    Published,
+    Failed(String),
+}
+
+#[derive(Clone, PartialEq)]
+struct SignedTransaction {
+    txid: String,
+    raw: String,
+    status: BroadcastStatus,
+    /// Coins the transaction was built from, at least those it spends.
+    coins: Vec<RichOutput>,
+    /// Txid of the transaction this one double spends with a higher fee.
+    replaces: Option<String>,
+}
+
+fn sign_and_publish(
+    mut transaction: Transaction,
+    settings: &Settings,
+    outputs: &[RichOutput],
+    key_fetcher: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    sighash_type: SigHashType,
+    on_signed: &Callback<SignedTransaction>,
+) {
+    if let Err(error) = sending::sign(&mut transaction, outputs, key_fetcher, sighash_type) {
+        alert(&format!("Unable to sign transaction: {error:?}"));
+        return;
+    }
+    publish(transaction, settings, outputs, None, on_signed);
+}
+
+/// Tells the open pages of `origin` what `sent` paid, summarized before
+/// `state` is updated.
+fn announce_send(sent: &Transaction, state: &WalletState, origin: String) {
+    let summary = SendSummary::new(
+        sent,
+        &state.unspent_outputs,
+        |address| state.is_own_address(address),
+        state.network,
+    );
+    spawn_local(async move {
+        let announced = match summary {
+            Ok(summary) => util::dispatch_event("beesv-sent", &origin, &summary).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = announced {
+            log(&format!("Unable to announce send: {error:?}"));
+        }
+    });
+}
+
+/// Broadcasts a signed `transaction` spending some of `outputs`, possibly
+/// replacing the transaction with the txid `replaces`.
+fn publish(
+    transaction: Transaction,
+    settings: &Settings,
+    outputs: &[RichOutput],
+    replaces: Option<String>,
+    on_signed: &Callback<SignedTransaction>,
+) {
+    let signed = SignedTransaction {
+        txid: transaction.txid(),
+        raw: transaction.to_hex(),
+        status: BroadcastStatus::Pending,
+        coins: outputs.to_vec(),
+        replaces,
+    };
+    log(&format!(
+        "Transaction: {}, fee: {:?}",
+        signed.raw,
+        sending::previous_outputs(outputs)
+            .map_err(anyhow::Error::from)
+            .and_then(|output_map| Ok(transaction.actual_fee(&output_map)?))
+    ));
+    on_signed.emit(signed.clone());
+
+    let on_signed = on_signed.clone();
+    let settings = settings.clone();
+    spawn_local(async move {
+        let status = match transactions::publish_transaction(
+            &transaction,
+            &settings,
+            settings.request_timeout_ms,
+        )
+        .await
+        {
+            Ok(_) => BroadcastStatus::Published,
+            Err(error) => BroadcastStatus::Failed(format!("{error:?}")),
+        };
+        on_signed.emit(SignedTransaction { status, ..signed });
+    })
+}
+
+#[derive(Properties, PartialEq)]
+struct BroadcastResultProps {
+    signed: SignedTransaction,
+    /// Asks to double spend the transaction at the given fee rate.
+    on_bump: Callback<(SignedTransaction, u64)>,
+}
+
+#[function_component(BroadcastResult)]
+fn broadcast_result(BroadcastResultProps { signed, on_bump }: &BroadcastResultProps) -> Html {
+    let bump_rate = use_state(String::default);
+    let set_bump_rate = {
+        let bump_rate = bump_rate.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            bump_rate.set(input.value());
+        }
+    };
+    let bump = {
+        let signed = signed.clone();
+        let on_bump = on_bump.clone();
+        move |_| match bump_rate.trim().parse() {
+            Ok(fee_rate) => on_bump.emit((signed.clone(), fee_rate)),
+            Err(_) => alert("Enter the new fee rate in satoshis per byte"),
+        }
+    };
+
+    let copy = |text: &str| {
+        let text = text.to_owned();
+        move |_| {
+            let text = text.clone();
+            spawn_local(async move {
+                if let Err(error) = util::copy_to_clipboard(&text).await {
+                    alert(&format!("Unable to copy: {error}"));
+                }
+            })
+        }
+    };
+
+    let status = match &signed.status {
+        BroadcastStatus::Pending => "Broadcasting...".to_owned(),
+        BroadcastStatus::Published => "Broadcast".to_owned(),
+        BroadcastStatus::Failed(error) => format!("Unable to publish transaction: {error}"),
+    };
+
+    html! {
+        <>
+            <p>{format!("Transaction {}", signed.txid)}</p>
+            <p>{status}</p>
+            <button onclick={copy(&signed.raw)}>{"Copy raw transaction"}</button>
+            <button onclick={copy(&signed.txid)}>{"Copy txid"}</button>
+            if signed.status == BroadcastStatus::Published {
+                <details>
+                    <summary>{"Stuck? Bump the fee"}</summary>
+                    <label for="bump_rate">{"New fee rate (sat/byte):"}</label>
+                    <input id="bump_rate" type="number" min="1" oninput={set_bump_rate}/>
+                    <button onclick={bump}>{"Bump fee"}</button>
+                </details>
+            }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct SignRawProps {
+    settings: Settings,
+    outputs: Vec<RichOutput>,
+    keys: ChainKeys,
+}
+
+/// Signs the wallet's inputs of a transaction constructed elsewhere.
+#[function_component(SignRaw)]
+fn sign_raw(
+    SignRawProps {
+        settings,
+        outputs,
+        keys,
+    }: &SignRawProps,
+) -> Html {
+    let raw = use_state(String::default);
+    let signed = use_state(|| None);
+    let wif = use_state(String::default);
+    // Coins and key of an imported WIF key, signed for next to the wallet's
+    let imported = use_state(|| None::<(Vec<RichOutput>, PrivateKey)>);
+
+    let set_wif = {
+        let wif = wif.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            wif.set(input.value());
+        }
+    };
+    let import_clicked = {
+        let settings = settings.clone();
+        let imported = imported.clone();
+        move |_| {
+            let key = match wif.parse::<PrivateKey>() {
+                Ok(key) => key,
+                Err(error) => {
+                    alert(&format!("Invalid key: {error}"));
+                    return;
+                }
+            };
+            let settings = settings.clone();
+            let imported = imported.clone();
+            spawn_local(async move {
+                let address = key.address(settings.network);
+                match transactions::fetch_foreign_unspent(
+                    &address,
+                    &settings,
+                    settings.request_timeout_ms,
+                )
+                .await
+                {
+                    Ok(outputs) => imported.set(Some((outputs, key))),
+                    Err(error) => alert(&format!("Unable to fetch coins of {address}: {error}")),
+                }
+            });
+        }
+    };
+
+    let set_raw = {
+        let raw = raw.clone();
+        move |e: InputEvent| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            raw.set(input.value());
+        }
+    };
+
+    let mut outputs = outputs.clone();
+    if let Some((imported_outputs, _)) = &*imported {
+        outputs.extend(imported_outputs.iter().cloned());
+    }
+    let summary = sending::previous_outputs(&outputs)
+        .ok()
+        .and_then(|previous_outputs| {
+            let transaction = Transaction::from_hex(&raw).ok()?;
+            Some(transaction.signing_summary(&previous_outputs, settings.network))
+        });
+    let unit = settings.unit;
+
+    let sign_clicked = {
+        let summary = summary.as_ref().map(|summary| summary_text(summary, unit));
+        let signed = signed.clone();
+        let keys = keys.clone();
+        let imported = imported.clone();
+        move |_| {
+            let Some(summary) = &summary else {
+                alert("Not a valid raw transaction");
+                return;
+            };
+            if !confirm(&format!("Sign this transaction?\n{summary}")) {
+                return;
+            }
+            let mut key_fetcher = keys.signing_keys(&outputs);
+            if let Some((_, key)) = &*imported {
+                key_fetcher.extend([key.address_key()]);
+            }
+            let result = sending::previous_outputs(&outputs)
+                .map_err(anyhow::Error::from)
+                .and_then(|previous_outputs| {
+                    sending::sign_raw_transaction(&raw, &previous_outputs, &key_fetcher)
+                });
+            match result {
+                Ok(result) => signed.set(Some(result)),
+                Err(error) => alert(&format!("Unable to sign transaction: {error}")),
+            }
+        }
+    };
+
+    html! {
+        <>
+            <label for="raw_transaction">{"Raw transaction to sign:"}</label>
+            <textarea id="raw_transaction" oninput={set_raw}/>
+            <label for="sign_wif">{"Also sign with WIF key (optional):"}</label>
+            <input id="sign_wif" type="password" oninput={set_wif}/>
+            <button onclick={import_clicked}>{"Import key"}</button>
+            if let Some((imported_outputs, key)) = &*imported {
+                <p>{format!("{} coins of {} imported", imported_outputs.len(), key.address(settings.network))}</p>
+            }
+            { for summary.as_ref().map(|summary| signing_summary(summary, unit)) }
+            <button onclick={sign_clicked}>{"Sign"}</button>
+            if let Some(signed) = &*signed {
+                <p>{signed.raw.clone()}</p>
+                { qr_codes(&signed.raw) }
+                if !signed.unsigned_inputs.is_empty() {
+                    <p>{format!("Not signed: {}", signed.unsigned_inputs
+                        .iter()
+                        .map(|(tx_hash, index)| format!("{tx_hash}:{index}"))
+                        .collect::<Vec<_>>()
+                        .join(", "))}</p>
+                }
+            }
+        </>
+    }
+}
+
+/// Lines listing what a transaction pays, shown before signing it.
+fn summary_lines(summary: &SigningSummary, unit: Unit) -> Vec<String> {
+    let fee = match summary.fee {
+        Some(fee) => format!("Fee: {}", fee.display_in(unit)),
+        None => "Fee: unknown, not every spent coin is known".to_owned(),
+    };
+    summary
+        .outputs
+        .iter()
+        .map(|(payee, amount)| format!("Pays {} to {payee}", amount.display_in(unit)))
+        .chain([fee])
+        .collect()
+}
+
+fn summary_text(summary: &SigningSummary, unit: Unit) -> String {
+    summary_lines(summary, unit).join("\n")
+}
+
+fn signing_summary(summary: &SigningSummary, unit: Unit) -> Html {
+    summary_lines(summary, unit)
+        .into_iter()
+        .map(|line| html! { <p>{line}</p> })
+        .collect()
+}
+
+/// The transaction as QR codes, so an online device can scan and broadcast it.
+fn qr_codes(raw: &str) -> Html {
+    qr::split_chunks(raw)
+        .iter()
+        .map(|chunk| match qr::to_svg(chunk) {
+            Ok(svg) => {
+                let src = format!("data:image/svg+xml,{}", js_sys::encode_uri_component(&svg));
+                html! { <img {src} alt={chunk.clone()}/> }
+            }
+            Err(error) => html! { <p>{format!("Unable to render QR code: {error}")}</p> },
+        })
+        .collect()
+}
+
+#[derive(Properties, PartialEq)]
+struct BroadcastChunksProps {
+    settings: Settings,
+}
+
+/// Broadcasts a transaction scanned from QR codes, one chunk per line.
+#[function_component(BroadcastChunks)]
+fn broadcast_chunks(BroadcastChunksProps { settings }: &BroadcastChunksProps) -> Html {
+    let chunks = use_state(String::default);
+
+    let set_chunks = {
+        let chunks = chunks.clone();
+        move |e: InputEvent| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            chunks.set(input.value());
+        }
+    };
+
+    let settings = settings.clone();
+    let broadcast_clicked = move |_| {
+        let lines: Vec<_> = chunks.lines().filter(|l| !l.trim().is_empty()).collect();
+        let transaction = qr::reassemble(&lines).and_then(|raw| Ok(Transaction::from_hex(&raw)?));
+        let transaction = match transaction {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                alert(&format!("Unable to read transaction: {error}"));
+                return;
+            }
+        };
+        let settings = settings.clone();
+        spawn_local(async move {
+            match transactions::publish_transaction(
+                &transaction,
+                &settings,
+                settings.request_timeout_ms,
+            )
+            .await
+            {
+                Ok(_) => alert(&format!("Broadcast {}", transaction.txid())),
+                Err(error) => alert(&format!("Unable to publish transaction: {error:?}")),
+            }
+        })
+    };
+
+    html! {
+        <>
+            <label for="scanned_chunks">{"Scanned transaction:"}</label>
+            <textarea id="scanned_chunks" oninput={set_chunks}/>
+            <button onclick={broadcast_clicked}>{"Broadcast"}</button>
+        </>
+    }
+}
+
+/// Signing keys by the hash of their public key.
+type AddressKeys = HashMap<[u8; 20], (SecretKey, PublicKey)>;
+
+#[derive(Properties, PartialEq)]
+struct CoSignProps {
+    settings: Settings,
+    /// The wallet's coins, to check the amounts the inputs claim.
+    outputs: Vec<RichOutput>,
+    /// The wallet's keys among the given public key hashes.
+    key_fetcher: Callback<Vec<[u8; 20]>, AddressKeys>,
+}
+
+/// Adds this wallet's signatures to a partially signed transaction and
+/// broadcasts it once complete.
+#[function_component(CoSign)]
+fn co_sign(
+    CoSignProps {
+        settings,
+        outputs,
+        key_fetcher,
+    }: &CoSignProps,
+) -> Html {
+    let json = use_state(String::default);
+    let signed = use_state(|| None::<PartialTransaction>);
+
+    let set_json = {
+        let json = json.clone();
+        move |e: InputEvent| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            json.set(input.value());
+        }
+    };
+
+    let summary = PartialTransaction::from_json(&json)
+        .and_then(|partial| partial.signing_summary(settings.network))
+        .ok();
+    let unit = settings.unit;
+
+    let sign_clicked = {
+        let outputs = outputs.clone();
+        let key_fetcher = key_fetcher.clone();
+        let signed = signed.clone();
+        let summary = summary.as_ref().map(|summary| summary_text(summary, unit));
+        move |_| {
+            let Some(summary) = &summary else {
+                alert("Not a valid partially signed transaction");
+                return;
+            };
+            if !confirm(&format!("Co-sign this transaction?\n{summary}")) {
+                return;
+            }
+            let result = PartialTransaction::from_json(&json).and_then(|mut partial| {
+                partial.check_amounts(&sending::previous_outputs(&outputs)?)?;
+                let keys = key_fetcher.emit(partial.key_hashes()?);
+                let added = partial.sign_available(&keys)?;
+                Ok((partial, added))
+            });
+            match result {
+                Ok((_, 0)) => alert("This wallet holds none of the missing keys"),
+                Ok((partial, _)) => signed.set(Some(partial)),
+                Err(error) => alert(&format!("Unable to sign transaction: {error}")),
+            }
+        }
+    };
+
+    let content = signed.as_ref().map(|partial| match partial.finalize() {
+        Ok(transaction) => {
+            let settings = settings.clone();
+            let publish = move |_| {
+                let transaction = transaction.clone();
+                let settings = settings.clone();
+                spawn_local(async move {
+                    if let Err(error) = transactions::publish_transaction(
+                        &transaction,
+                        &settings,
+                        settings.request_timeout_ms,
+                    )
+                    .await
+                    {
+                        alert(&format!("Unable to publish transaction: {error:?}"));
+                    }
+                })
+            };
+            html! { <button onclick={publish}>{"Broadcast"}</button> }
+        }
+        Err(error) => html! {
+            <>
+                <p>{format!("Still incomplete: {error}")}</p>
+                <p>{partial.to_json().unwrap_or_default()}</p>
+            </>
+        },
+    });
+
+    html! {
+        <>
+            <label for="partial_transaction">{"Partially signed transaction:"}</label>
+            <textarea id="partial_transaction" oninput={set_json}/>
+            { for summary.as_ref().map(|summary| signing_summary(summary, unit)) }
+            <button onclick={sign_clicked}>{"Co-sign"}</button>
+            { for content }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ConsolidateProps {
+    settings: Settings,
+    fee_quote: FeeQuote,
+    outputs: Vec<RichOutput>,
+    change_address: String,
+    keys: ChainKeys,
+    on_signed: Callback<SignedTransaction>,
+}
+
+#[function_component(Consolidate)]
+fn consolidate(
+    ConsolidateProps {
+        settings,
+        fee_quote,
+        outputs,
+        change_address,
+        keys,
+        on_signed,
+    }: &ConsolidateProps,
+) -> Html {
+    let unit = settings.unit;
+    let threshold = use_state(|| None);
+
+    let set_threshold = {
+        let threshold = threshold.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            threshold.set(Satoshis::parse_in(&input.value(), unit).ok());
+        }
+    };
+
+    let consolidate_clicked = {
+        let settings = settings.clone();
+        let fee_quote = fee_quote.clone();
+        let outputs = outputs.clone();
+        let change_address = change_address.clone();
+        let keys = keys.clone();
+        let on_signed = on_signed.clone();
+        move |_| {
+            let consolidated = sending::consolidate(
+                &outputs,
+                *threshold,
+                &change_address,
+                settings.network,
+                fee_quote.rate(FeeTier::default()),
+            );
+            let (transaction, fee) = match consolidated {
+                Ok(result) => result,
+                Err(error) => {
+                    alert(&format!("Unable to consolidate: {error}"));
+                    return;
+                }
+            };
+            let message = format!(
+                "Consolidate {} outputs for a fee of {}?",
+                transaction.input_count(),
+                fee.display_in(settings.unit),
+            );
+            if !confirm(&message) {
+                return;
+            }
+            sign_and_publish(
+                transaction,
+                &settings,
+                &outputs,
+                &keys.signing_keys(&outputs),
+                SigHashType::default(),
+                &on_signed,
+            );
+        }
+    };
+
+    html! {
+        <>
+            <label for="consolidate_threshold">{format!("Consolidate outputs below ({}, empty for all):", unit.name())}</label>
+            <input id="consolidate_threshold" inputmode="decimal" oninput={set_threshold}/>
+            <button onclick={consolidate_clicked}>{"Consolidate"}</button>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct SettingsFormProps {
+    settings: Settings,
+    on_save: Callback<Settings>,
+}
+
+#[function_component(SettingsForm)]
+fn settings_form(SettingsFormProps { settings, on_save }: &SettingsFormProps) -> Html {
+    let draft = use_state(|| settings.clone());
+    {
+        // Settings loaded after the form was first shown replace the draft
+        let draft = draft.clone();
+        use_effect_with_deps(
+            move |settings| {
+                draft.set(settings.clone());
+                || ()
+            },
+            settings.clone(),
+        );
+    }
+
+    let update = |apply: fn(&mut Settings, String) -> Option<()>| {
+        let draft = draft.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut value = (*draft).clone();
+            if apply(&mut value, input.value()).is_some() {
+                draft.set(value);
+            }
+        }
+    };
+    let set_gap_limit = update(|s, v| v.parse().map(|v| s.gap_limit = v).ok());
+    let set_fee_rate = update(|s, v| v.parse().map(|v| s.fee_rate = v).ok());
+    let set_endpoint = update(|s, v| {
+        s.endpoint = v;
+        Some(())
+    });
+    let set_sync_interval = update(|s, v| v.parse().map(|v| s.sync_interval_ms = v).ok());
+    let set_request_timeout = update(|s, v| v.parse().map(|v| s.request_timeout_ms = v).ok());
+    let set_shuffle_outputs = {
+        let draft = draft.clone();
+        move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            draft.set(Settings {
+                shuffle_outputs: input.checked(),
+                ..(*draft).clone()
+            });
+        }
+    };
+    let set_unit = {
+        let draft = draft.clone();
+        move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(unit) = select.value().parse() {
+                draft.set(Settings {
+                    unit,
+                    ..(*draft).clone()
+                });
+            }
+        }
+    };
+    let units: Vec<_> = Unit::ALL
+        .iter()
+        .map(|option| {
+            html! {
+                <option value={option.name()} selected={*option == draft.unit}>{option.name()}</option>
+            }
+        })
+        .collect();
+    let set_network = {
+        let draft = draft.clone();
+        move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            let Ok(network) = select.value().parse() else {
+                return;
+            };
+            // A default endpoint follows the network, a custom one is kept
+            let endpoint = if draft.endpoint == default_endpoint(draft.network) {
+                default_endpoint(network).to_owned()
+            } else {
+                draft.endpoint.clone()
+            };
+            draft.set(Settings {
+                network,
+                endpoint,
+                ..(*draft).clone()
+            });
+        }
+    };
+    let networks: Vec<_> = Network::ALL
+        .iter()
+        .map(|option| {
+            html! {
+                <option value={option.to_string()} selected={*option == draft.network}>{option.to_string()}</option>
+            }
+        })
+        .collect();
+    let set_announce_origin = update(|s, v| {
+        let origin = v.trim().trim_end_matches('/');
+        s.announce_origin = (!origin.is_empty()).then(|| origin.to_owned());
+        Some(())
+    });
+    let set_scan_to_index = update(|s, v| {
+        s.scan_to_index = match v.trim() {
+            "" => None,
+            v => Some(v.parse().ok()?),
+        };
+        Some(())
+    });
+
+    let config_href = WalletConfig::new(settings.clone())
+        .to_json()
+        .map(|json| {
+            format!(
+                "data:application/json,{}",
+                js_sys::encode_uri_component(&json)
+            )
+        })
+        .ok();
+    let restore_config = {
+        let draft = draft.clone();
+        let network = settings.network;
+        move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            let draft = draft.clone();
+            spawn_local(async move {
+                let text = match wasm_bindgen_futures::JsFuture::from(file.text()).await {
+                    Ok(text) => text.as_string().unwrap_or_default(),
+                    Err(error) => {
+                        alert(&format!("Unable to read settings: {error:?}"));
+                        return;
+                    }
+                };
+                match WalletConfig::from_json(&text, network) {
+                    Ok(settings) => draft.set(settings),
+                    Err(error) => alert(&format!("Unable to restore settings: {error}")),
+                }
+            });
+        }
+    };
+
+    let save = {
+        let draft = draft.clone();
+        let on_save = on_save.clone();
+        move |_| {
+            let settings = (*draft).clone();
+            if let Err(error) = settings.validate() {
+                alert(&format!("Can't save settings: {error}"));
+                return;
+            }
+            let on_save = on_save.clone();
+            spawn_local(async move {
+                match settings.save().await {
+                    Ok(()) => on_save.emit(settings),
+                    Err(error) => alert(&format!("Unable to save settings: {error:?}")),
+                }
+            });
+        }
+    };
+
+    html! {
+        <>
+            <p>{"Settings"}</p>
+            <label for="gap_limit">{"Gap limit:"}</label>
+            <input id="gap_limit" type="number" min="1" max="100" value={draft.gap_limit.to_string()} oninput={set_gap_limit}/>
+            <label for="fee_rate">{"Fallback fee rate (sat/byte):"}</label>
+            <input id="fee_rate" type="number" min="1" value={draft.fee_rate.to_string()} oninput={set_fee_rate}/>
+            <label for="network">{"Network:"}</label>
+            <select id="network" onchange={set_network}>
+                { networks }
+            </select>
+            <label for="endpoint">{"API endpoint:"}</label>
+            <input id="endpoint" value={draft.endpoint.clone()} oninput={set_endpoint}/>
+            <label for="sync_interval">{"Sync interval (ms):"}</label>
+            <input id="sync_interval" type="number" min="1000" value={draft.sync_interval_ms.to_string()} oninput={set_sync_interval}/>
+            <label for="request_timeout">{"Request timeout (ms):"}</label>
+            <input id="request_timeout" type="number" min="1000" max="120000" value={draft.request_timeout_ms.to_string()} oninput={set_request_timeout}/>
+            <label for="scan_to_index">{"Always scan to index (optional):"}</label>
+            <input id="scan_to_index" type="number" min="0" max="10000" value={draft.scan_to_index.map(|i| i.to_string()).unwrap_or_default()} oninput={set_scan_to_index}/>
+            <label for="shuffle_outputs">{"Randomize change position:"}</label>
+            <input id="shuffle_outputs" type="checkbox" checked={draft.shuffle_outputs} onchange={set_shuffle_outputs}/>
+            <label for="unit">{"Unit:"}</label>
+            <select id="unit" onchange={set_unit}>
+                { units }
+            </select>
+            <label for="announce_origin">{"Announce sends to pages of (https origin, optional):"}</label>
+            <input id="announce_origin" placeholder="https://example.com" value={draft.announce_origin.clone().unwrap_or_default()} oninput={set_announce_origin}/>
+            <button onclick={save}>{"Save settings"}</button>
+            <a href={config_href} download={CONFIG_FILE_NAME}>{"Backup settings"}</a>
+            <label for="restore_settings">{"Restore settings:"}</label>
+            <input id="restore_settings" type="file" accept=".json" onchange={restore_config}/>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ExportBackupProps {
+    xprv: XPrv,
+}
+
+/// Offers the wallet as a password protected file to download.
+#[function_component(ExportBackup)]
+fn export_backup(ExportBackupProps { xprv }: &ExportBackupProps) -> Html {
+    let password = use_state(String::default);
+    let set_password = {
+        let password = password.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        }
+    };
+    let confirmation = use_state(String::default);
+    let set_confirmation = {
+        let confirmation = confirmation.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            confirmation.set(input.value());
+        }
+    };
+
+    let href = use_state(|| None::<String>);
+    let export_clicked = {
+        let xprv = xprv.clone();
+        let href = href.clone();
+        move |_| {
+            let exported = encryption::validate_password(&password, &confirmation)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| backup::export(&xprv, &password));
+            match exported {
+                Ok(file) => href.set(Some(format!(
+                    "data:application/octet-stream,{}",
+                    js_sys::encode_uri_component(&file)
+                ))),
+                Err(error) => alert(&format!("Unable to export backup: {error}")),
+            }
+        }
+    };
+
+    html! {
+        <>
+            <p>{"Encrypted backup"}</p>
+            <label for="backup_password">{"Password:"}</label>
+            <input id="backup_password" type="password" oninput={set_password}/>
+            <label for="backup_confirmation">{"Confirm password:"}</label>
+            <input id="backup_confirmation" type="password" oninput={set_confirmation}/>
+            <button onclick={export_clicked}>{"Create backup"}</button>
+            if let Some(href) = &*href {
+                <a href={href.clone()} download={backup::BACKUP_FILE_NAME}>{"Download backup"}</a>
+            }
         </>
     }
 }
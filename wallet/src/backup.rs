@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{bip32::XPrv, encryption::EncryptedData};
+
+const BACKUP_FORMAT: &str = "beesv-backup";
+/// Bumped whenever the encrypted contents change incompatibly.
+const BACKUP_VERSION: u32 = 1;
+pub const BACKUP_FILE_NAME: &str = "wallet.beesv";
+
+#[derive(Debug, Error)]
+enum BackupError {
+    #[error("Not a BeeSV backup file")]
+    NotABackup,
+    #[error("Backup version {0} is newer than this wallet supports")]
+    UnsupportedVersion(u32),
+}
+
+/// The header stays readable so that the format and version can be checked
+/// before asking for a password.
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    data: EncryptedData,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupContents {
+    xprv: String,
+}
+
+/// Serializes the wallet into the contents of an encrypted backup file.
+pub fn export(xprv: &XPrv, password: &str) -> Result<String> {
+    let contents = serde_json::to_vec(&BackupContents {
+        xprv: String::from(xprv),
+    })?;
+    let file = BackupFile {
+        format: BACKUP_FORMAT.to_owned(),
+        version: BACKUP_VERSION,
+        data: EncryptedData::encrypt(&contents, password)?,
+    };
+    Ok(serde_json::to_string(&file)?)
+}
+
+/// Restores the wallet from the contents of a backup file.
+pub fn import(file: &str, password: &str) -> Result<XPrv> {
+    let file: BackupFile = serde_json::from_str(file).map_err(|_| BackupError::NotABackup)?;
+    if file.format != BACKUP_FORMAT {
+        return Err(BackupError::NotABackup.into());
+    }
+    if file.version > BACKUP_VERSION {
+        return Err(BackupError::UnsupportedVersion(file.version).into());
+    }
+
+    let contents: BackupContents = serde_json::from_slice(&file.data.decrypt(password)?)?;
+    Ok(XPrv::from_str(&contents.xprv)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encryption::EncryptionError;
+
+    use super::*;
+
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    #[test]
+    fn backup_round_trips() -> Result<()> {
+        let xprv: XPrv = XPRV.parse()?;
+
+        let file = export(&xprv, "hunter2")?;
+
+        assert_eq!(XPRV, String::from(&import(&file, "hunter2")?));
+        Ok(())
+    }
+
+    #[test]
+    fn import_rejects_wrong_password_and_newer_versions() -> Result<()> {
+        let file = export(&XPRV.parse()?, "hunter2")?;
+
+        let error = import(&file, "hunter3").unwrap_err();
+        assert_eq!(
+            Some(&EncryptionError::WrongPassword),
+            error.downcast_ref::<EncryptionError>()
+        );
+
+        let newer = file.replace(r#""version":1"#, r#""version":2"#);
+        let error = import(&newer, "hunter2").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BackupError>(),
+            Some(BackupError::UnsupportedVersion(2))
+        ));
+        assert!(import("{}", "hunter2").is_err());
+        Ok(())
+    }
+}
@@ -3,31 +3,42 @@ use std::str::FromStr;
 use anyhow::Result;
 use hmac::{Hmac, Mac};
 use regex::Regex;
-use secp256k1::{PublicKey, SecretKey};
+use secp256k1::{PublicKey, Scalar, SecretKey, SECP256K1};
 use sha2::Sha512;
 use thiserror::Error;
 
 use crate::util::double_sha256;
-use crate::util::ripemd160;
+use crate::util::hash160;
 use crate::util::sha256;
+use crate::util::to_address;
+use crate::util::Network;
 
 const HARDENED_INDEX: u32 = 0x80000000;
 
 #[derive(Debug, Error)]
-enum Bip32Error {
+pub enum Bip32Error {
     #[error("Invalid derivation path")]
     InvalidDerivationPath,
     #[error("Cannot derive hardened key from public")]
     PublicHardenedDerivation,
     #[error("Checksum mismatch")]
     ChecksumMismatch,
+    #[error("Invalid extended key length")]
+    InvalidLength,
+    #[error("Child index {0} out of range")]
+    IndexOutOfRange(u32),
+    #[error("Invalid base58: {0}")]
+    InvalidBase58(#[from] bs58::decode::Error),
+    #[error("Invalid key: {0}")]
+    InvalidKey(#[from] secp256k1::Error),
 }
 
 pub trait DerivePath<T> {
-    fn parse_path(path: &str) -> Result<Vec<u32>> {
-        let path_regex = Regex::new(r"^m(/\d+'?)+$")?;
+    /// The child indices of `path`, none for the bare master path `m`.
+    fn parse_path(path: &str) -> Result<Vec<u32>, Bip32Error> {
+        let path_regex = Regex::new(r"^m(/\d+'?)*$").expect("Path pattern should be valid");
         if !path_regex.is_match(path) {
-            return Err(Bip32Error::InvalidDerivationPath.into());
+            return Err(Bip32Error::InvalidDerivationPath);
         }
         Ok(path
             .split('/')
@@ -39,7 +50,7 @@ pub trait DerivePath<T> {
             .collect())
     }
 
-    fn derive_path(&self, path: &str) -> Result<T>;
+    fn derive_path(&self, path: &str) -> Result<T, Bip32Error>;
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -52,17 +63,7 @@ pub struct XPrv {
 }
 
 impl XPrv {
-    pub fn empty() -> Self {
-        Self {
-            depth: 0,
-            child_number: 0,
-            parent_fingerprint: [0u8; 4],
-            key: SecretKey::from_slice(&[0xCD; 32]).unwrap(),
-            chain_code: [0u8; 32],
-        }
-    }
-
-    pub fn new(key: [u8; 32], chain_code: [u8; 32]) -> Result<Self> {
+    pub fn new(key: [u8; 32], chain_code: [u8; 32]) -> Result<Self, Bip32Error> {
         Ok(Self {
             depth: 0,
             child_number: 0,
@@ -72,7 +73,54 @@ impl XPrv {
         })
     }
 
-    pub fn derive(&self, index: u32) -> XPrv {
+    /// Derives the hardened child `index'`, i.e. `index + 2³¹`.
+    pub fn derive_hardened(&self, index: u32) -> Result<XPrv, Bip32Error> {
+        if index >= HARDENED_INDEX {
+            return Err(Bip32Error::IndexOutOfRange(index));
+        }
+        Ok(self.derive(HARDENED_INDEX + index))
+    }
+
+    pub fn derive_normal(&self, index: u32) -> Result<XPrv, Bip32Error> {
+        if index >= HARDENED_INDEX {
+            return Err(Bip32Error::IndexOutOfRange(index));
+        }
+        Ok(self.derive(index))
+    }
+
+    /// Lazily derives the keys `chain/0`, `chain/1`, ... below this key.
+    pub fn key_iter(&self, chain: u32) -> impl Iterator<Item = Result<XPrv, Bip32Error>> {
+        let (chain_key, error) = match self.derive_normal(chain) {
+            Ok(chain_key) => (Some(chain_key), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        error.map(Err).into_iter().chain(
+            chain_key
+                .into_iter()
+                .flat_map(|key| (0..HARDENED_INDEX).map(move |i| Ok(key.derive(i)))),
+        )
+    }
+
+    /// Lazily derives the addresses of the keys `chain/0`, `chain/1`, ...
+    pub fn address_iter(
+        &self,
+        chain: u32,
+        network: Network,
+    ) -> impl Iterator<Item = Result<String, Bip32Error>> {
+        self.key_iter(chain)
+            .map(move |key| key.map(|key| key.derive_public().to_address(network)))
+    }
+
+    /// The child at `index`, or per BIP32 at the next index if that child's
+    /// key is invalid, which happens with a probability below 2⁻¹²⁷.
+    fn derive(&self, index: u32) -> XPrv {
+        (index..)
+            .find_map(|index| self.try_derive(index))
+            .expect("Some index should give a valid key")
+    }
+
+    fn try_derive(&self, index: u32) -> Option<XPrv> {
         let mut hmac = Hmac::<Sha512>::new_from_slice(&self.chain_code).expect("Size is fixed");
 
         // >= 2³¹ indicates hardned keys
@@ -90,21 +138,16 @@ impl XPrv {
         }
         let i = hmac.finalize().into_bytes();
 
-        let secret = SecretKey::from_slice(&i[..32]).expect("Secret key could not be constructed");
-        let secret = secret
-            .add_tweak(&self.key.into())
-            .expect("Tweak must always be correct");
-
         let chain_code = i[32..]
             .try_into()
             .expect("Fixed size should always succeed");
-        XPrv {
+        Some(XPrv {
             depth: self.depth + 1,
             child_number: index,
             parent_fingerprint: self.fingerprint(),
-            key: secret,
+            key: child_secret_key(&self.key, &i[..32])?,
             chain_code,
-        }
+        })
     }
 
     pub fn derive_public(&self) -> XPub {
@@ -126,21 +169,19 @@ impl XPrv {
     fn fingerprint(&self) -> [u8; 4] {
         let public_key = PublicKey::from_secret_key_global(&self.key);
 
-        let sha = sha256(&public_key.serialize());
-        let ripemd = ripemd160(&sha);
-        ripemd[..4].try_into().expect("Should always succeed")
+        hash160(&public_key.serialize())[..4]
+            .try_into()
+            .expect("Should always succeed")
     }
 }
 
 impl DerivePath<XPrv> for XPrv {
-    fn derive_path(&self, path: &str) -> Result<XPrv> {
+    fn derive_path(&self, path: &str) -> Result<XPrv, Bip32Error> {
         let path = Self::parse_path(path)?;
 
-        let mut key = self.derive(path[0]);
-        for item in path.iter().skip(1) {
-            key = key.derive(*item);
-        }
-        Ok(key)
+        Ok(path
+            .iter()
+            .fold(self.clone(), |key, &index| key.derive(index)))
     }
 }
 
@@ -162,29 +203,47 @@ impl From<&XPrv> for String {
     }
 }
 
-impl FromStr for XPrv {
-    type Err = anyhow::Error;
+/// Decodes a base58 extended key and verifies its checksum. Whitespace is
+/// ignored, pasted keys often carry newlines from wherever they were copied.
+fn decode_extended_key(s: &str) -> Result<[u8; 82], Bip32Error> {
+    let key: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let decoded: [u8; 82] = bs58::decode(key)
+        .into_vec()?
+        .try_into()
+        .map_err(|_| Bip32Error::InvalidLength)?;
+
+    let checksum = sha256(&sha256(&decoded[..78]));
+
+    if decoded[78..] != checksum[..4] {
+        return Err(Bip32Error::ChecksumMismatch);
+    }
+    Ok(decoded)
+}
 
-    fn from_str(s: &str) -> Result<Self> {
-        let decoded = bs58::decode(s).into_vec()?;
+/// The `N` bytes of a decoded extended key from `start` on.
+fn split<const N: usize>(bytes: &[u8], start: usize) -> [u8; N] {
+    bytes[start..start + N]
+        .try_into()
+        .expect("Extended keys have a fixed length")
+}
 
-        let checksum = sha256(&sha256(&decoded[..78]));
+impl FromStr for XPrv {
+    type Err = Bip32Error;
 
-        if decoded[78..] != checksum[..4] {
-            return Err(Bip32Error::ChecksumMismatch.into());
-        }
+    fn from_str(s: &str) -> Result<Self, Bip32Error> {
+        let decoded = decode_extended_key(s)?;
 
         Ok(XPrv {
             depth: decoded[4],
-            child_number: u32::from_be_bytes(decoded[9..13].try_into()?),
-            parent_fingerprint: decoded[5..9].try_into()?,
+            child_number: u32::from_be_bytes(split(&decoded, 9)),
+            parent_fingerprint: split(&decoded, 5),
             key: SecretKey::from_slice(&decoded[46..78])?,
-            chain_code: decoded[13..45].try_into()?,
+            chain_code: split(&decoded, 13),
         })
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct XPub {
     depth: u8,
     child_number: u32,
@@ -195,83 +254,110 @@ pub struct XPub {
 
 impl XPub {
     fn fingerprint(&self) -> [u8; 4] {
-        let sha = sha256(&self.public_key.serialize());
-        let ripemd = ripemd160(&sha);
-        ripemd[..4].try_into().expect("Should always succeed")
+        hash160(&self.public_key_bytes())[..4]
+            .try_into()
+            .expect("Should always succeed")
     }
 
-    pub fn derive(&self, index: u32) -> Result<XPub> {
+    /// The child at `index`, or the next valid one like [`XPrv::derive`].
+    pub fn derive(&self, index: u32) -> Result<XPub, Bip32Error> {
         if index >= HARDENED_INDEX {
-            return Err(Bip32Error::PublicHardenedDerivation.into());
+            return Err(Bip32Error::PublicHardenedDerivation);
         }
-        let mut hmac = Hmac::<Sha512>::new_from_slice(&self.chain_code)?;
+        (index..HARDENED_INDEX)
+            .find_map(|index| self.try_derive(index))
+            .ok_or(Bip32Error::IndexOutOfRange(index))
+    }
+
+    fn try_derive(&self, index: u32) -> Option<XPub> {
+        let mut hmac = Hmac::<Sha512>::new_from_slice(&self.chain_code).expect("Size is fixed");
         let serialized_point = self.public_key.serialize();
         hmac.update(&serialized_point);
         hmac.update(&index.to_be_bytes());
         let i = hmac.finalize().into_bytes();
 
-        let public_key = PublicKey::from_secret_key_global(&SecretKey::from_slice(&i[..32])?)
-            .combine(&self.public_key)?;
-
-        let chain_code = i[32..].try_into()?;
-
-        Ok(XPub {
+        let chain_code = i[32..]
+            .try_into()
+            .expect("Fixed size should always succeed");
+        Some(XPub {
             depth: self.depth + 1,
             child_number: index,
             parent_fingerprint: self.fingerprint(),
-            public_key,
+            public_key: child_public_key(&self.public_key, &i[..32])?,
             chain_code,
         })
     }
 
-    pub fn to_address(&self) -> String {
-        let serialized_key = self.public_key.serialize();
-        let hashed = ripemd160(&sha256(&serialized_key));
-        let mut prefixed = Vec::with_capacity(21);
-        prefixed.push(0x00);
-        prefixed.extend(&hashed);
+    /// Lazily derives the keys `chain/0`, `chain/1`, ... without the private
+    /// key, which makes scanning cheaper than through [`XPrv::key_iter`].
+    pub fn key_iter(&self, chain: u32) -> impl Iterator<Item = Result<XPub, Bip32Error>> {
+        let (chain_key, error) = match self.derive(chain) {
+            Ok(chain_key) => (Some(chain_key), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        error.map(Err).into_iter().chain(
+            chain_key
+                .into_iter()
+                .flat_map(|key| (0..HARDENED_INDEX).map(move |i| key.derive(i))),
+        )
+    }
+
+    pub fn address_iter(
+        &self,
+        chain: u32,
+        network: Network,
+    ) -> impl Iterator<Item = Result<String, Bip32Error>> {
+        self.key_iter(chain)
+            .map(move |key| key.map(|key| key.to_address(network)))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
 
-        let checksum = sha256(&sha256(&prefixed));
-        prefixed.extend(&checksum[..4]);
+    /// The compressed public key, the form addresses are hashed from.
+    pub fn public_key_bytes(&self) -> [u8; 33] {
+        self.public_key.serialize()
+    }
 
-        bs58::encode(prefixed).into_string()
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key_bytes())
+    }
+
+    pub fn to_address(&self, network: Network) -> String {
+        to_address(hash160(&self.public_key_bytes()), network)
     }
 }
 
 impl DerivePath<XPub> for XPub {
-    fn derive_path(&self, path: &str) -> Result<XPub> {
+    fn derive_path(&self, path: &str) -> Result<XPub, Bip32Error> {
         let path = Self::parse_path(path)?;
 
         if path.iter().any(|i| *i >= HARDENED_INDEX) {
-            return Err(Bip32Error::PublicHardenedDerivation.into());
+            return Err(Bip32Error::PublicHardenedDerivation);
         }
 
-        let mut key = self.derive(path[0])?;
-        for item in path.iter().skip(1) {
-            key = key.derive(*item)?;
+        let mut key = self.clone();
+        for index in path {
+            key = key.derive(index)?;
         }
         Ok(key)
     }
 }
 
 impl FromStr for XPub {
-    type Err = anyhow::Error;
+    type Err = Bip32Error;
 
-    fn from_str(s: &str) -> Result<Self> {
-        let decoded = bs58::decode(s).into_vec()?;
-
-        let checksum = sha256(&sha256(&decoded[..78]));
-
-        if decoded[78..] != checksum[..4] {
-            return Err(Bip32Error::ChecksumMismatch.into());
-        }
+    fn from_str(s: &str) -> Result<Self, Bip32Error> {
+        let decoded = decode_extended_key(s)?;
 
         Ok(XPub {
             depth: decoded[4],
-            child_number: u32::from_be_bytes(decoded[9..13].try_into()?),
-            parent_fingerprint: decoded[5..9].try_into()?,
+            child_number: u32::from_be_bytes(split(&decoded, 9)),
+            parent_fingerprint: split(&decoded, 5),
             public_key: PublicKey::from_slice(&decoded[45..78])?,
-            chain_code: decoded[13..45].try_into()?,
+            chain_code: split(&decoded, 13),
         })
     }
 }
@@ -294,13 +380,62 @@ impl From<&XPub> for String {
     }
 }
 
+/// Key a wallet derives its addresses from. Watch-only wallets know only the
+/// public key, so they can sync but not sign.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalletKey {
+    Private(XPrv),
+    WatchOnly(XPub),
+}
+
+impl WalletKey {
+    pub fn derive_normal(&self, index: u32) -> Result<WalletKey, Bip32Error> {
+        Ok(match self {
+            WalletKey::Private(xprv) => WalletKey::Private(xprv.derive_normal(index)?),
+            WalletKey::WatchOnly(xpub) => WalletKey::WatchOnly(xpub.derive(index)?),
+        })
+    }
+
+    pub fn derive_public(&self) -> XPub {
+        match self {
+            WalletKey::Private(xprv) => xprv.derive_public(),
+            WalletKey::WatchOnly(xpub) => xpub.clone(),
+        }
+    }
+
+    /// The private key, `None` for watch-only wallets.
+    pub fn private(&self) -> Option<&XPrv> {
+        match self {
+            WalletKey::Private(xprv) => Some(xprv),
+            WalletKey::WatchOnly(_) => None,
+        }
+    }
+}
+
+/// `parent + tweak`, `None` if the tweak is not below the curve order or the
+/// sum is zero, either making the child invalid.
+fn child_secret_key(parent: &SecretKey, tweak: &[u8]) -> Option<SecretKey> {
+    let tweak = Scalar::from_be_bytes(tweak.try_into().ok()?).ok()?;
+    parent.add_tweak(&tweak).ok()
+}
+
+/// `parent + tweak·G`, `None` if the tweak is not below the curve order or
+/// the sum is the point at infinity.
+fn child_public_key(parent: &PublicKey, tweak: &[u8]) -> Option<PublicKey> {
+    let tweak = Scalar::from_be_bytes(tweak.try_into().ok()?).ok()?;
+    parent.add_exp_tweak(SECP256K1, &tweak).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use secp256k1::{PublicKey, SecretKey};
 
     use crate::bip32::DerivePath;
 
-    use super::{XPrv, XPub, HARDENED_INDEX};
+    use super::{
+        child_public_key, child_secret_key, Bip32Error, Network, XPrv, XPub, HARDENED_INDEX,
+    };
 
     #[test]
     fn derive_hardened_returns_correct() -> Result<()> {
@@ -318,6 +453,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn derive_hardened_adds_offset() -> Result<()> {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let key: XPrv = xprv.parse()?;
+
+        assert_eq!(key.derive(HARDENED_INDEX), key.derive_hardened(0)?);
+        assert_eq!(key.derive(5), key.derive_normal(5)?);
+        assert!(key.derive_hardened(HARDENED_INDEX).is_err());
+        assert!(key.derive_normal(HARDENED_INDEX).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn address_iter_matches_direct_derivation() -> Result<()> {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let key: XPrv = xprv.parse()?;
+
+        let addresses = key
+            .address_iter(1, Network::Mainnet)
+            .take(25)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(25, addresses.len());
+        for (i, address) in addresses.iter().enumerate() {
+            let direct = key.derive_normal(1)?.derive_normal(i as u32)?;
+            assert_eq!(
+                &direct.derive_public().to_address(Network::Mainnet),
+                address
+            );
+        }
+        assert!(key
+            .address_iter(HARDENED_INDEX, Network::Mainnet)
+            .next()
+            .unwrap()
+            .is_err());
+
+        let public = key
+            .derive_public()
+            .address_iter(1, Network::Mainnet)
+            .take(25);
+        assert_eq!(addresses, public.collect::<Result<Vec<_>, _>>()?);
+        Ok(())
+    }
+
     #[test]
     fn derive_private_returns_correct() -> Result<()> {
         let xprv = "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7";
@@ -371,7 +551,7 @@ mod tests {
         struct Dummy;
 
         impl DerivePath<Dummy> for Dummy {
-            fn derive_path(&self, _: &str) -> Result<Dummy> {
+            fn derive_path(&self, _: &str) -> Result<Dummy, Bip32Error> {
                 Ok(Dummy)
             }
         }
@@ -388,7 +568,7 @@ mod tests {
         struct Dummy;
 
         impl DerivePath<Dummy> for Dummy {
-            fn derive_path(&self, _: &str) -> Result<Dummy> {
+            fn derive_path(&self, _: &str) -> Result<Dummy, Bip32Error> {
                 Ok(Dummy)
             }
         }
@@ -408,6 +588,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn derive_master_and_single_segment_paths() -> Result<()> {
+        let xprv: XPrv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".parse()?;
+        let xpub = xprv.derive_public();
+
+        assert_eq!(xprv, xprv.derive_path("m")?);
+        assert_eq!(
+            xpub.to_address(Network::Mainnet),
+            xpub.derive_path("m")?.to_address(Network::Mainnet)
+        );
+        assert_eq!(xprv.derive(HARDENED_INDEX), xprv.derive_path("m/0'")?);
+        assert_eq!(
+            xpub.derive(0)?.to_address(Network::Mainnet),
+            xpub.derive_path("m/0")?.to_address(Network::Mainnet)
+        );
+        assert!(xprv.derive_path("").is_err());
+        assert!(xprv.derive_path("m/").is_err());
+        Ok(())
+    }
+
     #[test]
     fn derive_by_path() -> Result<()> {
         let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
@@ -438,13 +638,11 @@ mod tests {
         let path = "m/0'/0/0";
         let result = key.derive_path(path)?.derive_public();
 
-        let serialized_public = result.public_key.serialize();
-        assert_eq!(
-            "027b6a7dd645507d775215a9035be06700e1ed8c541da9351b4bd14bd50ab61428",
-            hex::encode(serialized_public)
-        );
+        let public_key = "027b6a7dd645507d775215a9035be06700e1ed8c541da9351b4bd14bd50ab61428";
+        assert_eq!(public_key, result.public_key_hex());
+        assert_eq!(hex::decode(public_key)?, result.public_key_bytes());
 
-        let address = result.to_address();
+        let address = result.to_address(Network::Mainnet);
         assert_eq!("1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr", address);
 
         Ok(())
@@ -465,4 +663,43 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn invalid_child_keys_are_rejected() -> Result<()> {
+        let parent = SecretKey::from_slice(&[0x0c; 32])?;
+        let parent_public = PublicKey::from_secret_key_global(&parent);
+
+        let tweak = [0x21; 32];
+        let child = child_secret_key(&parent, &tweak).unwrap();
+        assert_eq!(
+            Some(PublicKey::from_secret_key_global(&child)),
+            child_public_key(&parent_public, &tweak)
+        );
+
+        // At least the curve order
+        assert_eq!(None, child_secret_key(&parent, &[0xff; 32]));
+        assert_eq!(None, child_public_key(&parent_public, &[0xff; 32]));
+        // Cancels out the parent, giving zero and the point at infinity
+        let negated = parent.negate().secret_bytes();
+        assert_eq!(None, child_secret_key(&parent, &negated));
+        assert_eq!(None, child_public_key(&parent_public, &negated));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_ignores_surrounding_whitespace() -> Result<()> {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+        let key: XPrv = format!("{xprv}\n").parse()?;
+        assert_eq!(xprv, String::from(&key));
+        let key: XPrv = format!("{}\n{}", &xprv[..50], &xprv[50..]).parse()?;
+        assert_eq!(xprv, String::from(&key));
+
+        let key: XPub = format!("  {xpub}  ").parse()?;
+        assert_eq!(xpub, String::from(&key));
+
+        assert!(format!("{xprv} x").parse::<XPrv>().is_err());
+        Ok(())
+    }
 }
@@ -3,15 +3,105 @@ use std::str::FromStr;
 use anyhow::Result;
 use hmac::{Hmac, Mac};
 use pbkdf2::pbkdf2_hmac;
-use sha2::Sha512;
+use sha2::{Digest, Sha256, Sha512};
 use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
 
-use crate::bip32::XPrv;
+use crate::bip32::{Bip32Error, XPrv};
+
+/// Iterations mandated by BIP39, changing this derives different wallets.
+const BIP39_PBKDF2_ROUNDS: u32 = 2048;
+const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+/// Each word encodes this many bits of entropy and checksum.
+const BITS_PER_WORD: usize = 11;
+
+/// The English wordlist, one word per line in index order.
+pub const WORDS: &str = include_str!("english.txt");
+/// The Spanish wordlist, precomposed as published.
+const SPANISH_WORDS: &str = include_str!("spanish.txt");
 
 #[derive(Debug, Error)]
-enum Bip39Error {
+pub enum Bip39Error {
     #[error("Invalid size")]
     InvalidSize,
+    #[error("Invalid hex: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    #[error("A phrase has 12, 15, 18, 21 or 24 words, found {0}")]
+    WordCount(usize),
+    #[error("Words missing at positions {0:?}")]
+    EmptyWords(Vec<usize>),
+    #[error("Unrecognized word {0}")]
+    UnknownWord(String),
+    #[error("The phrase is invalid, check the words for typos")]
+    Checksum,
+}
+
+/// Language a phrase is entered in. Phrases in the BIP39 languages whose
+/// wordlist isn't bundled are derived as entered, without checking their
+/// words or checksum, which only happens when picked explicitly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+    Other,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::Spanish, Language::Other];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Spanish",
+            Language::Other => "Other (words not checked)",
+        }
+    }
+
+    /// The wordlist, one word per line, `None` where it isn't bundled.
+    pub fn words(&self) -> Option<&'static str> {
+        match self {
+            Language::English => Some(WORDS),
+            Language::Spanish => Some(SPANISH_WORDS),
+            Language::Other => None,
+        }
+    }
+
+    /// Whether `word`, as typed, is on the wordlist. Always false for
+    /// languages without one.
+    pub fn contains(&self, word: &str) -> bool {
+        let word = normalize_mnemonic(word);
+        self.words()
+            .is_some_and(|wordlist| word_index(wordlist, &word).is_some())
+    }
+
+    /// Spanish once every word entered so far is on its list and some are
+    /// not on the English one, English otherwise. Never a language whose
+    /// words aren't checked, so a typo can't turn the checks off.
+    pub fn detect(words: &[String]) -> Language {
+        let entered: Vec<_> = words
+            .iter()
+            .filter(|word| !word.trim().is_empty())
+            .collect();
+        let spanish = entered.iter().all(|word| Language::Spanish.contains(word))
+            && entered.iter().any(|word| !Language::English.contains(word));
+        if spanish {
+            Language::Spanish
+        } else {
+            Language::English
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        Language::ALL
+            .into_iter()
+            .find(|language| language.name() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown language: {value}"))
+    }
 }
 
 pub struct Seed {
@@ -19,45 +109,136 @@ pub struct Seed {
 }
 
 impl Seed {
+    /// Derives the seed of `mnemonic` after [`normalize_mnemonic`], so pasted
+    /// phrases with stray whitespace or capitals give the canonical seed.
     pub fn generate(mnemonic: &str, password: &str) -> Self {
-        let salt = format!("mnemonic{password}");
+        let mnemonic = normalize_mnemonic(mnemonic);
+        let salt = nfkd(&format!("mnemonic{password}"));
 
         let mut seed = [0u8; 64];
-        pbkdf2_hmac::<Sha512>(mnemonic.as_bytes(), salt.as_bytes(), 2048, &mut seed);
+        pbkdf2_hmac::<Sha512>(
+            mnemonic.as_bytes(),
+            salt.as_bytes(),
+            BIP39_PBKDF2_ROUNDS,
+            &mut seed,
+        );
 
         Self { seed }
     }
 
-    pub fn to_xprv(&self) -> Result<XPrv> {
+    pub fn to_xprv(&self) -> Result<XPrv, Bip32Error> {
         type HmacSha256 = Hmac<Sha512>;
-        let mut hmac = HmacSha256::new_from_slice(b"Bitcoin seed")?;
+        let mut hmac = HmacSha256::new_from_slice(b"Bitcoin seed").expect("Any key size is valid");
         hmac.update(&self.seed);
 
-        let seed = hmac.finalize().into_bytes();
+        let seed: [u8; 64] = hmac.finalize().into_bytes().into();
+        let (key, chain_code) = seed.split_at(32);
+
+        XPrv::new(
+            key.try_into().expect("Halves of the seed"),
+            chain_code.try_into().expect("Halves of the seed"),
+        )
+    }
+}
+
+/// Lowercases the words of `mnemonic` and joins them with single spaces, in
+/// the NFKD form BIP39 derives seeds from.
+pub fn normalize_mnemonic(mnemonic: &str) -> String {
+    nfkd(mnemonic)
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `text` in normalization form KD, the form BIP39 hashes phrases and
+/// passphrases in, so the same words typed as precomposed or combined
+/// characters give the same seed.
+fn nfkd(text: &str) -> String {
+    text.nfkd().collect()
+}
 
-        XPrv::new(seed[..32].try_into()?, seed[32..].try_into()?)
+/// Position of the normalized `word` in `wordlist`, whose words may be
+/// precomposed.
+fn word_index(wordlist: &str, word: &str) -> Option<usize> {
+    wordlist
+        .lines()
+        .position(|candidate| candidate.nfkd().eq(word.chars()))
+}
+
+/// Checks that `mnemonic` has as many words as a BIP39 phrase can.
+pub fn check_word_count(mnemonic: &str) -> Result<(), Bip39Error> {
+    let count = mnemonic.split_whitespace().count();
+    if !WORD_COUNTS.contains(&count) {
+        return Err(Bip39Error::WordCount(count));
     }
+    Ok(())
 }
 
 impl FromStr for Seed {
-    type Err = anyhow::Error;
+    type Err = Bip39Error;
 
-    fn from_str(value: &str) -> Result<Self> {
-        let seed = hex::decode(value)?;
-        let seed = match seed.try_into() {
-            Ok(seed) => seed,
-            Err(_) => return Err(Bip39Error::InvalidSize.into()),
-        };
+    fn from_str(value: &str) -> Result<Self, Bip39Error> {
+        let seed = hex::decode(value)?
+            .try_into()
+            .map_err(|_| Bip39Error::InvalidSize)?;
 
         Ok(Self { seed })
     }
 }
 
+/// Checks the words typed into the recovery grid, returning the phrase they
+/// make once no cell is blank and, where the wordlist of `language` is
+/// bundled, its checksum matches.
+pub fn check_phrase(words: &[String], language: Language) -> Result<String, Bip39Error> {
+    let empty: Vec<_> = words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| word.trim().is_empty())
+        .map(|(index, _)| index + 1)
+        .collect();
+    if !empty.is_empty() {
+        return Err(Bip39Error::EmptyWords(empty));
+    }
+    let mnemonic = normalize_mnemonic(&words.join(" "));
+    check_word_count(&mnemonic)?;
+    if let Some(wordlist) = language.words() {
+        check_checksum(&mnemonic, wordlist)?;
+    }
+    Ok(mnemonic)
+}
+
+/// The last bits of a phrase are the start of the SHA256 of the entropy the
+/// rest encodes, a third of a bit per word.
+fn check_checksum(mnemonic: &str, wordlist: &str) -> Result<(), Bip39Error> {
+    let mut bits = vec![];
+    for word in mnemonic.split_whitespace() {
+        let index =
+            word_index(wordlist, word).ok_or_else(|| Bip39Error::UnknownWord(word.to_owned()))?;
+        bits.extend((0..BITS_PER_WORD).rev().map(|bit| index >> bit & 1 == 1));
+    }
+    let checksum_length = bits.len() / 33;
+    let (entropy, checksum) = bits.split_at(bits.len() - checksum_length);
+    let entropy: Vec<u8> = entropy
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+        .collect();
+    let hash = Sha256::digest(entropy);
+    let expected = (0..checksum_length).map(|bit| hash[0] >> (7 - bit) & 1 == 1);
+    if !checksum.iter().copied().eq(expected) {
+        return Err(Bip39Error::Checksum);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
 
-    use crate::bip39::Seed;
+    use crate::bip39::{
+        check_phrase, check_word_count, normalize_mnemonic, Bip39Error, Language, Seed,
+        BIP39_PBKDF2_ROUNDS,
+    };
 
     #[test]
     fn generate_seed_generates_correct() {
@@ -70,6 +251,149 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_seed_matches_bip39_vector() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed = Seed::generate(mnemonic, "TREZOR");
+
+        assert_eq!(2048, BIP39_PBKDF2_ROUNDS);
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            hex::encode(seed.seed)
+        );
+    }
+
+    #[test]
+    fn generate_seed_matches_japanese_vector() {
+        // From the Japanese BIP39 test vectors, words separated by ideographic
+        // spaces and a passphrase needing compatibility decomposition
+        let mnemonic = [["あいこくしん"; 11].as_slice(), &["あおぞら"]]
+            .concat()
+            .join("\u{3000}");
+        let seed = Seed::generate(&mnemonic, "㍍ガバヴァぱばぐゞちぢ十人十色");
+
+        assert_eq!(
+            "a262d6fb6122ecf45be09c50492b31f92e9beb7d9a845987a02cefda57a15f9c467a17872029a9e92299b5cbdf306e3a0ee620245cbd508959b6cb7ca637bd55",
+            hex::encode(seed.seed)
+        );
+    }
+
+    #[test]
+    fn unchecked_language_keeps_words() {
+        let words: Vec<_> = [["あいこくしん"; 11].as_slice(), &["あおぞら"]]
+            .concat()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+
+        assert_eq!(Language::English, Language::detect(&words));
+        assert!(matches!(
+            check_phrase(&words, Language::English),
+            Err(Bip39Error::UnknownWord(_))
+        ));
+        let phrase = check_phrase(&words, Language::Other).unwrap();
+        assert_eq!(
+            Seed::generate(&words.join(" "), "").seed,
+            Seed::generate(&phrase, "").seed
+        );
+
+        let english = ["abandon".to_owned(), "".to_owned(), "About".to_owned()];
+        assert_eq!(Language::English, Language::detect(&english));
+    }
+
+    #[test]
+    fn detection_keeps_checking_mistyped_words() {
+        let words =
+            |phrase: &str| -> Vec<String> { phrase.split(' ').map(str::to_owned).collect() };
+        let typo = words("initial devote cake drill toy hidden foam gasp film palace flip clumb");
+
+        let language = Language::detect(&typo);
+
+        assert_eq!(Language::English, language);
+        assert!(matches!(
+            check_phrase(&typo, language),
+            Err(Bip39Error::UnknownWord(word)) if word == "clumb"
+        ));
+    }
+
+    #[test]
+    fn spanish_phrase_is_checked() {
+        let words = |last: &str| -> Vec<String> {
+            let mut words = vec!["ábaco".to_owned(); 11];
+            words.push(last.to_owned());
+            words
+        };
+        let valid = words("abierto");
+
+        assert_eq!(Language::Spanish, Language::detect(&valid));
+        assert!(Language::Spanish.contains("A\u{301}baco"));
+        let phrase = check_phrase(&valid, Language::Spanish).unwrap();
+        assert_eq!(normalize_mnemonic(&valid.join(" ")), phrase);
+        assert!(matches!(
+            check_phrase(&words("abeja"), Language::Spanish),
+            Err(Bip39Error::Checksum)
+        ));
+        assert!(matches!(
+            check_phrase(&words("abierta"), Language::detect(&words("abierta"))),
+            Err(Bip39Error::UnknownWord(_))
+        ));
+    }
+
+    #[test]
+    fn generate_normalizes_padding_and_case() {
+        let canonical = "initial devote cake drill toy hidden foam gasp film palace flip clump";
+        let pasted = "  Initial devote\tCAKE  drill toy hidden foam gasp film palace flip Clump \n";
+
+        assert_eq!(canonical, normalize_mnemonic(pasted));
+        assert_eq!(
+            Seed::generate(canonical, "").seed,
+            Seed::generate(pasted, "").seed
+        );
+    }
+
+    #[test]
+    fn word_count_must_match_bip39_lengths() {
+        let phrase = |count| vec!["abandon"; count].join(" ");
+
+        assert!(check_word_count(&phrase(12)).is_ok());
+        assert!(check_word_count(&phrase(24)).is_ok());
+        for count in [11, 13] {
+            let error = check_word_count(&phrase(count)).unwrap_err();
+            assert!(matches!(error, Bip39Error::WordCount(c) if c == count));
+            assert_eq!(
+                format!("A phrase has 12, 15, 18, 21 or 24 words, found {count}"),
+                error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn check_phrase_reports_missing_and_invalid_words() {
+        let words =
+            |phrase: &str| -> Vec<String> { phrase.split(' ').map(str::to_owned).collect() };
+        let valid = "initial devote cake drill toy hidden foam gasp film palace flip clump";
+
+        assert_eq!(
+            valid,
+            check_phrase(&words(valid), Language::English).unwrap()
+        );
+        assert!(matches!(
+            check_phrase(&words("initial devote cake drill toy  foam gasp film palace flip clump"), Language::English),
+            Err(Bip39Error::EmptyWords(missing)) if missing == [6]
+        ));
+        assert!(matches!(
+            check_phrase(
+                &words("initial devote cake drill toy hidden foam gasp film palace flip flip"),
+                Language::English,
+            ),
+            Err(Bip39Error::Checksum)
+        ));
+        assert!(matches!(
+            check_phrase(&words("initial devote cake drill toy hidden foam gasp film palace flip clumb"), Language::English),
+            Err(Bip39Error::UnknownWord(word)) if word == "clumb"
+        ));
+    }
+
     #[test]
     fn generate_xprv_returns_correct() -> Result<()> {
         let seed = "88a6b54bf042d0ba673e497dd283feeca6a1d0fd31cf26d8b7e115f2b3cc92294541855a9c0e74a3c3b87a5aee5adc89faf0702721b6b8af31c0d2b403aba531";
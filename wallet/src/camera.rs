@@ -0,0 +1,223 @@
+use anyhow::Result;
+use gloo_timers::future::TimeoutFuture;
+use js_sys::{Array, Object, Reflect};
+use thiserror::Error;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, HtmlVideoElement, MediaStream,
+    MediaStreamConstraints, MediaStreamTrack,
+};
+
+use crate::util;
+
+const SCAN_INTERVAL_MS: u32 = 250;
+/// Frames are scaled down to this many pixels on the longer side before
+/// being decoded in the wallet, keeping each attempt short.
+const MAX_FRAME_SIDE: u32 = 800;
+/// The camera is released when no code was seen for this long.
+const SCAN_TIMEOUT_MS: f64 = 30_000.0;
+
+#[wasm_bindgen]
+extern "C" {
+    type BarcodeDetector;
+
+    #[wasm_bindgen(catch, constructor)]
+    fn new(options: &JsValue) -> Result<BarcodeDetector, JsValue>;
+
+    #[wasm_bindgen(method, catch)]
+    async fn detect(this: &BarcodeDetector, source: &HtmlVideoElement) -> Result<JsValue, JsValue>;
+}
+
+#[derive(Debug, Error)]
+pub enum CameraError {
+    #[error("Camera access was denied")]
+    PermissionDenied,
+    #[error("No camera was found")]
+    NoCamera,
+    #[error("No QR code was found")]
+    NothingFound,
+    #[error("Camera failed: {0}")]
+    Failed(String),
+}
+
+impl From<JsValue> for CameraError {
+    fn from(value: JsValue) -> Self {
+        let name = Reflect::get(&value, &JsValue::from_str("name"))
+            .ok()
+            .and_then(|name| name.as_string());
+        match name.as_deref() {
+            Some("NotAllowedError" | "SecurityError") => Self::PermissionDenied,
+            Some("NotFoundError" | "OverconstrainedError") => Self::NoCamera,
+            _ => Self::Failed(format!("{value:?}")),
+        }
+    }
+}
+
+/// Reads QR codes from video frames, with the browser's detector where there
+/// is one and rqrr otherwise.
+enum Reader {
+    Detector(BarcodeDetector),
+    Canvas(HtmlCanvasElement, CanvasRenderingContext2d),
+}
+
+impl Reader {
+    fn new() -> Result<Self, CameraError> {
+        if Reflect::has(&js_sys::global(), &JsValue::from_str("BarcodeDetector")).unwrap_or(false) {
+            let options = Object::new();
+            Reflect::set(
+                &options,
+                &JsValue::from_str("formats"),
+                &Array::of1(&JsValue::from_str("qr_code")),
+            )?;
+            // Detectors may still lack QR support, leaving the canvas to it
+            if let Ok(detector) = BarcodeDetector::new(&options) {
+                return Ok(Self::Detector(detector));
+            }
+        }
+
+        let canvas: HtmlCanvasElement = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| CameraError::Failed("No document".to_string()))?
+            .create_element("canvas")?
+            .unchecked_into();
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| CameraError::Failed("No canvas context".to_string()))?
+            .unchecked_into();
+        Ok(Self::Canvas(canvas, context))
+    }
+
+    /// The text of a QR code in the current frame of `video`, if any.
+    async fn read(&self, video: &HtmlVideoElement) -> Result<Option<String>, CameraError> {
+        match self {
+            Self::Detector(detector) => {
+                let codes: Array = detector.detect(video).await?.unchecked_into();
+                Ok(codes.iter().find_map(|code| {
+                    Reflect::get(&code, &JsValue::from_str("rawValue"))
+                        .ok()?
+                        .as_string()
+                }))
+            }
+            Self::Canvas(canvas, context) => {
+                let (width, height) = (video.video_width(), video.video_height());
+                // No frame has arrived yet
+                if width == 0 || height == 0 {
+                    return Ok(None);
+                }
+                let scale = (MAX_FRAME_SIDE as f64 / width.max(height) as f64).min(1.0);
+                let width = (width as f64 * scale) as u32;
+                let height = (height as f64 * scale) as u32;
+                canvas.set_width(width);
+                canvas.set_height(height);
+                context.draw_image_with_html_video_element_and_dw_and_dh(
+                    video,
+                    0.0,
+                    0.0,
+                    width as f64,
+                    height as f64,
+                )?;
+                let frame = context.get_image_data(0.0, 0.0, width as f64, height as f64)?;
+                Ok(decode_rgba(width as usize, height as usize, &frame.data()))
+            }
+        }
+    }
+}
+
+/// The text of a QR code in an image of `width` by `height` pixels, given as
+/// RGBA bytes like a canvas' image data.
+fn decode_rgba(width: usize, height: usize, rgba: &[u8]) -> Option<String> {
+    if rgba.len() < width * height * 4 {
+        return None;
+    }
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| {
+        let pixel = &rgba[(y * width + x) * 4..];
+        ((pixel[0] as u16 + 2 * pixel[1] as u16 + pixel[2] as u16) / 4) as u8
+    });
+    image
+        .detect_grids()
+        .into_iter()
+        .find_map(|grid| grid.decode().ok())
+        .map(|(_, text)| text)
+}
+
+/// Shows the camera in `video` until a QR code is seen, returning its text.
+pub async fn scan_qr(video: &HtmlVideoElement) -> Result<String> {
+    let reader = Reader::new()?;
+
+    let stream = open_camera().await?;
+    video.set_src_object(Some(&stream));
+    let result = detect(&reader, video).await;
+    // Released whether or not a code was found, turning the camera light off
+    for track in stream.get_tracks().iter() {
+        track.unchecked_into::<MediaStreamTrack>().stop();
+    }
+    video.set_src_object(None);
+    Ok(result?)
+}
+
+async fn open_camera() -> Result<MediaStream, CameraError> {
+    let devices = web_sys::window()
+        .ok_or(CameraError::NoCamera)?
+        .navigator()
+        .media_devices()?;
+    let video = Object::new();
+    Reflect::set(
+        &video,
+        &JsValue::from_str("facingMode"),
+        &JsValue::from_str("environment"),
+    )?;
+    let constraints = MediaStreamConstraints::new();
+    constraints.set_video(&video);
+
+    let stream = JsFuture::from(devices.get_user_media_with_constraints(&constraints)?).await?;
+    Ok(stream.unchecked_into())
+}
+
+async fn detect(reader: &Reader, video: &HtmlVideoElement) -> Result<String, CameraError> {
+    JsFuture::from(video.play()?).await?;
+    let started = util::get_timestamp();
+    while util::get_timestamp() - started < SCAN_TIMEOUT_MS {
+        if let Some(value) = reader.read(video).await? {
+            return Ok(value);
+        }
+        TimeoutFuture::new(SCAN_INTERVAL_MS).await;
+    }
+    Err(CameraError::NothingFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use qrcode::QrCode;
+
+    use super::*;
+
+    /// RGBA image of `code`, `scale` pixels per module with a four module
+    /// quiet zone.
+    fn render(code: &QrCode, scale: usize) -> (usize, Vec<u8>) {
+        let width = code.width();
+        let size = (width + 8) * scale;
+        let colors = code.to_colors();
+        let rgba = (0..size * size)
+            .flat_map(|index| {
+                let (x, y) = ((index % size) / scale, (index / size) / scale);
+                let dark = (4..width + 4).contains(&x)
+                    && (4..width + 4).contains(&y)
+                    && colors[(y - 4) * width + x - 4] == qrcode::Color::Dark;
+                let luma = if dark { 20 } else { 235 };
+                [luma, luma, luma, 255]
+            })
+            .collect();
+        (size, rgba)
+    }
+
+    #[test]
+    fn decodes_rendered_payment_request() -> anyhow::Result<()> {
+        let text = "bitcoin:1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr?amount=0.001&label=Coffee";
+        let (size, rgba) = render(&QrCode::new(text)?, 4);
+
+        assert_eq!(Some(text.to_owned()), decode_rgba(size, size, &rgba));
+        assert_eq!(None, decode_rgba(size, size, &vec![255; size * size * 4]));
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use crate::bip32::{WalletKey, XPub};
+
+/// The account key `m/0'` of BIP32 test vector 1. It is public, so the demo
+/// shows a real wallet's history, and being watch-only it can't sign.
+const DEMO_XPUB: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+
+/// Whether the page query, e.g. `?demo`, asks for the read-only demo.
+pub fn is_demo(search: &str) -> bool {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .any(|parameter| parameter == "demo" || parameter.starts_with("demo="))
+}
+
+pub fn demo_key() -> WalletKey {
+    WalletKey::WatchOnly(DEMO_XPUB.parse::<XPub>().expect("Demo key should be valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::active;
+    use crate::bip32::{DerivePath, XPrv};
+
+    #[test]
+    fn demo_is_requested_by_query() {
+        assert!(is_demo("?demo"));
+        assert!(is_demo("?lang=en&demo=1"));
+        assert!(!is_demo(""));
+        assert!(!is_demo("?demonstration"));
+    }
+
+    #[test]
+    fn demo_key_is_test_vector_account() -> Result<()> {
+        let master: XPrv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".parse()?;
+        let account = master.derive_path("m/0'")?.derive_public();
+
+        assert_eq!(WalletKey::WatchOnly(account), demo_key());
+        Ok(())
+    }
+
+    #[test]
+    fn demo_never_enables_sending() {
+        assert!(!active::can_send(&demo_key()));
+    }
+}
@@ -0,0 +1,182 @@
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use anyhow::Result;
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::util::sha256;
+
+const KEY_DERIVATION_ROUNDS: u32 = 100_000;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EncryptionError {
+    #[error("Password must not be empty")]
+    EmptyPassword,
+    #[error("Passwords do not match")]
+    PasswordMismatch,
+    #[error("Wrong password")]
+    WrongPassword,
+    #[error("Encrypted data is corrupt")]
+    CorruptData,
+    #[error("Unable to generate random data")]
+    RandomUnavailable,
+}
+
+pub fn validate_password(password: &str, confirmation: &str) -> Result<(), EncryptionError> {
+    if password.is_empty() {
+        return Err(EncryptionError::EmptyPassword);
+    }
+    if password != confirmation {
+        return Err(EncryptionError::PasswordMismatch);
+    }
+    Ok(())
+}
+
+/// Data encrypted with AES-256-GCM under a PBKDF2-derived key. Alongside the
+/// ciphertext a hash of a second derived key is stored, which allows telling a
+/// wrong password apart from tampered or truncated data.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedData {
+    salt: String,
+    nonce: String,
+    check: String,
+    ciphertext: String,
+}
+
+impl EncryptedData {
+    pub fn encrypt(data: &[u8], password: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        getrandom::getrandom(&mut salt).map_err(|_| EncryptionError::RandomUnavailable)?;
+        getrandom::getrandom(&mut nonce).map_err(|_| EncryptionError::RandomUnavailable)?;
+
+        Self::encrypt_with(data, password, salt, nonce)
+    }
+
+    fn encrypt_with(
+        data: &[u8],
+        password: &str,
+        salt: [u8; SALT_SIZE],
+        nonce: [u8; NONCE_SIZE],
+    ) -> Result<Self> {
+        let (key, check) = derive_keys(password, &salt);
+        let cipher = Aes256Gcm::new_from_slice(&key)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), data)
+            .map_err(|_| EncryptionError::CorruptData)?;
+
+        Ok(Self {
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            check: hex::encode(sha256(&check)),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>, EncryptionError> {
+        let salt = decode_field(&self.salt, Some(SALT_SIZE))?;
+        let nonce = decode_field(&self.nonce, Some(NONCE_SIZE))?;
+        let check = decode_field(&self.check, Some(32))?;
+        let ciphertext = decode_field(&self.ciphertext, None)?;
+
+        let (key, expected_check) = derive_keys(password, &salt);
+        if sha256(&expected_check)[..] != check[..] {
+            return Err(EncryptionError::WrongPassword);
+        }
+
+        let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| EncryptionError::CorruptData)?;
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), &ciphertext[..])
+            .map_err(|_| EncryptionError::CorruptData)
+    }
+}
+
+fn decode_field(value: &str, size: Option<usize>) -> Result<Vec<u8>, EncryptionError> {
+    let decoded = hex::decode(value).map_err(|_| EncryptionError::CorruptData)?;
+    match size {
+        Some(size) if decoded.len() != size => Err(EncryptionError::CorruptData),
+        _ => Ok(decoded),
+    }
+}
+
+fn derive_keys(password: &str, salt: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut derived = [0u8; 64];
+    pbkdf2_hmac::<Sha256>(
+        password.as_bytes(),
+        salt,
+        KEY_DERIVATION_ROUNDS,
+        &mut derived,
+    );
+
+    let key = derived[..32]
+        .try_into()
+        .expect("Fixed size should always succeed");
+    let check = derived[32..]
+        .try_into()
+        .expect("Fixed size should always succeed");
+    (key, check)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    #[test]
+    fn decrypt_returns_original() -> Result<()> {
+        let encrypted = EncryptedData::encrypt_with(XPRV.as_bytes(), "hunter2", [1; 16], [2; 12])?;
+
+        assert_eq!(XPRV.as_bytes(), encrypted.decrypt("hunter2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_wrong_password_and_tampered_differ() -> Result<()> {
+        let encrypted = EncryptedData::encrypt_with(XPRV.as_bytes(), "hunter2", [1; 16], [2; 12])?;
+
+        assert_eq!(
+            Err(EncryptionError::WrongPassword),
+            encrypted.decrypt("hunter3")
+        );
+
+        let mut ciphertext = hex::decode(&encrypted.ciphertext)?;
+        ciphertext[0] ^= 0x01;
+        let tampered = EncryptedData {
+            ciphertext: hex::encode(ciphertext),
+            ..encrypted.clone()
+        };
+        assert_eq!(
+            Err(EncryptionError::CorruptData),
+            tampered.decrypt("hunter2")
+        );
+
+        let truncated = EncryptedData {
+            nonce: encrypted.nonce[..10].to_owned(),
+            ..encrypted
+        };
+        assert_eq!(
+            Err(EncryptionError::CorruptData),
+            truncated.decrypt("hunter2")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_password_requires_confirmation() {
+        assert_eq!(Ok(()), validate_password("secret", "secret"));
+        assert_eq!(
+            Err(EncryptionError::PasswordMismatch),
+            validate_password("secret", "secert")
+        );
+        assert_eq!(
+            Err(EncryptionError::EmptyPassword),
+            validate_password("", "")
+        );
+    }
+}
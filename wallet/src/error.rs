@@ -0,0 +1,117 @@
+use thiserror::Error;
+
+use crate::{
+    bip32::Bip32Error,
+    bip39::Bip39Error,
+    sending::{DeserializeError, SendingError, SignatureError},
+    util::AddressError,
+    wif::WifError,
+};
+
+/// Error of the public functions spanning several steps, e.g. building and
+/// signing a send, which every step's error converts into. Functions of a
+/// single step return that step's error.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Bip32(#[from] Bip32Error),
+    #[error(transparent)]
+    Bip39(#[from] Bip39Error),
+    #[error(transparent)]
+    Wif(#[from] WifError),
+    #[error(transparent)]
+    Address(#[from] AddressError),
+    #[error(transparent)]
+    Deserialize(#[from] DeserializeError),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Sending(#[from] SendingError),
+    #[error(transparent)]
+    Hex(#[from] hex::FromHexError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        bip32::XPrv,
+        sending::{self, Input, Output, Transaction},
+        util::{Network, Satoshis},
+        wif::PrivateKey,
+    };
+
+    use super::*;
+
+    #[test]
+    fn public_functions_return_domain_errors() {
+        let mut tampered = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
+        tampered.replace_range(20..21, "x");
+        assert!(matches!(
+            tampered.parse::<XPrv>(),
+            Err(Bip32Error::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTx".parse::<PrivateKey>(),
+            Err(WifError::ChecksumError)
+        ));
+
+        assert!(matches!(
+            Transaction::from_hex("not hex"),
+            Err(DeserializeError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Transaction::from_hex("010000000001f0ca052a010000001976a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac00000000"),
+            Err(DeserializeError::NoInputs)
+        ));
+
+        assert!(matches!(
+            Output::new(
+                Satoshis(1_000),
+                "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYD",
+                Network::Mainnet
+            ),
+            Err(AddressError::WrongNetwork { .. })
+        ));
+
+        assert!(matches!(
+            Input::new_decoded(vec![0x3f; 31], 0),
+            Err(SendingError::TxHashLength(31))
+        ));
+
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(vec![0x3f; 32], 0).unwrap());
+        let previous_outputs = HashMap::from([(
+            (vec![0x3f; 32], 0),
+            Output::new_from_decoded(Satoshis(1_000), [0x0c; 20]),
+        )]);
+        assert!(matches!(
+            transaction.sign_inputs(&previous_outputs, &HashMap::new()),
+            Err(SignatureError::MissingKey)
+        ));
+    }
+
+    #[test]
+    fn steps_keep_their_error_in_combined_functions() {
+        let result = sending::check_recipient(
+            "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYD",
+            Satoshis(1_000),
+            Network::Mainnet,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Address(AddressError::WrongNetwork { .. }))
+        ));
+
+        let result = sending::check_recipient(
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+            Satoshis(0),
+            Network::Mainnet,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Sending(SendingError::AmountTooSmall))
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+use std::future::Future;
+
+use anyhow::Result;
+use gloo_net::http::Request;
+use gloo_timers::future::TimeoutFuture;
+use thiserror::Error;
+use web_sys::HtmlInputElement;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use crate::settings::Settings;
+use crate::transactions::race_timeout;
+use crate::util::{self, Network};
+
+const FAUCET_URL: &str = "https://api.whatsonchain.com/v1/bsv/test/faucet/send";
+
+#[derive(Debug, Error)]
+enum FaucetError {
+    #[error("Faucet returned no transaction id")]
+    EmptyResponse,
+}
+
+/// Asks the testnet faucet to fund `address`, returning the funding txid.
+/// The request may take as long as `settings` allow.
+pub async fn request_coins(address: &str, settings: &Settings) -> Result<String> {
+    let timeout_ms = settings.request_timeout_ms;
+    request_coins_with(address, |url| async move {
+        let request = async { Ok(Request::get(&url).send().await?.text().await?) };
+        race_timeout(request, TimeoutFuture::new(timeout_ms), timeout_ms).await
+    })
+    .await
+}
+
+async fn request_coins_with<F, Fut>(address: &str, fetch: F) -> Result<String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    util::validate_address(address.trim(), Network::Testnet)?;
+
+    let txid = fetch(format!("{FAUCET_URL}/{}", address.trim())).await?;
+    let txid = txid.trim().trim_matches('"');
+    if txid.is_empty() {
+        return Err(FaucetError::EmptyResponse.into());
+    }
+    Ok(txid.to_owned())
+}
+
+#[derive(Properties, PartialEq)]
+pub struct FaucetProps {
+    pub settings: Settings,
+}
+
+#[function_component(Faucet)]
+pub fn faucet(FaucetProps { settings }: &FaucetProps) -> Html {
+    let address = use_state(String::default);
+    let result = use_state(String::default);
+
+    let set_address = {
+        let address = address.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            address.set(input.value());
+        }
+    };
+
+    let request = {
+        let result = result.clone();
+        let settings = settings.clone();
+        move |_| {
+            let address = (*address).clone();
+            let result = result.clone();
+            let settings = settings.clone();
+            spawn_local(async move {
+                match request_coins(&address, &settings).await {
+                    Ok(txid) => result.set(format!("Funded in {txid}")),
+                    Err(error) => result.set(format!("Faucet request failed: {error}")),
+                }
+            });
+        }
+    };
+
+    html! {
+        <>
+            <label for="faucet_address">{"Testnet address:"}</label>
+            <input id="faucet_address" oninput={set_address}/>
+            <button onclick={request}>{"Request testnet coins"}</button>
+            <p>{(*result).clone()}</p>
+        </>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn faucet_request_is_formed_for_address() -> Result<()> {
+        let address = "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYD";
+        let requested = RefCell::new(None);
+
+        let txid = block_on(request_coins_with(address, |url| {
+            requested.replace(Some(url));
+            async {
+                Ok(
+                    "\"c80b343d2ce2b5d829c2de9854c7c8d423c0e33bda264c40138d834aab4c0638\"\n"
+                        .to_owned(),
+                )
+            }
+        }))?;
+
+        assert_eq!(
+            Some(format!("{FAUCET_URL}/{address}")),
+            requested.into_inner()
+        );
+        assert_eq!(
+            "c80b343d2ce2b5d829c2de9854c7c8d423c0e33bda264c40138d834aab4c0638",
+            txid
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn faucet_rejects_mainnet_address() {
+        let result = block_on(request_coins_with(
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+            |_| async { Ok(String::new()) },
+        ));
+
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<util::AddressError>(),
+            Some(util::AddressError::WrongNetwork { .. })
+        ));
+    }
+
+    #[test]
+    fn faucet_rejects_bad_checksum() {
+        // A valid testnet address with its last character changed
+        let result = block_on(request_coins_with(
+            "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYE",
+            |_| async { Ok(String::new()) },
+        ));
+
+        assert!(result
+            .unwrap_err()
+            .downcast_ref::<util::AddressError>()
+            .is_some());
+    }
+}
@@ -0,0 +1,219 @@
+use std::future::Future;
+use std::str::FromStr;
+
+use gloo_net::http::Request;
+use gloo_timers::future::TimeoutFuture;
+use serde::Deserialize;
+
+use crate::{
+    ratelimit::RateLimiter, sending::DEFAULT_FEE_RATE, settings::Settings,
+    transactions::race_timeout, util::log,
+};
+
+/// How long a fetched quote is used before asking for a new one.
+const FEE_QUOTE_MAX_AGE_MS: f64 = 5.0 * 60_000.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FeeTier {
+    Economy,
+    #[default]
+    Standard,
+    Priority,
+}
+
+impl FeeTier {
+    pub const ALL: [FeeTier; 3] = [FeeTier::Economy, FeeTier::Standard, FeeTier::Priority];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeeTier::Economy => "Economy",
+            FeeTier::Standard => "Standard",
+            FeeTier::Priority => "Priority",
+        }
+    }
+
+    /// A rough guess of how long a transaction paying this tier takes to confirm.
+    pub fn confirmation_estimate(&self) -> &'static str {
+        match self {
+            FeeTier::Economy => "may take several blocks (an hour or more)",
+            FeeTier::Standard => "usually the next block (~10 minutes)",
+            FeeTier::Priority => "next block (~10 minutes)",
+        }
+    }
+}
+
+impl FromStr for FeeTier {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        FeeTier::ALL
+            .into_iter()
+            .find(|tier| tier.name() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown fee tier: {value}"))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FeeAmount {
+    pub satoshis: u64,
+    pub bytes: u64,
+}
+
+impl FeeAmount {
+    /// Satoshis per byte, rounded up and never below one.
+    fn rate(&self) -> u64 {
+        if self.bytes == 0 {
+            return DEFAULT_FEE_RATE;
+        }
+        self.satoshis.div_ceil(self.bytes).max(1)
+    }
+}
+
+/// Fees a provider asks for, as paid to miners and for relaying respectively.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FeeQuote {
+    pub mining_fee: FeeAmount,
+    pub relay_fee: FeeAmount,
+}
+
+impl FeeQuote {
+    /// Rate in satoshis per byte to pay for the given tier.
+    pub fn rate(&self, tier: FeeTier) -> u64 {
+        match tier {
+            FeeTier::Economy => self.relay_fee.rate(),
+            FeeTier::Standard => self.mining_fee.rate(),
+            FeeTier::Priority => self.mining_fee.rate() * 2,
+        }
+    }
+
+    /// A quote asking `rate` satoshis per byte for both fees.
+    pub fn flat(rate: u64) -> Self {
+        Self {
+            mining_fee: FeeAmount {
+                satoshis: rate,
+                bytes: 1,
+            },
+            relay_fee: FeeAmount {
+                satoshis: rate,
+                bytes: 1,
+            },
+        }
+    }
+}
+
+impl Default for FeeQuote {
+    fn default() -> Self {
+        Self::flat(DEFAULT_FEE_RATE)
+    }
+}
+
+/// The last fetched quote, so that not every send has to ask for one.
+#[derive(Default)]
+pub struct FeeQuoteCache {
+    fetched: Option<(f64, FeeQuote)>,
+}
+
+impl FeeQuoteCache {
+    /// The cached quote, unless it is older than [`FEE_QUOTE_MAX_AGE_MS`].
+    pub fn get(&self, now: f64) -> Option<FeeQuote> {
+        self.fetched
+            .as_ref()
+            .filter(|(fetched_at, _)| now - fetched_at < FEE_QUOTE_MAX_AGE_MS)
+            .map(|(_, quote)| quote.clone())
+    }
+
+    pub fn store(&mut self, quote: FeeQuote, now: f64) {
+        self.fetched = Some((now, quote));
+    }
+}
+
+/// Asks the network for current fees, falling back to the configured rate
+/// when that fails.
+pub async fn fetch_fee_quote(rate_limiter: &RateLimiter, settings: &Settings) -> FeeQuote {
+    rate_limiter.take().await;
+    let url = settings.url("miner/fees");
+    fetch_fee_quote_with(settings.fee_rate, || async {
+        let request = async { Ok(Request::get(&url).send().await?.json().await?) };
+        race_timeout(
+            request,
+            TimeoutFuture::new(settings.request_timeout_ms),
+            settings.request_timeout_ms,
+        )
+        .await
+        .inspect_err(|error| log(&format!("Unable to fetch fee quote: {error:?}")))
+    })
+    .await
+}
+
+async fn fetch_fee_quote_with<F, Fut>(fallback_rate: u64, fetch: F) -> FeeQuote
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<FeeQuote>>,
+{
+    fetch()
+        .await
+        .unwrap_or_else(|_| FeeQuote::flat(fallback_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn quote_maps_to_tiers() {
+        let quote = FeeQuote {
+            mining_fee: FeeAmount {
+                satoshis: 500,
+                bytes: 100,
+            },
+            relay_fee: FeeAmount {
+                satoshis: 250,
+                bytes: 1000,
+            },
+        };
+
+        assert_eq!(1, quote.rate(FeeTier::Economy));
+        assert_eq!(5, quote.rate(FeeTier::Standard));
+        assert_eq!(10, quote.rate(FeeTier::Priority));
+    }
+
+    #[test]
+    fn failed_fetch_falls_back_to_configured_rate() {
+        let quote = block_on(fetch_fee_quote_with(3, || async {
+            Err(anyhow::anyhow!("Service unavailable"))
+        }));
+
+        assert_eq!(FeeQuote::flat(3), quote);
+        assert_eq!(3, quote.rate(FeeTier::Standard));
+    }
+
+    #[test]
+    fn timed_out_fetch_falls_back_to_configured_rate() {
+        let quote = block_on(fetch_fee_quote_with(3, || {
+            race_timeout(futures::future::pending(), async {}, 50)
+        }));
+
+        assert_eq!(FeeQuote::flat(3), quote);
+    }
+
+    #[test]
+    fn cache_expires() {
+        let mut cache = FeeQuoteCache::default();
+        assert_eq!(None, cache.get(0.0));
+
+        cache.store(FeeQuote::default(), 1_000.0);
+
+        assert_eq!(Some(FeeQuote::default()), cache.get(1_000.0));
+        assert_eq!(None, cache.get(1_000.0 + FEE_QUOTE_MAX_AGE_MS));
+    }
+
+    #[test]
+    fn tier_parses_from_name() {
+        for tier in FeeTier::ALL {
+            assert_eq!(tier, tier.name().parse::<FeeTier>().unwrap());
+        }
+        assert_eq!(FeeTier::Standard, FeeTier::default());
+    }
+}
@@ -1,4 +1,8 @@
-use crate::bip32::XPrv;
+use crate::bip32::{WalletKey, XPrv};
+use crate::encryption::EncryptedData;
+use crate::storage::StorageKey;
+use anyhow::Result;
+use std::future::Future;
 use std::str::FromStr;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
@@ -11,14 +15,35 @@ use yew::{function_component, Renderer};
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 mod active;
+mod backup;
 mod bip32;
 mod bip39;
+mod camera;
+mod demo;
+mod encryption;
+mod error;
+#[cfg(feature = "testnet-faucet")]
+mod faucet;
+mod fees;
+mod psbt;
+mod qr;
 mod ratelimit;
 mod recover;
 mod script;
 mod sending;
+mod settings;
+mod storage;
+mod sync;
 mod transactions;
 mod util;
+mod wif;
+
+pub use bip32::Bip32Error;
+pub use bip39::Bip39Error;
+pub use error::Error;
+pub use sending::{DeserializeError, SendingError, SignatureError};
+pub use util::AddressError;
+pub use wif::WifError;
 
 #[wasm_bindgen(start)]
 pub fn main() {
@@ -27,38 +52,190 @@ pub fn main() {
 
 #[function_component(App)]
 fn app() -> Html {
-    let page = window().unwrap_throw().document().unwrap_throw().title();
+    let page = window()
+        .and_then(|window| window.document())
+        .map(|document| document.title())
+        .unwrap_or_default();
     let page = page.as_str();
+    let demo = window()
+        .and_then(|window| window.location().search().ok())
+        .is_some_and(|search| demo::is_demo(&search));
 
     let xprv = use_state(|| None);
-    spawn_local(load_xprv(xprv.clone()));
-    let xprv_recover = xprv.clone();
-    let on_recover = {
-        move |_| {
-            let xprv = xprv_recover.clone();
-            spawn_local(load_xprv(xprv));
+    let locked = use_state(|| None);
+    let error = use_state(|| None::<String>);
+    let reload = {
+        let xprv = xprv.clone();
+        let locked = locked.clone();
+        let error = error.clone();
+        move || {
+            error.set(None);
+            spawn_local(load_xprv(xprv.clone(), locked.clone(), error.clone()));
         }
     };
+    {
+        let reload = reload.clone();
+        use_effect_with_deps(
+            move |_| {
+                // The demo leaves the stored wallet untouched
+                if demo {
+                    storage::set_read_only(true);
+                } else {
+                    reload();
+                }
+                || ()
+            },
+            (),
+        );
+    }
+    let on_recover = {
+        let reload = reload.clone();
+        move |_| reload()
+    };
+    let on_unlock = {
+        let xprv = xprv.clone();
+        move |unlocked| xprv.set(Some(unlocked))
+    };
 
-    match (page, xprv.as_ref()) {
-        ("BeeSV Settings", None) => html! {<recover::Recover {on_recover} />},
-        ("BeeSV Settings", Some(xprv)) => html! {<active::Fullscreen xprv={xprv.clone()}/>},
-        (_, None) => html! {<recover::Popup />},
-        (_, Some(_xprv)) => html! {<active::Popup/>},
+    if let Some(error) = error.as_ref() {
+        let retry = move |_| reload();
+        return html! {
+            <>
+                <p>{format!("Unable to load wallet: {error}")}</p>
+                <button onclick={retry}>{"Retry"}</button>
+            </>
+        };
     }
-}
 
-async fn load_xprv(xprv_state: UseStateHandle<Option<XPrv>>) {
-    match util::store_load::<String>("xprv").await {
-        Ok(Some(value)) => {
-            let Ok(xprv) = XPrv::from_str(&value) else {
-                return;
-            };
-            xprv_state.set(Some(xprv));
+    if demo && page == "BeeSV Settings" {
+        return html! {<active::Fullscreen wallet_key={demo::demo_key()}/>};
+    }
+
+    match (page, xprv.as_ref(), locked.as_ref()) {
+        ("BeeSV Settings", Some(xprv), _) => {
+            html! {<active::Fullscreen wallet_key={WalletKey::Private(xprv.clone())}/>}
+        }
+        ("BeeSV Settings", None, Some(encrypted)) => {
+            html! {<recover::Unlock encrypted={encrypted.clone()} {on_unlock} />}
         }
+        ("BeeSV Settings", None, None) => html! {<recover::Recover {on_recover} />},
+        (_, None, None) => html! {<recover::Popup />},
+        _ => html! {<active::Popup/>},
+    }
+}
+
+async fn load_xprv(
+    xprv_state: UseStateHandle<Option<XPrv>>,
+    locked_state: UseStateHandle<Option<EncryptedData>>,
+    error_state: UseStateHandle<Option<String>>,
+) {
+    let stored = async {
+        storage::migrate().await?;
+        load_stored_xprv(
+            storage::load(StorageKey::EncryptedXprv),
+            storage::load(StorageKey::Xprv),
+        )
+        .await
+    }
+    .await;
+    let stored = match stored {
+        Ok(stored) => stored,
         Err(error) => {
-            gloo_dialogs::alert(&format!("Unable to load wallet: {error:?}"));
+            error_state.set(Some(format!("{error:?}")));
+            return;
         }
-        _ => (), // Wallet not stored
     };
+
+    match stored {
+        StoredXprv::Locked(encrypted) => locked_state.set(Some(encrypted)),
+        StoredXprv::Valid(xprv) => xprv_state.set(Some(xprv)),
+        StoredXprv::Corrupt(error) => {
+            let message = format!(
+                "Stored wallet is corrupt ({error}). Remove it and recover the wallet again?"
+            );
+            if gloo_dialogs::confirm(&message) {
+                if let Err(error) = storage::remove(StorageKey::Xprv).await {
+                    gloo_dialogs::alert(&format!("Unable to remove wallet: {error:?}"));
+                }
+            }
+        }
+        StoredXprv::Missing => (),
+    }
+}
+
+enum StoredXprv {
+    Missing,
+    Corrupt(Bip32Error),
+    Valid(XPrv),
+    Locked(EncryptedData),
+}
+
+/// Reads the wallet from storage, preferring the encrypted one. `plain` is
+/// only awaited when no encrypted wallet exists.
+async fn load_stored_xprv(
+    encrypted: impl Future<Output = Result<Option<EncryptedData>>>,
+    plain: impl Future<Output = Result<Option<String>>>,
+) -> Result<StoredXprv> {
+    if let Some(encrypted) = encrypted.await? {
+        return Ok(StoredXprv::Locked(encrypted));
+    }
+    // Fall back to a wallet stored before encryption was supported
+    Ok(parse_stored_xprv(plain.await?))
+}
+
+fn parse_stored_xprv(value: Option<String>) -> StoredXprv {
+    match value.map(|value| XPrv::from_str(&value)) {
+        None => StoredXprv::Missing,
+        Some(Ok(xprv)) => StoredXprv::Valid(xprv),
+        Some(Err(error)) => StoredXprv::Corrupt(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    async fn failing<T>() -> Result<T> {
+        Err(anyhow::anyhow!("Storage unavailable"))
+    }
+
+    #[test]
+    fn load_stored_xprv_surfaces_storage_errors() {
+        let result = block_on(load_stored_xprv(async { Ok(None) }, failing()));
+        assert!(result.is_err());
+
+        let result = block_on(load_stored_xprv(failing(), async {
+            Ok(Some(XPRV.to_owned()))
+        }));
+        assert!(result.is_err());
+
+        let result = block_on(load_stored_xprv(async { Ok(None) }, async {
+            Ok(Some(XPRV.to_owned()))
+        }));
+        assert!(matches!(result, Ok(StoredXprv::Valid(_))));
+    }
+
+    #[test]
+    fn parse_stored_xprv_detects_corruption() {
+        assert!(matches!(parse_stored_xprv(None), StoredXprv::Missing));
+        assert!(matches!(
+            parse_stored_xprv(Some(XPRV.to_owned())),
+            StoredXprv::Valid(_)
+        ));
+        assert!(matches!(
+            parse_stored_xprv(Some(XPRV[..60].to_owned())),
+            StoredXprv::Corrupt(_)
+        ));
+
+        let mut tampered = XPRV.to_owned();
+        tampered.replace_range(20..21, "x");
+        assert!(matches!(
+            parse_stored_xprv(Some(tampered)),
+            StoredXprv::Corrupt(_)
+        ));
+    }
 }
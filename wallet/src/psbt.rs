@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use secp256k1::{Message, PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    script,
+    sending::{Output, SigHashType, SigningSummary, Transaction},
+    util::{ripemd160, sha256, Network, Satoshis},
+};
+
+#[derive(Debug, Error)]
+enum PsbtError {
+    #[error("Missing previous output for input {0}")]
+    MissingPreviousOutput(usize),
+    #[error("Input count {0} does not match the transaction's {1}")]
+    InputCountMismatch(usize, usize),
+    #[error("Input {0} has an unsupported script")]
+    UnsupportedScript(usize),
+    #[error("Input {0} has {1} of {2} required signatures")]
+    MissingSignatures(usize, usize, usize),
+    #[error("Input {0} claims to spend {1}, the previous output holds {2}")]
+    AmountMismatch(usize, Satoshis, Satoshis),
+}
+
+/// Per-input data a signer needs without access to the chain, a simplified
+/// take on BIP174 for P2PKH and bare multisig outputs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PartialInput {
+    amount: Satoshis,
+    script: String,
+    /// Where a signer finds the key, set by coordinators that know it.
+    #[serde(default)]
+    derivation_path: Option<String>,
+    /// Public key to signature (with sighash byte), both hex encoded.
+    partial_signatures: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PartialTransaction {
+    transaction: String,
+    inputs: Vec<PartialInput>,
+}
+
+impl PartialTransaction {
+    pub fn new(
+        transaction: &Transaction,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    ) -> Result<Self> {
+        let inputs = transaction
+            .outpoints()
+            .iter()
+            .enumerate()
+            .map(|(i, outpoint)| {
+                let output = previous_outputs
+                    .get(outpoint)
+                    .ok_or(PsbtError::MissingPreviousOutput(i))?;
+                Ok(PartialInput {
+                    amount: output.amount(),
+                    script: hex::encode(output.script()),
+                    derivation_path: None,
+                    partial_signatures: BTreeMap::new(),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            transaction: transaction.to_hex(),
+            inputs,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let partial: Self = serde_json::from_str(json)?;
+        let transaction = Transaction::from_hex(&partial.transaction)?;
+        if transaction.input_count() != partial.inputs.len() {
+            return Err(PsbtError::InputCountMismatch(
+                partial.inputs.len(),
+                transaction.input_count(),
+            )
+            .into());
+        }
+        Ok(partial)
+    }
+
+    /// Every input carries the spent amount, so the fee is always known.
+    pub fn signing_summary(&self, network: Network) -> Result<SigningSummary> {
+        let transaction = Transaction::from_hex(&self.transaction)?;
+        let previous_outputs = transaction
+            .outpoints()
+            .into_iter()
+            .zip(&self.inputs)
+            .map(|(outpoint, input)| Ok((outpoint, input.previous_output()?)))
+            .collect::<Result<_>>()?;
+        Ok(transaction.signing_summary(&previous_outputs, network))
+    }
+
+    /// Fails if an input spending one of `previous_outputs` gives another
+    /// amount. Signatures commit to the amount, so one signed with a wrong
+    /// amount is rejected by nodes, or misstates the fee to the signer.
+    pub fn check_amounts(&self, previous_outputs: &HashMap<(Vec<u8>, u32), Output>) -> Result<()> {
+        let transaction = Transaction::from_hex(&self.transaction)?;
+        for (i, (outpoint, input)) in transaction.outpoints().iter().zip(&self.inputs).enumerate() {
+            if let Some(output) = previous_outputs.get(outpoint) {
+                if output.amount() != input.amount {
+                    return Err(PsbtError::AmountMismatch(i, input.amount, output.amount()).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Adds a signature for every input key found in `keys`, returning how many
+    /// were added.
+    pub fn sign_available(
+        &mut self,
+        keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    ) -> Result<usize> {
+        self.sign_available_with(keys, SigHashType::default())
+    }
+
+    /// Like [`Self::sign_available`], with signatures covering what
+    /// `sighash_type` selects.
+    pub fn sign_available_with(
+        &mut self,
+        keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+        sighash_type: SigHashType,
+    ) -> Result<usize> {
+        let transaction = Transaction::from_hex(&self.transaction)?;
+        let mut added = 0;
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            let hash = transaction.sighash(i, &input.previous_output()?, sighash_type.value())?;
+
+            for public_key in input.signing_keys(i, keys)? {
+                let public_key_hex = hex::encode(&public_key);
+                if input.partial_signatures.contains_key(&public_key_hex) {
+                    continue;
+                }
+                let Some((secret_key, _)) = keys.get(&ripemd160(&sha256(&public_key))) else {
+                    continue;
+                };
+
+                let signature = secret_key.sign_ecdsa(Message::from_slice(&hash)?);
+                let mut signature = signature.serialize_der().to_vec();
+                signature.push(sighash_type.value() as u8);
+                input
+                    .partial_signatures
+                    .insert(public_key_hex, hex::encode(signature));
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Hashes of every public key able to sign one of the inputs, to look the
+    /// keys up in a wallet that may hold some of them.
+    pub fn key_hashes(&self) -> Result<Vec<[u8; 20]>> {
+        let mut hashes = vec![];
+        for input in &self.inputs {
+            let script = hex::decode(&input.script)?;
+            if let Some((_, public_keys)) = script::parse_multisig(&script) {
+                hashes.extend(public_keys.iter().map(|key| ripemd160(&sha256(key))));
+            } else if let Ok(address) = input.previous_output()?.address() {
+                hashes.push(address);
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Builds the final transaction once every input has enough signatures.
+    pub fn finalize(&self) -> Result<Transaction> {
+        let mut transaction = Transaction::from_hex(&self.transaction)?;
+        for (i, input) in self.inputs.iter().enumerate() {
+            let script = hex::decode(&input.script)?;
+            let mut script_sig = vec![];
+
+            if let Ok(address) = input.previous_output()?.address() {
+                let (public_key, signature) = input
+                    .signatures()?
+                    .into_iter()
+                    .find(|(key, _)| ripemd160(&sha256(key)) == address)
+                    .ok_or(PsbtError::MissingSignatures(i, 0, 1))?;
+                script::push_data(&mut script_sig, &signature);
+                script::push_data(&mut script_sig, &public_key);
+            } else {
+                let (required, public_keys) =
+                    script::parse_multisig(&script).ok_or(PsbtError::UnsupportedScript(i))?;
+                let signatures: BTreeMap<_, _> = input.signatures()?.into_iter().collect();
+                // Signatures have to be in the same order as their keys
+                let ordered: Vec<_> = public_keys
+                    .iter()
+                    .filter_map(|key| signatures.get(key))
+                    .collect();
+                if ordered.len() < required {
+                    return Err(PsbtError::MissingSignatures(i, ordered.len(), required).into());
+                }
+                // CHECKMULTISIG pops one element more than it uses
+                script_sig.push(script::OP_0);
+                for signature in &ordered[..required] {
+                    script::push_data(&mut script_sig, signature);
+                }
+            }
+            transaction.set_script_sig(i, script_sig)?;
+        }
+        Ok(transaction)
+    }
+}
+
+impl PartialInput {
+    fn previous_output(&self) -> Result<Output> {
+        Ok(Output::from_script(self.amount, hex::decode(&self.script)?))
+    }
+
+    fn signatures(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.partial_signatures
+            .iter()
+            .map(|(key, signature)| Ok((hex::decode(key)?, hex::decode(signature)?)))
+            .collect()
+    }
+
+    /// The public keys in `keys` that can sign this input.
+    fn signing_keys(
+        &self,
+        index: usize,
+        keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let script = hex::decode(&self.script)?;
+        if let Some((_, public_keys)) = script::parse_multisig(&script) {
+            return Ok(public_keys);
+        }
+
+        let address = self
+            .previous_output()?
+            .address()
+            .map_err(|_| PsbtError::UnsupportedScript(index))?;
+        let Some((_, public_key)) = keys.get(&address) else {
+            return Ok(vec![]);
+        };
+        // The output may pay to either encoding of the key
+        if ripemd160(&sha256(&public_key.serialize())) == address {
+            Ok(vec![public_key.serialize().to_vec()])
+        } else {
+            Ok(vec![public_key.serialize_uncompressed().to_vec()])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::ecdsa::Signature;
+
+    use super::*;
+    use crate::sending::{Input, SigHashBase};
+
+    const TX_HASH: &str = "ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373";
+
+    fn key(byte: u8) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+        (secret_key, PublicKey::from_secret_key_global(&secret_key))
+    }
+
+    fn lookup(keys: &[(SecretKey, PublicKey)]) -> HashMap<[u8; 20], (SecretKey, PublicKey)> {
+        keys.iter()
+            .map(|k| (ripemd160(&sha256(&k.1.serialize())), *k))
+            .collect()
+    }
+
+    fn spending_transaction() -> Result<Transaction> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(TX_HASH.to_owned(), 0)?);
+        transaction.add_output(Output::new(
+            Satoshis(9_000),
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+            Network::Mainnet,
+        )?);
+        Ok(transaction)
+    }
+
+    fn previous(output: Output) -> Result<HashMap<(Vec<u8>, u32), Output>> {
+        Ok(HashMap::from([((hex::decode(TX_HASH)?, 0), output)]))
+    }
+
+    #[test]
+    fn json_round_trips() -> Result<()> {
+        let transaction = spending_transaction()?;
+        let previous_outputs = previous(Output::new_from_decoded(Satoshis(10_000), [0x0c; 20]))?;
+        let mut partial = PartialTransaction::new(&transaction, &previous_outputs)?;
+        partial.inputs[0].derivation_path = Some("m/0'/0/3".to_owned());
+
+        let parsed = PartialTransaction::from_json(&partial.to_json()?)?;
+
+        assert_eq!(partial, parsed);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_lists_outputs_and_fee() -> Result<()> {
+        let previous_outputs = previous(Output::new_from_decoded(Satoshis(10_000), [0x0c; 20]))?;
+        let partial = PartialTransaction::new(&spending_transaction()?, &previous_outputs)?;
+
+        let summary = partial.signing_summary(Network::Mainnet)?;
+
+        assert_eq!(
+            vec![(
+                "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr".to_owned(),
+                Satoshis(9_000)
+            )],
+            summary.outputs
+        );
+        assert_eq!(Some(Satoshis(1_000)), summary.fee);
+        Ok(())
+    }
+
+    #[test]
+    fn check_amounts_rejects_mismatch() -> Result<()> {
+        let previous_outputs = previous(Output::new_from_decoded(Satoshis(10_000), [0x0c; 20]))?;
+        let mut partial = PartialTransaction::new(&spending_transaction()?, &previous_outputs)?;
+        partial.check_amounts(&previous_outputs)?;
+        partial.check_amounts(&HashMap::new())?;
+
+        partial.inputs[0].amount = Satoshis(20_000);
+
+        let error = partial.check_amounts(&previous_outputs).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(PsbtError::AmountMismatch(
+                0,
+                Satoshis(20_000),
+                Satoshis(10_000)
+            ))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn p2pkh_signs_and_finalizes() -> Result<()> {
+        let owner = key(1);
+        let address = ripemd160(&sha256(&owner.1.serialize()));
+        let transaction = spending_transaction()?;
+        let previous_outputs = previous(Output::new_from_decoded(Satoshis(10_000), address))?;
+        let mut partial = PartialTransaction::new(&transaction, &previous_outputs)?;
+
+        assert!(partial.finalize().is_err());
+        assert_eq!(1, partial.sign_available(&lookup(&[owner]))?);
+
+        partial.finalize()?.verify(&previous_outputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn signs_with_chosen_flags() -> Result<()> {
+        let owner = key(1);
+        let address = ripemd160(&sha256(&owner.1.serialize()));
+        let previous_outputs = previous(Output::new_from_decoded(Satoshis(10_000), address))?;
+        let mut partial = PartialTransaction::new(&spending_transaction()?, &previous_outputs)?;
+        let sighash_type = SigHashType {
+            base: SigHashBase::None,
+            anyone_can_pay: false,
+        };
+
+        assert_eq!(
+            1,
+            partial.sign_available_with(&lookup(&[owner]), sighash_type)?
+        );
+
+        let (_, signature) = &partial.inputs[0].signatures()?[0];
+        assert_eq!(Some(&0x42), signature.last());
+        partial.finalize()?.verify(&previous_outputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn multisig_signed_by_two_parties() -> Result<()> {
+        let (first, second, third) = (key(1), key(2), key(3));
+        let mut script = vec![script::OP_1 + 1];
+        for (_, public_key) in [first, second, third] {
+            script::push_data(&mut script, &public_key.serialize());
+        }
+        script.extend([script::OP_1 + 2, script::OP_CHECKMULTISIG]);
+        let transaction = spending_transaction()?;
+        let previous_outputs = previous(Output::from_script(Satoshis(10_000), script))?;
+        let mut partial = PartialTransaction::new(&transaction, &previous_outputs)?;
+
+        assert_eq!(1, partial.sign_available(&lookup(&[second]))?);
+        assert_eq!(0, partial.sign_available(&lookup(&[second]))?);
+        assert!(partial.finalize().is_err());
+
+        let mut cosigned = PartialTransaction::from_json(&partial.to_json()?)?;
+        assert_eq!(1, cosigned.sign_available(&lookup(&[first]))?);
+        let finalized = cosigned.finalize()?;
+
+        let hash = transaction.signature_hash(0, &previous_outputs[&(hex::decode(TX_HASH)?, 0)])?;
+        for (public_key, signature) in cosigned.inputs[0].signatures()? {
+            let signature = Signature::from_der(&signature[..signature.len() - 1])?;
+            signature.verify(
+                &Message::from_slice(&hash)?,
+                &PublicKey::from_slice(&public_key)?,
+            )?;
+        }
+        assert_ne!(transaction, finalized);
+        Ok(())
+    }
+}
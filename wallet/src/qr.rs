@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use qrcode::{render::svg, QrCode};
+use thiserror::Error;
+
+const CHUNK_PREFIX: &str = "BSVTX";
+/// Characters of the transaction per QR code, well below a code's capacity
+/// so that a phone camera can still read it reliably.
+const CHUNK_SIZE: usize = 800;
+
+#[derive(Debug, Error)]
+enum QrError {
+    #[error("Not a transaction chunk: {0}")]
+    InvalidChunk(String),
+    #[error("Chunk claims {0} parts, expected {1}")]
+    InconsistentTotal(usize, usize),
+    #[error("Missing part {0} of {1}")]
+    MissingChunk(usize, usize),
+    #[error("No chunks given")]
+    Empty,
+}
+
+/// Splits a raw transaction into chunks of the form `BSVTX:<n>/<total>:<hex>`.
+/// Hex is upper cased so the codes can use the denser alphanumeric mode.
+pub fn split_chunks(raw: &str) -> Vec<String> {
+    split_chunks_with(raw, CHUNK_SIZE)
+}
+
+fn split_chunks_with(raw: &str, size: usize) -> Vec<String> {
+    let raw = raw.trim().to_uppercase();
+    let parts: Vec<_> = raw.as_bytes().chunks(size).collect();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            format!(
+                "{CHUNK_PREFIX}:{}/{}:{}",
+                i + 1,
+                parts.len(),
+                String::from_utf8_lossy(part)
+            )
+        })
+        .collect()
+}
+
+/// Joins scanned chunks, in any order, back into the raw transaction.
+pub fn reassemble<S: AsRef<str>>(chunks: &[S]) -> Result<String> {
+    let mut total = None;
+    let mut parts = BTreeMap::new();
+    for chunk in chunks {
+        let (index, count, part) = parse_chunk(chunk.as_ref())?;
+        let expected = *total.get_or_insert(count);
+        if expected != count {
+            return Err(QrError::InconsistentTotal(count, expected).into());
+        }
+        parts.insert(index, part);
+    }
+
+    let total = total.ok_or(QrError::Empty)?;
+    if let Some(missing) = (1..=total).find(|i| !parts.contains_key(i)) {
+        return Err(QrError::MissingChunk(missing, total).into());
+    }
+    Ok(parts.into_values().collect::<String>().to_lowercase())
+}
+
+fn parse_chunk(chunk: &str) -> Result<(usize, usize, &str), QrError> {
+    let invalid = || QrError::InvalidChunk(chunk.to_owned());
+    let mut fields = chunk.trim().splitn(3, ':');
+    if fields.next() != Some(CHUNK_PREFIX) {
+        return Err(invalid());
+    }
+    let (index, total) = fields
+        .next()
+        .and_then(|f| f.split_once('/'))
+        .ok_or_else(invalid)?;
+    let index: usize = index.parse().map_err(|_| invalid())?;
+    let total: usize = total.parse().map_err(|_| invalid())?;
+    if index == 0 || index > total {
+        return Err(invalid());
+    }
+    Ok((index, total, fields.next().ok_or_else(invalid)?))
+}
+
+pub fn to_svg(chunk: &str) -> Result<String> {
+    let code = QrCode::new(chunk.as_bytes())?;
+    Ok(code.render::<svg::Color>().min_dimensions(256, 256).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_transaction_round_trips_through_chunks() -> Result<()> {
+        let raw = "0100000001".to_owned() + &"ab".repeat(1_200) + "00000000";
+
+        let mut chunks = split_chunks(&raw);
+
+        assert_eq!(4, chunks.len());
+        assert!(chunks[0].starts_with("BSVTX:1/4:0100000001AB"));
+        chunks.reverse();
+        assert_eq!(raw, reassemble(&chunks)?);
+        Ok(())
+    }
+
+    #[test]
+    fn reassemble_reports_missing_chunk() {
+        let chunks = split_chunks_with("0100000001abcdef", 4);
+
+        let error = reassemble(&[&chunks[0], &chunks[2], &chunks[3]]).unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<QrError>(),
+            Some(QrError::MissingChunk(2, 4))
+        ));
+        assert!(reassemble(&["BSVTX:0/1:00"]).is_err());
+    }
+}
@@ -1,37 +1,95 @@
+use std::cell::Cell;
+
 use gloo_timers::future::TimeoutFuture;
 
 use crate::util::get_timestamp;
 
+/// Token bucket shared by every request to the API, tokens being refilled at
+/// `capacity` per second. Taking a token needs no exclusive borrow, so
+/// concurrent tasks can share one limiter.
 pub struct RateLimiter {
     capacity: u32,
-    tokens: u32,
-    last_update: f64,
+    tokens: Cell<u32>,
+    last_update: Cell<f64>,
+    clock: fn() -> f64,
 }
 
 impl RateLimiter {
     pub fn new(capacity: u32) -> Self {
+        Self::with_clock(capacity, get_timestamp)
+    }
+
+    pub fn with_clock(capacity: u32, clock: fn() -> f64) -> Self {
         Self {
             capacity,
-            tokens: capacity,
-            last_update: get_timestamp(),
+            tokens: Cell::new(capacity),
+            last_update: Cell::new(clock()),
+            clock,
         }
     }
 
-    pub async fn take(&mut self) {
-        while self.tokens == 0 {
+    pub async fn take(&self) {
+        while self.tokens.get() == 0 {
             TimeoutFuture::new(100).await;
             self.update_tokens()
         }
 
-        self.tokens -= 1;
+        self.tokens.set(self.tokens.get() - 1);
     }
 
-    fn update_tokens(&mut self) {
-        let elapsed = get_timestamp() - self.last_update;
+    /// Adds the whole tokens earned since the last update. The clock only
+    /// advances by the time those tokens took, so polls shorter than a token
+    /// still add up.
+    fn update_tokens(&self) {
+        let now = (self.clock)();
+        let elapsed = now - self.last_update.get();
         let tokens_to_add = (elapsed / 1000.0 * self.capacity as f64).floor() as u32;
 
-        self.tokens += tokens_to_add;
-        self.tokens = self.tokens.min(self.capacity);
-        self.last_update = get_timestamp();
+        let tokens = self.tokens.get() + tokens_to_add;
+        if tokens >= self.capacity {
+            self.tokens.set(self.capacity);
+            self.last_update.set(now);
+        } else {
+            self.tokens.set(tokens);
+            let earned_ms = tokens_to_add as f64 * 1000.0 / self.capacity as f64;
+            self.last_update.set(self.last_update.get() + earned_ms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::RateLimiter;
+
+    thread_local! {
+        static NOW: Cell<f64> = const { Cell::new(0.0) };
+    }
+
+    fn fake_clock() -> f64 {
+        NOW.with(Cell::get)
+    }
+
+    #[test]
+    fn update_tokens_refills_from_clock() {
+        NOW.with(|now| now.set(1_000.0));
+        let limiter = RateLimiter::with_clock(3, fake_clock);
+        limiter.tokens.set(0);
+
+        // Polls shorter than a token add up, a token every 333 ms
+        for (time, tokens) in [(1_100.0, 0), (1_200.0, 0), (1_300.0, 0), (1_400.0, 1)] {
+            NOW.with(|now| now.set(time));
+            limiter.update_tokens();
+            assert_eq!(tokens, limiter.tokens.get(), "at {time}");
+        }
+
+        NOW.with(|now| now.set(1_700.0));
+        limiter.update_tokens();
+        assert_eq!(2, limiter.tokens.get());
+
+        NOW.with(|now| now.set(10_000.0));
+        limiter.update_tokens();
+        assert_eq!(3, limiter.tokens.get());
     }
 }
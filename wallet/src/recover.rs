@@ -1,15 +1,17 @@
-use gloo_dialogs::alert;
+use gloo_dialogs::{alert, confirm};
 use wasm_bindgen::prelude::*;
-use web_sys::{Event, HtmlInputElement};
+use web_sys::{Event, HtmlInputElement, HtmlSelectElement};
 use yew::{platform::spawn_local, prelude::*};
 
 use crate::{
-    bip39::Seed,
-    util::{self, log},
+    backup,
+    bip32::XPrv,
+    bip39::{self, Bip39Error, Language, Seed},
+    encryption::{self, EncryptedData, EncryptionError},
+    storage::{self, StorageKey},
+    util::log,
 };
 
-const WORDS: &str = include_str!("english.txt");
-
 #[derive(Properties, PartialEq)]
 pub struct RecoverProps {
     pub on_recover: Callback<()>,
@@ -27,29 +29,228 @@ pub fn recover(RecoverProps { on_recover }: &RecoverProps) -> Html {
         }
     };
 
+    // Follows the words typed until a language is picked, detection only
+    // picks languages whose words are checked
+    let chosen_language = use_state(|| None::<Language>);
+    let language = chosen_language.unwrap_or_else(|| Language::detect(&mnemonic_words));
+    let set_language = {
+        let chosen_language = chosen_language.clone();
+        move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(language) = select.value().parse() {
+                chosen_language.set(Some(language));
+            }
+        }
+    };
+    let languages: Vec<_> = Language::ALL
+        .iter()
+        .map(|option| {
+            html! {
+                <option value={option.name()} selected={*option == language}>{option.name()}</option>
+            }
+        })
+        .collect();
+
+    let password = use_state(String::default);
+    let set_password = {
+        let password = password.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        }
+    };
+    let confirmation = use_state(String::default);
+    let set_confirmation = {
+        let confirmation = confirmation.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            confirmation.set(input.value());
+        }
+    };
+
     let recover_clicked = {
         let on_recover = on_recover.clone();
         move |_| {
-            let on_recover = on_recover.clone();
-            let seed = Seed::generate(&mnemonic_words.join(" "), "");
-            let xprv = seed.to_xprv().expect("Should create a private key");
-            spawn_local(async move {
-                let serialized = String::from(&xprv);
-                let Err(error) = util::store_save("xprv", &serialized).await else {
-                    on_recover.emit(());
+            if let Err(error) = encryption::validate_password(&password, &confirmation) {
+                alert(&format!("Unable to recover wallet: {error}"));
+                return;
+            }
+
+            let mnemonic = match bip39::check_phrase(&mnemonic_words, language) {
+                Ok(mnemonic) => mnemonic,
+                Err(error) => {
+                    if let Bip39Error::EmptyWords(positions) = &error {
+                        highlight_empty(positions);
+                    }
+                    alert(&format!("Unable to recover wallet: {error}"));
                     return;
-                };
-                alert(&format!("Unable to save wallet: {error:?}"));
-            });
+                }
+            };
+            if language.words().is_none()
+                && !confirm(
+                    "The words of this phrase can't be checked, a single typo recovers a \
+                    different, empty wallet. Recover anyway?",
+                )
+            {
+                return;
+            }
+            let seed = Seed::generate(&mnemonic, "");
+            let xprv = seed.to_xprv().expect("Should create a private key");
+            store_wallet(&xprv, &password, on_recover.clone());
         }
     };
 
     html! {
         <>
             <h1>{"Options"}</h1>
-            <MnemonicInput word_changed={word_changed}/>
-            <MnemonicDatalist/>
+            <label for="language">{"Phrase language:"}</label>
+            <select id="language" onchange={set_language}>
+                { languages }
+            </select>
+            <MnemonicInput word_changed={word_changed} language={language}/>
+            <MnemonicDatalist language={language}/>
+            <label for="password">{"Password:"}</label>
+            <input id="password" type="password" oninput={set_password}/>
+            <label for="password_confirmation">{"Confirm password:"}</label>
+            <input id="password_confirmation" type="password" oninput={set_confirmation}/>
             <button onclick={recover_clicked}>{"Recover"}</button>
+            <ImportBackup on_recover={on_recover.clone()} />
+        </>
+    }
+}
+
+/// Marks the cells at the 1-based `positions` as missing their word.
+fn highlight_empty(positions: &[usize]) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    for position in positions {
+        let cell = document
+            .get_element_by_id(&format!("word{}", position - 1))
+            .and_then(|element| element.dyn_into::<HtmlInputElement>().ok());
+        if let Some(cell) = cell {
+            cell.set_custom_validity("Missing word");
+        }
+    }
+}
+
+/// Encrypts the wallet with `password` and stores it.
+fn store_wallet(xprv: &XPrv, password: &str, on_recover: Callback<()>) {
+    let serialized = String::from(xprv);
+    let encrypted = match EncryptedData::encrypt(serialized.as_bytes(), password) {
+        Ok(encrypted) => encrypted,
+        Err(error) => {
+            alert(&format!("Unable to encrypt wallet: {error:?}"));
+            return;
+        }
+    };
+    spawn_local(async move {
+        let Err(error) = storage::save(StorageKey::EncryptedXprv, &encrypted).await else {
+            on_recover.emit(());
+            return;
+        };
+        alert(&format!("Unable to save wallet: {error:?}"));
+    });
+}
+
+/// Restores the wallet from a backup file, protecting it with the backup's
+/// password.
+#[function_component(ImportBackup)]
+fn import_backup(RecoverProps { on_recover }: &RecoverProps) -> Html {
+    let contents = use_state(String::default);
+    let set_file = {
+        let contents = contents.clone();
+        move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            let contents = contents.clone();
+            spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(file.text()).await {
+                    Ok(text) => contents.set(text.as_string().unwrap_or_default()),
+                    Err(error) => alert(&format!("Unable to read backup: {error:?}")),
+                }
+            });
+        }
+    };
+
+    let password = use_state(String::default);
+    let set_password = {
+        let password = password.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        }
+    };
+
+    let import_clicked = {
+        let on_recover = on_recover.clone();
+        move |_| match backup::import(&contents, &password) {
+            Ok(xprv) => store_wallet(&xprv, &password, on_recover.clone()),
+            Err(error) => alert(&format!("Unable to import backup: {error}")),
+        }
+    };
+
+    html! {
+        <>
+            <label for="backup_file">{"Or restore a backup:"}</label>
+            <input id="backup_file" type="file" accept=".beesv" onchange={set_file}/>
+            <label for="backup_password">{"Backup password:"}</label>
+            <input id="backup_password" type="password" oninput={set_password}/>
+            <button onclick={import_clicked}>{"Restore"}</button>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct UnlockProps {
+    pub encrypted: EncryptedData,
+    pub on_unlock: Callback<XPrv>,
+}
+
+#[function_component(Unlock)]
+pub fn unlock(
+    UnlockProps {
+        encrypted,
+        on_unlock,
+    }: &UnlockProps,
+) -> Html {
+    let password = use_state(String::default);
+    let set_password = {
+        let password = password.clone();
+        move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        }
+    };
+
+    let unlock_clicked = {
+        let encrypted = encrypted.clone();
+        let on_unlock = on_unlock.clone();
+        move |_| match encrypted.decrypt(&password) {
+            Ok(decrypted) => {
+                let xprv = String::from_utf8(decrypted)
+                    .ok()
+                    .and_then(|xprv| xprv.parse::<XPrv>().ok());
+                match xprv {
+                    Some(xprv) => on_unlock.emit(xprv),
+                    None => alert("Stored wallet is corrupt, please recover it again"),
+                }
+            }
+            Err(EncryptionError::WrongPassword) => alert("Wrong password, please try again"),
+            Err(error) => alert(&format!(
+                "Stored wallet is corrupt, please recover it again: {error}"
+            )),
+        }
+    };
+
+    html! {
+        <>
+            <h1>{"Unlock wallet"}</h1>
+            <label for="password">{"Password:"}</label>
+            <input id="password" type="password" oninput={set_password}/>
+            <button onclick={unlock_clicked}>{"Unlock"}</button>
         </>
     }
 }
@@ -57,14 +258,20 @@ pub fn recover(RecoverProps { on_recover }: &RecoverProps) -> Html {
 #[derive(Properties, PartialEq)]
 struct MnemonicInputProps {
     word_changed: Callback<(u32, String)>,
+    language: Language,
 }
 
 #[function_component(MnemonicInput)]
-fn mnemonic_input(MnemonicInputProps { word_changed }: &MnemonicInputProps) -> Html {
+fn mnemonic_input(
+    MnemonicInputProps {
+        word_changed,
+        language,
+    }: &MnemonicInputProps,
+) -> Html {
     let rows: Vec<_> = (0..4)
         .map(|row| {
             html! {
-                <MnemonicRow number={row} word_changed={word_changed.clone()} />
+                <MnemonicRow number={row} word_changed={word_changed.clone()} language={*language}/>
             }
         })
         .collect();
@@ -80,6 +287,7 @@ fn mnemonic_input(MnemonicInputProps { word_changed }: &MnemonicInputProps) -> H
 struct RowProps {
     number: u32,
     word_changed: Callback<(u32, String)>,
+    language: Language,
 }
 
 #[function_component(MnemonicRow)]
@@ -87,13 +295,14 @@ fn mnemonic_row(
     RowProps {
         number,
         word_changed,
+        language,
     }: &RowProps,
 ) -> Html {
     let columns: Vec<_> = (0..3)
         .map(|column| {
             let index = number * 3 + column;
             html! {
-                <MnemonicCell index={index} word_changed={word_changed.clone()}/>
+                <MnemonicCell index={index} word_changed={word_changed.clone()} language={*language}/>
             }
         })
         .collect();
@@ -109,6 +318,7 @@ fn mnemonic_row(
 struct CellProps {
     index: u32,
     word_changed: Callback<(u32, String)>,
+    language: Language,
 }
 
 #[function_component(MnemonicCell)]
@@ -116,6 +326,7 @@ fn mnemonic_cell(
     CellProps {
         index,
         word_changed,
+        language,
     }: &CellProps,
 ) -> Html {
     let id = format!("word{index}");
@@ -135,27 +346,37 @@ fn mnemonic_cell(
         }
     };
 
+    // Only words of a bundled wordlist are suggested and checked
+    let language = *language;
+    let checked = language.words().is_some();
     let on_change = {
         move |e: Event| {
             let target = e.target();
             let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
 
-            if let Some(input) = input {
-                check_word(&input);
+            if let Some(input) = input.filter(|_| checked) {
+                check_word(&input, language);
             }
         }
     };
 
     html! {
         <div class="cell">
-            <input id={id} oninput={on_input.clone()} onchange={on_change} type="text" list="word_list" placeholder={placeholder}/>
+            <input id={id} oninput={on_input.clone()} onchange={on_change} type="text" list={checked.then_some("word_list")} placeholder={placeholder}/>
         </div>
     }
 }
 
+#[derive(Properties, PartialEq)]
+struct DatalistProps {
+    language: Language,
+}
+
 #[function_component(MnemonicDatalist)]
-fn mnemonic_datalist() -> Html {
-    let words: Vec<_> = WORDS
+fn mnemonic_datalist(DatalistProps { language }: &DatalistProps) -> Html {
+    let words: Vec<_> = language
+        .words()
+        .unwrap_or_default()
         .lines()
         .map(|word| {
             html! {
@@ -171,9 +392,8 @@ fn mnemonic_datalist() -> Html {
     }
 }
 
-fn check_word(input: &HtmlInputElement) {
-    let input_word = input.value();
-    if !WORDS.contains(&input_word.to_lowercase()) {
+fn check_word(input: &HtmlInputElement, language: Language) {
+    if !language.contains(&input.value()) {
         log("Showing error");
         input.set_custom_validity("Unrecognized word");
         input.report_validity();
@@ -1 +1,340 @@
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4C;
+pub const OP_PUSHDATA2: u8 = 0x4D;
+pub const OP_PUSHDATA4: u8 = 0x4E;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_RETURN: u8 = 0x6A;
+pub const OP_DROP: u8 = 0x75;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_HASH160: u8 = 0xA9;
 pub const OP_CODESEPARATOR: u8 = 0xAB;
+pub const OP_CHECKSIG: u8 = 0xAC;
+pub const OP_CHECKMULTISIG: u8 = 0xAE;
+pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xB1;
+
+/// Locktimes below this are block heights, the rest unix timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// Appends a push of `data` to `script`, using the shortest encoding of
+/// its length.
+pub fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    let length = data.len();
+    if length < OP_PUSHDATA1 as usize {
+        script.push(length as u8);
+    } else if let Ok(length) = u8::try_from(length) {
+        script.push(OP_PUSHDATA1);
+        script.push(length);
+    } else if let Ok(length) = u16::try_from(length) {
+        script.push(OP_PUSHDATA2);
+        script.extend(length.to_le_bytes());
+    } else {
+        let length = u32::try_from(length).expect("Scripts are far smaller than 4 GiB");
+        script.push(OP_PUSHDATA4);
+        script.extend(length.to_le_bytes());
+    }
+    script.extend(data);
+}
+
+/// Whether `script` is an unspendable data carrier, `OP_RETURN` optionally
+/// preceded by `OP_FALSE`.
+pub fn is_op_return(script: &[u8]) -> bool {
+    matches!(script, [OP_RETURN, ..] | [OP_0, OP_RETURN, ..])
+}
+
+/// Returns the public key hash of a pay to public key hash script.
+pub fn parse_p2pkh(script: &[u8]) -> Option<[u8; 20]> {
+    match script {
+        [OP_DUP, OP_HASH160, 0x14, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] => hash.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the locktime and public key hash of a pay to public key hash
+/// script prefixed with `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP`.
+pub fn parse_cltv_p2pkh(script: &[u8]) -> Option<(u32, [u8; 20])> {
+    let (&length, rest) = script.split_first()?;
+    // Script numbers used by CHECKLOCKTIMEVERIFY are at most 5 bytes
+    if !(1..=5).contains(&length) || rest.len() < length as usize + 2 {
+        return None;
+    }
+    let (number, rest) = rest.split_at(length as usize);
+    match rest {
+        [OP_CHECKLOCKTIMEVERIFY, OP_DROP, p2pkh @ ..] => {
+            Some((parse_locktime(number)?, parse_p2pkh(p2pkh)?))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a little endian script number, rejecting negative ones and those
+/// not fitting a transaction locktime.
+fn parse_locktime(number: &[u8]) -> Option<u32> {
+    if number.last()? & 0x80 != 0 {
+        return None;
+    }
+    let value = number
+        .iter()
+        .rev()
+        .fold(0u64, |value, &byte| value << 8 | byte as u64);
+    value.try_into().ok()
+}
+
+/// Returns the required signature count and public keys of a bare multisig
+/// script.
+pub fn parse_multisig(script: &[u8]) -> Option<(usize, Vec<Vec<u8>>)> {
+    let (&first, rest) = script.split_first()?;
+    let (&last, rest) = rest.split_last()?;
+    let (&count, mut rest) = rest.split_last()?;
+    if last != OP_CHECKMULTISIG || !(OP_1..=OP_16).contains(&first) {
+        return None;
+    }
+    if !(OP_1..=OP_16).contains(&count) {
+        return None;
+    }
+
+    let mut public_keys = vec![];
+    while let Some((&length, remaining)) = rest.split_first() {
+        let length = length as usize;
+        if !(33..=65).contains(&length) || remaining.len() < length {
+            return None;
+        }
+        public_keys.push(remaining[..length].to_vec());
+        rest = &remaining[length..];
+    }
+
+    let required = (first - OP_1 + 1) as usize;
+    if public_keys.len() != (count - OP_1 + 1) as usize || required > public_keys.len() {
+        return None;
+    }
+    Some((required, public_keys))
+}
+
+/// Names of the standard script templates `script` matches, if any.
+pub fn template_name(script: &[u8]) -> Option<&'static str> {
+    if parse_p2pkh(script).is_some() {
+        Some("P2PKH")
+    } else if parse_p2sh(script).is_some() {
+        Some("P2SH")
+    } else if is_op_return(script) {
+        Some("OP_RETURN data")
+    } else if parse_cltv_p2pkh(script).is_some() {
+        Some("Time locked P2PKH")
+    } else if parse_multisig(script).is_some() {
+        Some("Bare multisig")
+    } else {
+        None
+    }
+}
+
+/// Returns the script hash of a pay to script hash script.
+pub fn parse_p2sh(script: &[u8]) -> Option<[u8; 20]> {
+    match script {
+        [OP_HASH160, 0x14, hash @ .., OP_EQUAL] => hash.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Human readable opcodes of `script`, pushed data as hex. A push running
+/// past the end of the script ends it with `[truncated pushdata]`.
+pub fn disassemble(script: &[u8]) -> String {
+    let mut parts = vec![];
+    let mut rest = script;
+    while let Some((&opcode, remaining)) = rest.split_first() {
+        rest = remaining;
+        let length_size = match opcode {
+            OP_PUSHDATA1 => 1,
+            OP_PUSHDATA2 => 2,
+            OP_PUSHDATA4 => 4,
+            0x01..OP_PUSHDATA1 => 0,
+            _ => {
+                parts.push(opcode_name(opcode));
+                continue;
+            }
+        };
+        let Some((length, data)) = read_push(opcode, length_size, rest) else {
+            parts.push("[truncated pushdata]".to_owned());
+            break;
+        };
+        parts.push(hex::encode(&data[..length]));
+        rest = &data[length..];
+    }
+    parts.join(" ")
+}
+
+/// Length and the bytes following the length of a push, `None` if the
+/// script ends before the pushed data does.
+fn read_push(opcode: u8, length_size: usize, rest: &[u8]) -> Option<(usize, &[u8])> {
+    if length_size == 0 {
+        let length = opcode as usize;
+        return (rest.len() >= length).then_some((length, rest));
+    }
+    let (length, data) = rest.split_at_checked(length_size)?;
+    let length = length
+        .iter()
+        .rev()
+        .fold(0usize, |value, &byte| value << 8 | byte as usize);
+    (data.len() >= length).then_some((length, data))
+}
+
+fn opcode_name(opcode: u8) -> String {
+    let name = match opcode {
+        OP_0 => "OP_0",
+        0x4F => "OP_1NEGATE",
+        0x50 => "OP_RESERVED",
+        OP_1..=OP_16 => return format!("OP_{}", opcode - OP_1 + 1),
+        0x61 => "OP_NOP",
+        0x63 => "OP_IF",
+        0x64 => "OP_NOTIF",
+        0x67 => "OP_ELSE",
+        0x68 => "OP_ENDIF",
+        0x69 => "OP_VERIFY",
+        OP_RETURN => "OP_RETURN",
+        0x6B => "OP_TOALTSTACK",
+        0x6C => "OP_FROMALTSTACK",
+        0x73 => "OP_IFDUP",
+        0x74 => "OP_DEPTH",
+        OP_DROP => "OP_DROP",
+        OP_DUP => "OP_DUP",
+        0x77 => "OP_NIP",
+        0x78 => "OP_OVER",
+        0x79 => "OP_PICK",
+        0x7A => "OP_ROLL",
+        0x7B => "OP_ROT",
+        0x7C => "OP_SWAP",
+        0x7D => "OP_TUCK",
+        0x7E => "OP_CAT",
+        0x7F => "OP_SPLIT",
+        0x82 => "OP_SIZE",
+        OP_EQUAL => "OP_EQUAL",
+        OP_EQUALVERIFY => "OP_EQUALVERIFY",
+        0x93 => "OP_ADD",
+        0x94 => "OP_SUB",
+        0x9C => "OP_NUMEQUAL",
+        0x9D => "OP_NUMEQUALVERIFY",
+        0xA6 => "OP_RIPEMD160",
+        0xA7 => "OP_SHA1",
+        0xA8 => "OP_SHA256",
+        OP_HASH160 => "OP_HASH160",
+        0xAA => "OP_HASH256",
+        OP_CODESEPARATOR => "OP_CODESEPARATOR",
+        OP_CHECKSIG => "OP_CHECKSIG",
+        0xAD => "OP_CHECKSIGVERIFY",
+        OP_CHECKMULTISIG => "OP_CHECKMULTISIG",
+        0xAF => "OP_CHECKMULTISIGVERIFY",
+        OP_CHECKLOCKTIMEVERIFY => "OP_CHECKLOCKTIMEVERIFY",
+        0xB2 => "OP_CHECKSEQUENCEVERIFY",
+        _ => return format!("OP_UNKNOWN_{opcode:#04x}"),
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_multisig_reads_keys() {
+        let keys = vec![vec![0x02; 33], vec![0x03; 33], vec![0x04; 65]];
+        let mut script = vec![OP_1 + 1];
+        for key in &keys {
+            push_data(&mut script, key);
+        }
+        script.extend([OP_1 + 2, OP_CHECKMULTISIG]);
+
+        assert_eq!(Some((2, keys)), parse_multisig(&script));
+        assert_eq!(None, parse_multisig(&script[..script.len() - 1]));
+    }
+
+    #[test]
+    fn parse_cltv_p2pkh_reads_locktime() {
+        let mut p2pkh = vec![OP_DUP, OP_HASH160];
+        push_data(&mut p2pkh, &[0x0c; 20]);
+        p2pkh.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+        let cltv = |number: &[u8]| {
+            let mut script = vec![];
+            push_data(&mut script, number);
+            script.extend([OP_CHECKLOCKTIMEVERIFY, OP_DROP]);
+            script.extend(&p2pkh);
+            script
+        };
+
+        assert_eq!(Some([0x0c; 20]), parse_p2pkh(&p2pkh));
+        assert_eq!(
+            Some((800_000, [0x0c; 20])),
+            parse_cltv_p2pkh(&cltv(&[0x00, 0x35, 0x0c]))
+        );
+        assert_eq!(
+            Some((u32::MAX, [0x0c; 20])),
+            parse_cltv_p2pkh(&cltv(&[0xff, 0xff, 0xff, 0xff, 0x00]))
+        );
+        // Negative
+        assert_eq!(None, parse_cltv_p2pkh(&cltv(&[0x00, 0x35, 0x8c])));
+        assert_eq!(None, parse_cltv_p2pkh(&p2pkh));
+    }
+
+    #[test]
+    fn disassemble_recognizes_templates() -> anyhow::Result<()> {
+        let p2pkh = hex::decode("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac")?;
+        assert_eq!(
+            "OP_DUP OP_HASH160 89abcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG",
+            disassemble(&p2pkh)
+        );
+        assert_eq!(Some("P2PKH"), template_name(&p2pkh));
+
+        let p2sh = hex::decode("a914748284390f9e263a4b766a75d0633c50426eb87587")?;
+        assert_eq!(
+            "OP_HASH160 748284390f9e263a4b766a75d0633c50426eb875 OP_EQUAL",
+            disassemble(&p2sh)
+        );
+        assert_eq!(Some("P2SH"), template_name(&p2sh));
+
+        let mut data = vec![OP_0, OP_RETURN];
+        push_data(&mut data, &[0xbe; 80]);
+        assert_eq!(
+            format!("OP_0 OP_RETURN {}", "be".repeat(80)),
+            disassemble(&data)
+        );
+        assert_eq!(Some("OP_RETURN data"), template_name(&data));
+
+        assert_eq!(None, template_name(&[OP_1, OP_1 + 1]));
+        assert_eq!("OP_1 OP_2", disassemble(&[OP_1, OP_1 + 1]));
+        Ok(())
+    }
+
+    #[test]
+    fn push_data_encodes_length() {
+        let pushed = |length: usize| {
+            let mut script = vec![];
+            push_data(&mut script, &vec![0xbe; length]);
+            script.truncate(script.len() - length);
+            script
+        };
+        assert_eq!(vec![0x4b], pushed(75));
+        assert_eq!(vec![OP_PUSHDATA1, 0x4c], pushed(76));
+        assert_eq!(vec![OP_PUSHDATA1, 0xff], pushed(255));
+        assert_eq!(vec![OP_PUSHDATA2, 0x00, 0x01], pushed(256));
+        assert_eq!(vec![OP_PUSHDATA2, 0xff, 0xff], pushed(65_535));
+        assert_eq!(vec![OP_PUSHDATA4, 0x00, 0x00, 0x01, 0x00], pushed(65_536));
+
+        let mut script = vec![];
+        push_data(&mut script, &[0xbe; 300]);
+        assert_eq!("be".repeat(300), disassemble(&script));
+    }
+
+    #[test]
+    fn disassemble_flags_truncated_pushdata() {
+        assert_eq!(
+            "OP_DUP OP_HASH160 [truncated pushdata]",
+            disassemble(&[OP_DUP, OP_HASH160, 0x14, 0x89, 0xab])
+        );
+        assert_eq!("[truncated pushdata]", disassemble(&[OP_PUSHDATA2, 0xff]));
+        assert_eq!(
+            "OP_RETURN [truncated pushdata]",
+            disassemble(&[OP_RETURN, OP_PUSHDATA4, 0x02, 0x00, 0x00, 0x00, 0xbe])
+        );
+        assert_eq!("", disassemble(&[]));
+    }
+}
@@ -1,10 +1,50 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{collections::HashMap, fmt::Debug, hash::Hash, str::FromStr};
 
 use anyhow::Result;
 use secp256k1::{ecdsa::Signature, Message, PublicKey, SecretKey};
+use serde::Serialize;
 use thiserror::Error;
 
-use crate::{script, util::double_sha256};
+use crate::{
+    script,
+    transactions::RichOutput,
+    util::{
+        self, double_sha256, ripemd160, sha256, validate_address, AddressError, Network, Satoshis,
+        SATOSHIS_PER_BSV,
+    },
+    Error,
+};
+
+/// Total supply, no output can hold more than this.
+const MAX_MONEY: Satoshis = Satoshis(21_000_000 * SATOSHIS_PER_BSV);
+
+/// Sequence of inputs that don't enable the transaction's locktime.
+const SEQUENCE_FINAL: u32 = 0xFFFF_FFFF;
+
+/// Largest transaction relayed under the default standardness policy.
+pub const MAX_STANDARD_TX_SIZE: u64 = 100_000;
+
+/// Outputs below this amount cost more to spend than they are worth and are
+/// rejected by relay policy when created.
+pub const DUST_LIMIT: Satoshis = Satoshis(546);
+
+/// Most bytes a data output can carry, as a single push.
+const MAX_DATA_SIZE: usize = 255;
+
+/// Serialized size of a P2PKH output.
+const CHANGE_OUTPUT_SIZE: u64 = 34;
+
+/// Smallest amount that may be sent, before considering the dust limit.
+const MIN_SEND_AMOUNT: Satoshis = Satoshis(1);
+
+/// Sends whose fee exceeds this percentage of the sent value get a warning.
+const HIGH_FEE_PERCENT: u64 = 10;
+
+/// Outputs below this multiple of the dust limit get a warning.
+const NEAR_DUST_MULTIPLE: u64 = 2;
+
+/// Fee rate in satoshis per byte used when no other rate is chosen.
+pub const DEFAULT_FEE_RATE: u64 = 1;
 
 struct SigHash {
     value: u32,
@@ -62,6 +102,81 @@ impl BaseSigHash {
     }
 }
 
+/// Which outputs a signature commits to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SigHashBase {
+    #[default]
+    All,
+    None,
+    Single,
+}
+
+impl SigHashBase {
+    pub const ALL: [SigHashBase; 3] = [SigHashBase::All, SigHashBase::None, SigHashBase::Single];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SigHashBase::All => "ALL",
+            SigHashBase::None => "NONE",
+            SigHashBase::Single => "SINGLE",
+        }
+    }
+}
+
+impl FromStr for SigHashBase {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        SigHashBase::ALL
+            .into_iter()
+            .find(|base| base.name() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown sighash type: {value}"))
+    }
+}
+
+/// The sighash flags to sign with, always including the fork id.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SigHashType {
+    pub base: SigHashBase,
+    pub anyone_can_pay: bool,
+}
+
+impl SigHashType {
+    fn sig_hash(&self) -> SigHash {
+        let base = match self.base {
+            SigHashBase::All => 0x01,
+            SigHashBase::None => 0x02,
+            SigHashBase::Single => 0x03,
+        };
+        let anyone_can_pay = if self.anyone_can_pay { 0x80 } else { 0x00 };
+        SigHash {
+            value: base | 0x40 | anyone_can_pay,
+        }
+    }
+
+    /// The sighash value as signed, including the fork id flag.
+    pub fn value(&self) -> u32 {
+        self.sig_hash().value
+    }
+
+    /// What signing with these flags allows others to change, `None` for the
+    /// default which commits to the whole transaction.
+    pub fn warning(&self) -> Option<String> {
+        let outputs = match self.base {
+            SigHashBase::All => None,
+            SigHashBase::None => Some("anyone can change where the coins are sent"),
+            SigHashBase::Single => {
+                Some("outputs other than the one matching each input can be changed")
+            }
+        };
+        let inputs = self
+            .anyone_can_pay
+            .then_some("others can add inputs to the transaction");
+        let warnings: Vec<_> = outputs.into_iter().chain(inputs).collect();
+        (!warnings.is_empty()).then(|| format!("Once signed, {}.", warnings.join(" and ")))
+    }
+}
+
 impl From<i32> for SigHash {
     fn from(value: i32) -> Self {
         Self {
@@ -80,6 +195,58 @@ pub enum SignatureError {
     MissingKey,
     #[error("Invalid script")]
     InvalidScript,
+    #[error("Previous output amount {0} exceeds the money supply")]
+    InvalidAmount(u64),
+    #[error("SIGHASH_SINGLE needs an output per input, found {0} for {1}")]
+    SingleWithoutOutput(usize, usize),
+    #[error("Input {0}: unable to parse signature")]
+    InvalidSignature(usize),
+    #[error("Input {0}: unable to parse public key")]
+    InvalidPublicKey(usize),
+    #[error("Input {0}: signature does not match")]
+    VerificationFailed(usize),
+    #[error("Input {0}: signature is not strict DER")]
+    NonCanonicalDer(usize),
+    #[error("Input {0}: signature has a high S value")]
+    HighS(usize),
+    #[error("Input {0}: locked until {1}")]
+    Locked(usize, u32),
+    #[error("Inputs are locked both by block height and by time")]
+    MixedLocktimes,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StandardnessError {
+    #[error("Transaction has no inputs")]
+    NoInputs,
+    #[error("Transaction size {0} exceeds the limit of {1} bytes")]
+    TooLarge(u64, u64),
+    #[error("Output {0} of {1} is below the dust limit")]
+    DustOutput(usize, Satoshis),
+    #[error("Data output {0} carries {1}, it must carry none")]
+    ValuedDataOutput(usize, Satoshis),
+}
+
+/// Reasons a valid send may still be a poor idea.
+#[derive(Debug, Error, PartialEq)]
+pub enum SendWarning {
+    #[error("Fee of {0} is more than {HIGH_FEE_PERCENT}% of the {1} sent")]
+    HighFee(Satoshis, Satoshis),
+    #[error("Output {0} of {1} is close to the dust limit")]
+    NearDust(usize, Satoshis),
+    #[error("This spends every coin without change, emptying the wallet")]
+    EmptiesWallet,
+}
+
+/// Result of verifying each input, in order.
+pub struct VerificationReport {
+    pub inputs: Vec<Result<(), SignatureError>>,
+}
+
+impl VerificationReport {
+    pub fn is_valid(&self) -> bool {
+        self.inputs.iter().all(Result::is_ok)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -91,17 +258,21 @@ pub struct Input {
 }
 
 impl Input {
-    pub fn new_decoded(tx_hash: Vec<u8>, index: u32) -> Self {
-        Self {
+    /// Fails unless `tx_hash` is the 32 bytes of a transaction id.
+    pub fn new_decoded(tx_hash: Vec<u8>, index: u32) -> Result<Self, SendingError> {
+        if tx_hash.len() != 32 {
+            return Err(SendingError::TxHashLength(tx_hash.len()));
+        }
+        Ok(Self {
             tx_hash,
             index,
             script_sig: vec![],
-            sequence: 0xFFFF_FFFF,
-        }
+            sequence: SEQUENCE_FINAL,
+        })
     }
 
-    pub fn new(tx_hash: String, index: u32) -> Result<Self> {
-        Ok(Input::new_decoded(hex::decode(tx_hash)?, index))
+    pub fn new(tx_hash: String, index: u32) -> Result<Self, SendingError> {
+        Input::new_decoded(hex::decode(tx_hash)?, index)
     }
 }
 
@@ -134,70 +305,136 @@ impl From<&Input> for Vec<u8> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Output {
-    amount: u64,
+    amount: Satoshis,
     script: Vec<u8>,
 }
 
 #[derive(Error, Debug)]
-enum SendingError {
-    #[error("Invalid address: {0}")]
-    InvalidAddress(String),
-    #[error("Address checksum error")]
-    ChecksumError,
+pub enum SendingError {
+    #[error("Insufficient balance, missing {0}")]
+    InsufficientBalance(Satoshis),
+    #[error("Insufficient BSV for transaction+fee: {0}")]
+    InsufficientFee(Satoshis),
+    #[error("Selected outputs are insufficient, missing {0}")]
+    InsufficientSelection(Satoshis),
+    #[error("At least two outputs are needed to consolidate, found {0}")]
+    NothingToConsolidate(usize),
+    #[error("Fee {0} exceeds the consolidated value {1}")]
+    FeeExceedsValue(Satoshis, Satoshis),
+    #[error("Amount must be at least {MIN_SEND_AMOUNT}")]
+    AmountTooSmall,
+    #[error("Amount {0} is below the dust limit of {1}")]
+    BelowDust(Satoshis, Satoshis),
+    #[error("Data of {0} bytes exceeds the {MAX_DATA_SIZE} bytes an output can carry")]
+    DataTooLarge(usize),
+    #[error("New fee {1} is not above the paid {0}")]
+    FeeNotHigher(Satoshis, Satoshis),
+    #[error("No change output to take a higher fee from")]
+    NoChange,
+    #[error("Transaction hash must be 32 bytes, was {0}")]
+    TxHashLength(usize),
+    #[error("Invalid transaction hash: {0}")]
+    InvalidTxHash(#[from] hex::FromHexError),
+    #[error("Signatures with sighash flags other than ALL are only shared for co-signing")]
+    CustomSigHash,
 }
 
 impl Output {
-    pub fn new(amount: u64, address: &str) -> Result<Self> {
-        let decoded_address = bs58::decode(address).into_vec()?;
-        if decoded_address.len() != 25 || decoded_address[0] != 0 {
-            return Err(SendingError::InvalidAddress(address.to_owned()).into());
-        }
-
-        let address: [u8; 20] = decoded_address[1..21]
-            .try_into()
-            .expect("Manual bounds set");
-        let checksum = double_sha256(&decoded_address[..21]);
-        if checksum[0..4] != decoded_address[21..] {
-            return Err(SendingError::ChecksumError.into());
-        }
+    /// Pays `amount` to `address`, which has to be on `network`.
+    pub fn new(amount: Satoshis, address: &str, network: Network) -> Result<Self, AddressError> {
+        Ok(Self::new_from_decoded(
+            amount,
+            validate_address(address, network)?,
+        ))
+    }
 
+    pub fn new_from_decoded(amount: Satoshis, address: [u8; 20]) -> Self {
         let mut script = vec![0x76, 0xA9, 0x14];
         script.extend(address);
         script.extend([0x88, 0xAC]);
 
-        Ok(Self { amount, script })
+        Self { amount, script }
     }
 
-    pub fn new_from_decoded(amount: u64, address: [u8; 20]) -> Self {
-        let mut script = vec![0x76, 0xA9, 0x14];
-        script.extend(address);
-        script.extend([0x88, 0xAC]);
-
+    pub fn from_script(amount: Satoshis, script: Vec<u8>) -> Self {
         Self { amount, script }
     }
 
-    fn address(&self) -> Result<[u8; 20]> {
-        if self.script.len() != 25
-            || self.script[0] != 0x76
-            || self.script[1] != 0xA9
-            || self.script[2] != 0x14
-            || self.script[23] != 0x88
-            || self.script[24] != 0xAC
-        {
-            return Err(SignatureError::InvalidScript.into());
+    /// An unspendable `OP_FALSE OP_RETURN` output carrying `data`, always
+    /// without value as anything it held would be burned.
+    pub fn new_op_return(data: &[u8]) -> Result<Self, SendingError> {
+        if data.len() > MAX_DATA_SIZE {
+            return Err(SendingError::DataTooLarge(data.len()));
+        }
+        let mut script = vec![script::OP_0, script::OP_RETURN];
+        script::push_data(&mut script, data);
+        Ok(Self::from_script(Satoshis(0), script))
+    }
+
+    pub fn amount(&self) -> Satoshis {
+        self.amount
+    }
+
+    pub fn script(&self) -> &[u8] {
+        &self.script
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        if let Some(address) = script::parse_p2pkh(&self.script) {
+            return ScriptType::P2pkh(address);
+        }
+        if script::is_op_return(&self.script) {
+            return ScriptType::OpReturn;
+        }
+        match script::parse_cltv_p2pkh(&self.script) {
+            Some((locktime, address)) => ScriptType::CltvP2pkh { locktime, address },
+            None => ScriptType::Other,
+        }
+    }
+
+    /// The public key hash able to spend this output.
+    pub fn address(&self) -> Result<[u8; 20], SignatureError> {
+        match self.script_type() {
+            ScriptType::P2pkh(address) | ScriptType::CltvP2pkh { address, .. } => Ok(address),
+            ScriptType::OpReturn | ScriptType::Other => Err(SignatureError::InvalidScript),
         }
-        let address = self.script[3..23].try_into()?;
-        Ok(address)
     }
 }
 
+/// Outputs and fee of a transaction about to be signed.
+#[derive(Debug, PartialEq)]
+pub struct SigningSummary {
+    /// Address, or the kind of script for other outputs, and amount paid.
+    pub outputs: Vec<(String, Satoshis)>,
+    /// `None` when the amount of some spent output isn't known.
+    pub fee: Option<Satoshis>,
+}
+
+/// Output scripts this wallet recognizes, it can sign for the P2PKH ones.
+#[derive(Debug, PartialEq)]
+pub enum ScriptType {
+    P2pkh([u8; 20]),
+    /// Spendable only by a transaction with at least this locktime.
+    CltvP2pkh {
+        locktime: u32,
+        address: [u8; 20],
+    },
+    /// Carries data, unspendable.
+    OpReturn,
+    Other,
+}
+
 impl Debug for Output {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let script = hex::encode(&self.script);
 
-        write!(f, "Output {{ amount: {}, script: {script} }}", self.amount)
+        write!(
+            f,
+            "Output {{ amount: {}, script: {script} }}",
+            self.amount.0
+        )
     }
 }
 
@@ -205,6 +442,7 @@ impl From<&Output> for Vec<u8> {
     fn from(value: &Output) -> Self {
         value
             .amount
+            .0
             .to_le_bytes()
             .into_iter()
             .chain(encode_compact_size(value.script.len() as u64))
@@ -213,7 +451,17 @@ impl From<&Output> for Vec<u8> {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Serialized size of a transaction's parts in bytes.
+#[derive(Debug, PartialEq)]
+pub struct SizeBreakdown {
+    /// Version, locktime and the input and output counts.
+    pub base: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+    pub total: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transaction {
     version: u32,
     inputs: Vec<Input>,
@@ -230,11 +478,85 @@ impl Transaction {
         self.outputs.push(output);
     }
 
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn from_hex(raw: &str) -> Result<Self, DeserializeError> {
+        hex::decode(raw.trim())?.try_into()
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(Vec::from(self))
+    }
+
+    pub fn outpoints(&self) -> Vec<(Vec<u8>, u32)> {
+        self.inputs
+            .iter()
+            .map(|i| (i.tx_hash.clone(), i.index))
+            .collect()
+    }
+
+    pub fn set_script_sig(
+        &mut self,
+        index: usize,
+        script_sig: Vec<u8>,
+    ) -> Result<(), SignatureError> {
+        let input_count = self.inputs.len();
+        let input = self
+            .inputs
+            .get_mut(index)
+            .ok_or(SignatureError::InputOutOfBounds(index, input_count))?;
+        input.script_sig = script_sig;
+        Ok(())
+    }
+
+    /// The SIGHASH_ALL|FORKID hash signed for the input at `index`.
+    pub fn signature_hash(
+        &self,
+        index: usize,
+        previous_output: &Output,
+    ) -> Result<[u8; 32], SignatureError> {
+        self.sighash(index, previous_output, SigHash::default().value)
+    }
+
+    /// The transaction id, as displayed by explorers (byte-reversed hash).
+    pub fn txid(&self) -> String {
+        let mut hash = double_sha256(&Vec::from(self));
+        hash.reverse();
+        hex::encode(hash)
+    }
+
     pub fn sign_inputs(
         &mut self,
         previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
         address_keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
-    ) -> Result<()> {
+    ) -> Result<(), SignatureError> {
+        self.sign_inputs_with(previous_outputs, address_keys, SigHashType::default())
+    }
+
+    pub fn sign_inputs_with(
+        &mut self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        address_keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+        sighash_type: SigHashType,
+    ) -> Result<(), SignatureError> {
+        // Without a matching output the signature would commit to nothing
+        if sighash_type.base == SigHashBase::Single && self.outputs.len() < self.inputs.len() {
+            return Err(SignatureError::SingleWithoutOutput(
+                self.outputs.len(),
+                self.inputs.len(),
+            ));
+        }
+
+        // Signatures commit to the locktime and sequences, so they are set first
+        self.apply_locktimes(previous_outputs)?;
+
+        let sig_hash = sighash_type.sig_hash();
         for i in 0..self.inputs.len() {
             let input = &self.inputs[i];
             let prev_out = previous_outputs
@@ -243,76 +565,415 @@ impl Transaction {
                     hex::encode(&input.tx_hash),
                     input.index,
                 ))?;
-
-            let hash = self.hash_fork(i, &prev_out.script, &SigHash::default(), prev_out.amount)?;
-
-            let (sk, pk) = address_keys
+            let keys = address_keys
                 .get(&prev_out.address()?)
                 .ok_or(SignatureError::MissingKey)?;
 
-            let signature = sk.sign_ecdsa(Message::from_slice(&hash)?);
-            let der = signature.serialize_der().to_vec();
-            let mut sig_script = vec![];
-            sig_script.extend(encode_compact_size(der.len() as u64 + 1));
-            sig_script.extend(&der);
-            sig_script.push(0x41);
-            sig_script.push(0x21);
-            sig_script.extend(&pk.serialize());
-
-            self.inputs[i].script_sig = sig_script;
+            self.sign_input(i, prev_out, keys, &sig_hash)?;
         }
         Ok(())
     }
 
-    pub fn suggested_fee(&self) -> u64 {
-        let sig_len = self.inputs.len() * 107;
-
-        Vec::from(self).len() as u64 + 34 + sig_len as u64
+    /// Signs the inputs spending outputs the wallet holds keys for, leaving the
+    /// rest untouched apart from the locktime time-locked inputs need. Returns
+    /// the inputs that were not signed.
+    pub fn sign_owned_inputs(
+        &mut self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        address_keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    ) -> Result<Vec<(String, u32)>, SignatureError> {
+        self.apply_locktimes(previous_outputs)?;
+        let mut unsigned = vec![];
+        for i in 0..self.inputs.len() {
+            let input = &self.inputs[i];
+            let owned = previous_outputs
+                .get(&(input.tx_hash.clone(), input.index))
+                .and_then(|prev_out| {
+                    let keys = address_keys.get(&prev_out.address().ok()?)?;
+                    Some((prev_out, keys))
+                });
+
+            match owned {
+                Some((prev_out, keys)) => {
+                    self.sign_input(i, prev_out, keys, &SigHash::default())?
+                }
+                None => unsigned.push((hex::encode(&input.tx_hash), input.index)),
+            }
+        }
+        Ok(unsigned)
     }
 
-    pub fn verify(&self, previous_outputs: &HashMap<(Vec<u8>, u32), Output>) -> Result<()> {
+    /// Raises the locktime to that of every time-locked input being spent and
+    /// makes their sequences non-final so that the locktime is enforced.
+    fn apply_locktimes(
+        &mut self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    ) -> Result<(), SignatureError> {
         for i in 0..self.inputs.len() {
             let input = &self.inputs[i];
-            let signature_length = input.script_sig[0] as usize;
-            let signature = &input.script_sig[1..signature_length];
-            let signature = Signature::from_der(signature)?;
-            let pub_key = &input.script_sig[signature_length + 2..];
-            let pub_key = PublicKey::from_slice(pub_key)?;
-
-            let sig_hash = SigHash {
-                value: input.script_sig[signature_length] as u32,
+            let Some(ScriptType::CltvP2pkh { locktime, .. }) = previous_outputs
+                .get(&(input.tx_hash.clone(), input.index))
+                .map(Output::script_type)
+            else {
+                continue;
             };
+            if self.locktime != 0 && !same_locktime_kind(self.locktime, locktime) {
+                return Err(SignatureError::MixedLocktimes);
+            }
+            self.locktime = self.locktime.max(locktime);
+            self.inputs[i].sequence = SEQUENCE_FINAL - 1;
+        }
+        Ok(())
+    }
+
+    fn sign_input(
+        &mut self,
+        index: usize,
+        prev_out: &Output,
+        (sk, pk): &(SecretKey, PublicKey),
+        sig_hash: &SigHash,
+    ) -> Result<(), SignatureError> {
+        let hash = self.hash_fork(index, &prev_out.script, sig_hash, prev_out.amount.0)?;
+
+        // Outputs paying to the uncompressed key's hash must be unlocked with that key
+        let public_key = if ripemd160(&sha256(&pk.serialize())) == prev_out.address()? {
+            pk.serialize().to_vec()
+        } else {
+            pk.serialize_uncompressed().to_vec()
+        };
+
+        // RFC6979 nonces, signing again gives the same signature and txid
+        let signature = sk.sign_ecdsa(Message::from_slice(&hash).expect("Hashes are 32 bytes"));
+        let der = signature.serialize_der().to_vec();
+        let mut sig_script = vec![];
+        sig_script.extend(encode_compact_size(der.len() as u64 + 1));
+        sig_script.extend(&der);
+        sig_script.push(sig_hash.value as u8);
+        sig_script.push(public_key.len() as u8);
+        sig_script.extend(&public_key);
+
+        self.inputs[index].script_sig = sig_script;
+        Ok(())
+    }
+
+    /// Checks the transaction would be relayed, returning the first violation.
+    pub fn check_standardness(&self) -> Result<(), StandardnessError> {
+        self.check_standardness_with_limit(MAX_STANDARD_TX_SIZE)
+    }
+
+    pub fn check_standardness_with_limit(&self, max_size: u64) -> Result<(), StandardnessError> {
+        if self.inputs.is_empty() {
+            return Err(StandardnessError::NoInputs);
+        }
+        let size = Vec::from(self).len() as u64;
+        if size > max_size {
+            return Err(StandardnessError::TooLarge(size, max_size));
+        }
+        for (index, output) in self.outputs.iter().enumerate() {
+            // Data outputs are exempt from the dust limit but burn any value
+            if output.script_type() == ScriptType::OpReturn {
+                if output.amount > Satoshis(0) {
+                    return Err(StandardnessError::ValuedDataOutput(index, output.amount));
+                }
+            } else if output.amount < DUST_LIMIT {
+                return Err(StandardnessError::DustOutput(index, output.amount));
+            }
+        }
+        Ok(())
+    }
+
+    /// Flags a fee out of proportion with the `sent` value and outputs that are
+    /// barely above dust.
+    pub fn send_warnings(&self, sent: Satoshis, fee: Satoshis) -> Vec<SendWarning> {
+        let mut warnings = vec![];
+        if fee.0 * 100 > sent.0 * HIGH_FEE_PERCENT {
+            warnings.push(SendWarning::HighFee(fee, sent));
+        }
+        let near_dust = Satoshis(DUST_LIMIT.0 * NEAR_DUST_MULTIPLE);
+        for (index, output) in self.outputs.iter().enumerate() {
+            if output.amount < near_dust && output.script_type() != ScriptType::OpReturn {
+                warnings.push(SendWarning::NearDust(index, output.amount));
+            }
+        }
+        warnings
+    }
+
+    pub fn size_breakdown(&self) -> SizeBreakdown {
+        let inputs: usize = self.inputs.iter().map(|i| Vec::from(i).len()).sum();
+        let outputs: usize = self.outputs.iter().map(|o| Vec::from(o).len()).sum();
+        let base = 8
+            + encode_compact_size(self.inputs.len() as u64).len()
+            + encode_compact_size(self.outputs.len() as u64).len();
+        SizeBreakdown {
+            base: base as u64,
+            inputs: inputs as u64,
+            outputs: outputs as u64,
+            total: (base + inputs + outputs) as u64,
+        }
+    }
+
+    /// Estimated size once signed and with a change output added.
+    pub fn estimated_size(&self) -> u64 {
+        self.estimated_size_without_change() + CHANGE_OUTPUT_SIZE
+    }
+
+    /// Estimated size once signed, with only the outputs added so far.
+    pub fn estimated_size_without_change(&self) -> u64 {
+        let sig_len = self.inputs.len() * 107;
+
+        Vec::from(self).len() as u64 + sig_len as u64
+    }
+
+    pub fn suggested_fee(&self, fee_rate: u64) -> Satoshis {
+        Satoshis(self.estimated_size() * fee_rate)
+    }
+
+    /// The fee when no change output is added, as when the change would be
+    /// dust.
+    pub fn suggested_fee_without_change(&self, fee_rate: u64) -> Satoshis {
+        Satoshis(self.estimated_size_without_change() * fee_rate)
+    }
+
+    /// What signing this transaction agrees to, for review before signing one
+    /// built elsewhere.
+    pub fn signing_summary(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        network: Network,
+    ) -> SigningSummary {
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| {
+                let payee = match output.address() {
+                    Ok(address) => util::to_address(address, network),
+                    Err(_) => script::template_name(&output.script)
+                        .unwrap_or("Non-standard script")
+                        .to_owned(),
+                };
+                (payee, output.amount)
+            })
+            .collect();
+        SigningSummary {
+            outputs,
+            fee: self.actual_fee(previous_outputs).ok(),
+        }
+    }
+
+    /// The fee paid, i.e. the value of the spent outputs minus the created ones.
+    pub fn actual_fee(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    ) -> Result<Satoshis, SignatureError> {
+        let mut input_sum = Satoshis::default();
+        for input in &self.inputs {
             let output = previous_outputs
                 .get(&(input.tx_hash.clone(), input.index))
                 .ok_or(SignatureError::MissingInput(
                     hex::encode(&input.tx_hash),
                     input.index,
                 ))?;
-            let script = &output.script;
-            let message = if sig_hash.has_fork_id() {
-                self.hash_fork(i, script, &sig_hash, output.amount)?
-            } else {
-                self.hash_original(i, script, &sig_hash)?
-            };
-            let message = Message::from_slice(&message)?;
+            input_sum += output.amount;
+        }
+        let output_sum: Satoshis = self.outputs.iter().map(|o| o.amount).sum();
+
+        Ok(input_sum.saturating_sub(output_sum))
+    }
+
+    /// A copy spending the same coins at `fee_rate`, the difference taken from
+    /// the change, i.e. the last output whose address `is_change` accepts.
+    ///
+    /// BSV nodes don't replace mempool transactions, so this only helps while
+    /// the original hasn't propagated. Whichever reaches a miner first wins and
+    /// a payee may see the payment disappear and come back under a new txid.
+    pub fn bump_fee(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        address_keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+        is_change: impl Fn(&[u8; 20]) -> bool,
+        fee_rate: u64,
+    ) -> Result<Transaction, Error> {
+        let mut bumped = self.clone();
+        for input in &mut bumped.inputs {
+            input.script_sig.clear();
+        }
+        let paid = self.actual_fee(previous_outputs)?;
+        let fee = bumped.suggested_fee_without_change(fee_rate);
+        if fee <= paid {
+            return Err(SendingError::FeeNotHigher(paid, fee).into());
+        }
+
+        let change = bumped
+            .outputs
+            .iter_mut()
+            .rev()
+            .find(|o| o.address().is_ok_and(|a| is_change(&a)))
+            .ok_or(SendingError::NoChange)?;
+        let increase = fee - paid;
+        if change.amount < increase + DUST_LIMIT {
+            return Err(
+                SendingError::InsufficientFee(increase + DUST_LIMIT - change.amount).into(),
+            );
+        }
+        change.amount -= increase;
+
+        bumped.sign_inputs(previous_outputs, address_keys)?;
+        Ok(bumped)
+    }
+
+    /// Fails with the error of the first input whose signature doesn't verify.
+    pub fn verify(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    ) -> Result<(), SignatureError> {
+        self.verify_with_prevouts(previous_outputs)
+            .inputs
+            .into_iter()
+            .collect()
+    }
+
+    /// Checks the signature of every input, including those of transactions
+    /// this wallet had no part in.
+    pub fn verify_with_prevouts(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    ) -> VerificationReport {
+        self.verify_with_policy(previous_outputs, true)
+    }
 
-            signature.verify(&message, &pub_key)?;
-            println!("Input valid");
+    /// Like [`Self::verify_with_prevouts`], but only rejecting high S
+    /// signatures, which relays refuse as malleable, when `require_low_s`.
+    pub fn verify_with_policy(
+        &self,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        require_low_s: bool,
+    ) -> VerificationReport {
+        VerificationReport {
+            inputs: (0..self.inputs.len())
+                .map(|i| self.verify_input(i, previous_outputs, require_low_s))
+                .collect(),
         }
+    }
 
+    fn verify_input(
+        &self,
+        index: usize,
+        previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+        require_low_s: bool,
+    ) -> Result<(), SignatureError> {
+        let input = &self.inputs[index];
+        let (signature, sig_hash, pub_key) = parse_script_sig(index, &input.script_sig)?;
+        let output = previous_outputs
+            .get(&(input.tx_hash.clone(), input.index))
+            .ok_or(SignatureError::MissingInput(
+                hex::encode(&input.tx_hash),
+                input.index,
+            ))?;
+        if let ScriptType::CltvP2pkh { locktime, .. } = output.script_type() {
+            if input.sequence == SEQUENCE_FINAL
+                || !same_locktime_kind(self.locktime, locktime)
+                || self.locktime < locktime
+            {
+                return Err(SignatureError::Locked(index, locktime));
+            }
+        }
+        let message = self.sighash(index, output, sig_hash.value)?;
+        let message = Message::from_slice(&message).expect("Hashes are 32 bytes");
+
+        // Checked in low S form so that a high S signature is reported as such
+        // rather than as not matching
+        let mut normalized = signature;
+        normalized.normalize_s();
+        normalized
+            .verify(&message, &pub_key)
+            .map_err(|_| SignatureError::VerificationFailed(index))?;
+        if require_low_s && normalized != signature {
+            return Err(SignatureError::HighS(index));
+        }
         Ok(())
     }
 
+    /// The digest signed for input `index` with the given sighash type, using
+    /// the fork id algorithm when the type has the fork id flag set.
+    pub fn sighash(
+        &self,
+        index: usize,
+        previous_output: &Output,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], SignatureError> {
+        if index >= self.inputs.len() {
+            return Err(SignatureError::InputOutOfBounds(index, self.inputs.len()));
+        }
+        let sig_hash = SigHash {
+            value: sighash_type,
+        };
+        if sig_hash.has_fork_id() {
+            self.hash_fork(
+                index,
+                &previous_output.script,
+                &sig_hash,
+                previous_output.amount.0,
+            )
+        } else {
+            self.hash_original(index, &previous_output.script, &sig_hash)
+        }
+    }
+
+    /// The serialized data [`Self::sighash`] hashes, in hex, for comparing
+    /// against other implementations when a signature doesn't verify.
+    pub fn sighash_preimage(
+        &self,
+        index: usize,
+        previous_output: &Output,
+        sighash_type: u32,
+    ) -> Result<String, SignatureError> {
+        if index >= self.inputs.len() {
+            return Err(SignatureError::InputOutOfBounds(index, self.inputs.len()));
+        }
+        let sig_hash = SigHash {
+            value: sighash_type,
+        };
+        let preimage = if sig_hash.has_fork_id() {
+            self.fork_preimage(
+                index,
+                &previous_output.script,
+                &sig_hash,
+                previous_output.amount.0,
+            )?
+        } else {
+            self.original_preimage(index, &previous_output.script, &sig_hash)?
+        };
+        Ok(hex::encode(preimage))
+    }
+
     fn hash_fork(
         &self,
         index: usize,
         script: &[u8],
         sig_hash: &SigHash,
         amount: u64,
-    ) -> Result<[u8; 32]> {
+    ) -> Result<[u8; 32], SignatureError> {
         if !sig_hash.has_fork_id() {
             return self.hash_original(index, script, sig_hash);
         }
+        Ok(double_sha256(
+            &self.fork_preimage(index, script, sig_hash, amount)?,
+        ))
+    }
+
+    /// The BIP143 style serialization signed with the fork id flag.
+    fn fork_preimage(
+        &self,
+        index: usize,
+        script: &[u8],
+        sig_hash: &SigHash,
+        amount: u64,
+    ) -> Result<Vec<u8>, SignatureError> {
+        // The amount is committed to by the signature, a wrong value produces a
+        // signature that nodes reject
+        if amount > MAX_MONEY.0 {
+            return Err(SignatureError::InvalidAmount(amount));
+        }
 
         let mut preimage = vec![];
         preimage.extend(self.version.to_le_bytes());
@@ -367,12 +1028,29 @@ impl Transaction {
         preimage.extend(self.locktime.to_le_bytes());
         preimage.extend(sig_hash.value.to_le_bytes());
 
-        Ok(double_sha256(&preimage))
+        Ok(preimage)
+    }
+
+    fn hash_original(
+        &self,
+        index: usize,
+        script: &[u8],
+        sig_hash: &SigHash,
+    ) -> Result<[u8; 32], SignatureError> {
+        Ok(double_sha256(
+            &self.original_preimage(index, script, sig_hash)?,
+        ))
     }
 
-    fn hash_original(&self, index: usize, script: &[u8], sig_hash: &SigHash) -> Result<[u8; 32]> {
+    /// The pre fork serialization, a modified copy of the transaction.
+    fn original_preimage(
+        &self,
+        index: usize,
+        script: &[u8],
+        sig_hash: &SigHash,
+    ) -> Result<Vec<u8>, SignatureError> {
         if self.has_invalid_flag(index, sig_hash) {
-            return Err(SignatureError::InputOutOfBounds(index, self.inputs.len()).into());
+            return Err(SignatureError::InputOutOfBounds(index, self.inputs.len()));
         }
 
         let mut current_signing = self.clone();
@@ -405,7 +1083,7 @@ impl Transaction {
             for i in 0..current_signing.outputs.len() {
                 if i != index {
                     current_signing.outputs[i] = Output {
-                        amount: u64::MAX,
+                        amount: Satoshis(u64::MAX),
                         script: vec![],
                     }
                 }
@@ -414,7 +1092,7 @@ impl Transaction {
 
         let mut serialized = Vec::from(&current_signing);
         serialized.extend(sig_hash.value.to_le_bytes());
-        Ok(double_sha256(&serialized))
+        Ok(serialized)
     }
 
     fn has_invalid_flag(&self, index: usize, sig_hash: &SigHash) -> bool {
@@ -422,8 +1100,43 @@ impl Transaction {
     }
 }
 
-impl Default for Transaction {
-    fn default() -> Self {
+/// Whether both locktimes are block heights or both timestamps.
+fn same_locktime_kind(a: u32, b: u32) -> bool {
+    (a < script::LOCKTIME_THRESHOLD) == (b < script::LOCKTIME_THRESHOLD)
+}
+
+/// Splits a P2PKH unlocking script into its signature, sighash type and key.
+fn parse_script_sig(
+    index: usize,
+    script_sig: &[u8],
+) -> Result<(Signature, SigHash, PublicKey), SignatureError> {
+    let signature_length = *script_sig
+        .first()
+        .ok_or(SignatureError::InvalidSignature(index))? as usize;
+    if signature_length == 0 || script_sig.len() < signature_length + 2 {
+        return Err(SignatureError::InvalidSignature(index));
+    }
+
+    let der = &script_sig[1..signature_length];
+    let signature = match Signature::from_der(der) {
+        Ok(signature) => signature,
+        // Nodes reject any encoding but strict DER
+        Err(_) if Signature::from_der_lax(der).is_ok() => {
+            return Err(SignatureError::NonCanonicalDer(index))
+        }
+        Err(_) => return Err(SignatureError::InvalidSignature(index)),
+    };
+    let sig_hash = SigHash {
+        value: script_sig[signature_length] as u32,
+    };
+    let pub_key = PublicKey::from_slice(&script_sig[signature_length + 2..])
+        .map_err(|_| SignatureError::InvalidPublicKey(index))?;
+
+    Ok((signature, sig_hash, pub_key))
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
         Self {
             version: 1,
             inputs: vec![],
@@ -454,57 +1167,508 @@ impl From<&Transaction> for Vec<u8> {
     }
 }
 
+/// Where the value of an unsigned transaction's inputs goes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeBreakdown {
+    pub inputs: Satoshis,
+    pub sent: Satoshis,
+    pub change: Satoshis,
+    /// Includes a change remainder too small to be worth an output.
+    pub fee: Satoshis,
+}
+
+/// Assembles an unsigned transaction paying the added outputs, funding it
+/// either from automatically selected coins or from an explicit input set, and
+/// returning the remainder to the change address.
+pub struct TransactionBuilder {
+    outputs: Vec<Output>,
+    change_address: String,
+    network: Network,
+    inputs: Option<Vec<RichOutput>>,
+    fee_rate: u64,
+    shuffle_seed: Option<u64>,
+}
+
+impl TransactionBuilder {
+    /// Builds sends returning change to `change_address` on `network`.
+    pub fn new(change_address: &str, network: Network) -> Self {
+        Self {
+            outputs: vec![],
+            change_address: change_address.to_owned(),
+            network,
+            inputs: None,
+            fee_rate: DEFAULT_FEE_RATE,
+            shuffle_seed: None,
+        }
+    }
+
+    /// Orders the outputs, change included, by a permutation derived from
+    /// `seed` so that the change isn't recognizable as the last output. Only
+    /// suitable when signatures don't commit to output positions, i.e. not
+    /// with SIGHASH_SINGLE.
+    pub fn shuffle_outputs(&mut self, seed: u64) {
+        self.shuffle_seed = Some(seed);
+    }
+
+    /// Sets the fee rate in satoshis per byte.
+    pub fn set_fee_rate(&mut self, fee_rate: u64) {
+        self.fee_rate = fee_rate;
+    }
+
+    pub fn add_output(&mut self, output: Output) {
+        self.outputs.push(output);
+    }
+
+    /// Funds the transaction from exactly these outputs instead of selecting
+    /// coins automatically.
+    pub fn use_inputs(&mut self, inputs: Vec<RichOutput>) {
+        self.inputs = Some(inputs);
+    }
+
+    /// Selects inputs and adds change, leaving signing to [`sign`] so the
+    /// result can be inspected before committing to it.
+    pub fn build_unsigned(
+        &self,
+        utxos: &[RichOutput],
+    ) -> Result<(Transaction, FeeBreakdown), Error> {
+        let amount: Satoshis = self.outputs.iter().map(|o| o.amount).sum();
+
+        let mut transaction = Transaction::default();
+        for output in &self.outputs {
+            transaction.add_output(output.clone());
+        }
+
+        let input_sum = match &self.inputs {
+            Some(inputs) => {
+                let mut input_sum = Satoshis::default();
+                for input in inputs {
+                    input_sum += input.amount;
+                    transaction.add_input(Input::new(input.tx_hash.clone(), input.tx_pos)?);
+                }
+                let required = amount + transaction.suggested_fee_without_change(self.fee_rate);
+                if input_sum < required {
+                    return Err(SendingError::InsufficientSelection(required - input_sum).into());
+                }
+                input_sum
+            }
+            None => select_coins(&mut transaction, utxos, amount, self.fee_rate)?,
+        };
+
+        // The inputs cover at least the fee without change. Only when the fee
+        // with a change output leaves more than dust is one added, otherwise
+        // the remainder goes to the fee
+        let change = (input_sum - amount).saturating_sub(transaction.suggested_fee(self.fee_rate));
+        let change = if change >= DUST_LIMIT {
+            transaction.add_output(Output::new(change, &self.change_address, self.network)?);
+            change
+        } else {
+            Satoshis::default()
+        };
+        let breakdown = FeeBreakdown {
+            inputs: input_sum,
+            sent: amount,
+            change,
+            fee: input_sum - amount - change,
+        };
+        if let Some(seed) = self.shuffle_seed {
+            shuffle(&mut transaction.outputs, seed);
+        }
+        Ok((transaction, breakdown))
+    }
+}
+
+/// Fisher-Yates shuffle driven by SplitMix64, which mixes any seed well.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        items.swap(i, (next() % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Signs every input of `transaction`, which must spend some of `utxos`.
+pub fn sign(
+    transaction: &mut Transaction,
+    utxos: &[RichOutput],
+    keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    sighash_type: SigHashType,
+) -> Result<(), Error> {
+    Ok(transaction.sign_inputs_with(&previous_outputs(utxos)?, keys, sighash_type)?)
+}
+
+/// How a send picks and orders its coins and what its signatures cover.
+#[derive(Clone, Default)]
+pub struct SendOptions {
+    /// Coins to spend, all of them, instead of selecting automatically.
+    pub selected: Vec<RichOutput>,
+    /// Shuffles the outputs, ignored with SIGHASH_SINGLE.
+    pub shuffle_seed: Option<u64>,
+    pub sighash_type: SigHashType,
+    /// Stored on chain in an extra `OP_RETURN` output.
+    pub data: Option<Vec<u8>>,
+}
+
+/// The output paying `amount` to `address`, if both are valid for a send on
+/// `network`.
+pub fn check_recipient(address: &str, amount: Satoshis, network: Network) -> Result<Output, Error> {
+    check_send_amount(amount, DUST_LIMIT)?;
+    Ok(Output::new(amount, address, network)?)
+}
+
+/// Validates the recipients and builds the unsigned transaction paying them
+/// from `utxos`, returning the rest to `change_address`. Every address has
+/// to be on `network`.
+pub fn prepare(
+    recipients: &[(String, Satoshis)],
+    utxos: &[RichOutput],
+    change_address: &str,
+    network: Network,
+    fee_rate: u64,
+    options: &SendOptions,
+) -> Result<(Transaction, FeeBreakdown), Error> {
+    let mut builder = TransactionBuilder::new(change_address, network);
+    builder.set_fee_rate(fee_rate);
+    for (address, amount) in recipients {
+        builder.add_output(check_recipient(address, *amount, network)?);
+    }
+    if let Some(data) = &options.data {
+        builder.add_output(Output::new_op_return(data)?);
+    }
+    if !options.selected.is_empty() {
+        builder.use_inputs(options.selected.clone());
+    }
+    // SIGHASH_SINGLE pairs each input with the output at its index
+    if options.sighash_type.base != SigHashBase::Single {
+        if let Some(seed) = options.shuffle_seed {
+            builder.shuffle_outputs(seed);
+        }
+    }
+    builder.build_unsigned(utxos)
+}
+
+/// The whole send: [`prepare`] followed by signing every input with `keys`.
+/// The same arguments give the transaction [`prepare`] showed.
+pub fn prepare_and_sign(
+    recipients: &[(String, Satoshis)],
+    utxos: &[RichOutput],
+    change_address: &str,
+    network: Network,
+    keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    fee_rate: u64,
+    options: &SendOptions,
+) -> Result<Transaction, Error> {
+    // Anyone seeing a broadcast SIGHASH_NONE input could redirect its coins
+    if options.sighash_type != SigHashType::default() {
+        return Err(SendingError::CustomSigHash.into());
+    }
+    let (mut transaction, _) = prepare(
+        recipients,
+        utxos,
+        change_address,
+        network,
+        fee_rate,
+        options,
+    )?;
+    sign(&mut transaction, utxos, keys, options.sighash_type)?;
+    Ok(transaction)
+}
+
+/// What a broadcast transaction sent, as announced to the pages of the
+/// configured origin in the `beesv-sent` event's `detail`:
+///
+/// ```json
+/// {
+///   "txid": "…",
+///   "amount": 10000,
+///   "fee": 226,
+///   "recipients": [{ "address": "1…", "amount": 10000 }]
+/// }
+/// ```
+///
+/// Amounts are in satoshis. Outputs paying the wallet itself, like change,
+/// aren't recipients, so a consolidation has none.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SendSummary {
+    pub txid: String,
+    pub amount: Satoshis,
+    pub fee: Satoshis,
+    pub recipients: Vec<SummaryRecipient>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SummaryRecipient {
+    pub address: String,
+    pub amount: Satoshis,
+}
+
+impl SendSummary {
+    /// Summarizes `transaction` spending some of `utxos`, with `is_own`
+    /// telling which outputs pay the wallet. Addresses are listed as on
+    /// `network`.
+    pub fn new(
+        transaction: &Transaction,
+        utxos: &[RichOutput],
+        is_own: impl Fn(&str) -> bool,
+        network: Network,
+    ) -> Result<Self> {
+        let recipients: Vec<_> = transaction
+            .outputs()
+            .iter()
+            .filter_map(|output| {
+                let address = util::to_address(output.address().ok()?, network);
+                (!is_own(&address)).then_some(SummaryRecipient {
+                    address,
+                    amount: output.amount,
+                })
+            })
+            .collect();
+        Ok(Self {
+            txid: transaction.txid(),
+            amount: recipients.iter().map(|r| r.amount).sum(),
+            fee: transaction.actual_fee(&previous_outputs(utxos)?)?,
+            recipients,
+        })
+    }
+}
+
+/// `fee` as a percentage of the `sent` value, `None` when nothing is sent.
+pub fn fee_percent(fee: Satoshis, sent: Satoshis) -> Option<f64> {
+    (sent.0 > 0).then(|| fee.0 as f64 * 100.0 / sent.0 as f64)
+}
+
+/// Warns when a send built from `utxos` spends all of them and leaves no
+/// change without `send_max` asking for it, usually from an amount picked
+/// slightly too large.
+pub fn sweep_warning(
+    transaction: &Transaction,
+    breakdown: &FeeBreakdown,
+    utxos: &[RichOutput],
+    send_max: bool,
+) -> Option<SendWarning> {
+    let sweeps = breakdown.change == Satoshis(0) && transaction.input_count() == utxos.len();
+    (sweeps && !send_max).then_some(SendWarning::EmptiesWallet)
+}
+
+/// Checks that `amount` can be sent as an output, given the `dust_limit`.
+pub fn check_send_amount(amount: Satoshis, dust_limit: Satoshis) -> Result<(), SendingError> {
+    if amount < MIN_SEND_AMOUNT {
+        return Err(SendingError::AmountTooSmall);
+    }
+    if amount < dust_limit {
+        return Err(SendingError::BelowDust(amount, dust_limit));
+    }
+    Ok(())
+}
+
+/// Whether `utxos` can pay `amount` to a single address, including the fee.
+pub fn can_fund(utxos: &[RichOutput], amount: Satoshis, fee_rate: u64) -> bool {
+    let mut transaction = Transaction::default();
+    transaction.add_output(Output::new_from_decoded(amount, [0; 20]));
+    select_coins(&mut transaction, utxos, amount, fee_rate).is_ok()
+}
+
+/// The most `utxos` can pay to a single address, spending every coin that
+/// isn't dust. Sending it leaves nothing for change.
+pub fn max_sendable(utxos: &[RichOutput], fee_rate: u64) -> Satoshis {
+    let mut transaction = Transaction::default();
+    transaction.add_output(Output::new_from_decoded(Satoshis::default(), [0; 20]));
+    let mut value = Satoshis::default();
+    for utxo in utxos.iter().filter(|o| o.is_spendable()) {
+        let Ok(input) = Input::new(utxo.tx_hash.clone(), utxo.tx_pos) else {
+            continue;
+        };
+        value += utxo.amount;
+        transaction.add_input(input);
+    }
+    value.saturating_sub(transaction.suggested_fee_without_change(fee_rate))
+}
+
+/// Adds inputs from `utxos` in order until they cover `amount` and the fee of
+/// the resulting transaction with change, returning the sum of the added
+/// inputs. Covering only the fee without change is enough once all coins
+/// are added.
+fn select_coins(
+    transaction: &mut Transaction,
+    utxos: &[RichOutput],
+    amount: Satoshis,
+    fee_rate: u64,
+) -> Result<Satoshis, SendingError> {
+    let mut input_sum = Satoshis::default();
+    // Input count and sum when the fee without change was first covered
+    let mut covered = None;
+    for utxo in utxos {
+        if input_sum >= amount + transaction.suggested_fee(fee_rate) {
+            break;
+        }
+        input_sum += utxo.amount;
+        transaction.add_input(Input::new(utxo.tx_hash.clone(), utxo.tx_pos)?);
+        if covered.is_none()
+            && input_sum >= amount + transaction.suggested_fee_without_change(fee_rate)
+        {
+            covered = Some((transaction.inputs.len(), input_sum));
+        }
+    }
+    // Coins added after that which still didn't pay for change only raised
+    // the fee
+    if input_sum < amount + transaction.suggested_fee(fee_rate) {
+        if let Some((count, sum)) = covered {
+            transaction.inputs.truncate(count);
+            input_sum = sum;
+        }
+    }
+
+    if input_sum < amount {
+        return Err(SendingError::InsufficientBalance(amount - input_sum));
+    }
+    let fee = transaction.suggested_fee_without_change(fee_rate);
+    if input_sum < amount + fee {
+        return Err(SendingError::InsufficientFee(amount + fee));
+    }
+    Ok(input_sum)
+}
+
+/// Builds a transaction merging all `utxos` (or only those below `threshold`)
+/// into a single output paying `address` on `network` at `fee_rate`,
+/// returning it along with its fee.
+pub fn consolidate(
+    utxos: &[RichOutput],
+    threshold: Option<Satoshis>,
+    address: &str,
+    network: Network,
+    fee_rate: u64,
+) -> Result<(Transaction, Satoshis)> {
+    let selected: Vec<_> = utxos
+        .iter()
+        .filter(|o| threshold.is_none_or(|threshold| o.amount < threshold))
+        .collect();
+    if selected.len() < 2 {
+        return Err(SendingError::NothingToConsolidate(selected.len()).into());
+    }
+
+    let mut transaction = Transaction::default();
+    let mut value = Satoshis::default();
+    for utxo in selected {
+        value += utxo.amount;
+        transaction.add_input(Input::new(utxo.tx_hash.clone(), utxo.tx_pos)?);
+    }
+
+    // The suggested fee already accounts for the single output added below
+    let fee = transaction.suggested_fee(fee_rate);
+    if fee >= value {
+        return Err(SendingError::FeeExceedsValue(fee, value).into());
+    }
+    if value - fee < DUST_LIMIT {
+        return Err(SendingError::BelowDust(value - fee, DUST_LIMIT).into());
+    }
+    transaction.add_output(Output::new(value - fee, address, network)?);
+
+    Ok((transaction, fee))
+}
+
+pub struct SignedRaw {
+    pub raw: String,
+    pub unsigned_inputs: Vec<(String, u32)>,
+}
+
+/// Signs a transaction built elsewhere, given as raw hex together with the
+/// outputs it spends.
+pub fn sign_raw_transaction(
+    raw: &str,
+    previous_outputs: &HashMap<(Vec<u8>, u32), Output>,
+    address_keys: &HashMap<[u8; 20], (SecretKey, PublicKey)>,
+) -> Result<SignedRaw> {
+    let mut transaction = Transaction::from_hex(raw)?;
+    let unsigned_inputs = transaction.sign_owned_inputs(previous_outputs, address_keys)?;
+
+    Ok(SignedRaw {
+        raw: transaction.to_hex(),
+        unsigned_inputs,
+    })
+}
+
+pub fn previous_outputs(
+    utxos: &[RichOutput],
+) -> Result<HashMap<(Vec<u8>, u32), Output>, hex::FromHexError> {
+    utxos
+        .iter()
+        .map(|o| {
+            Ok((
+                (hex::decode(&o.tx_hash)?, o.tx_pos),
+                Output::new_from_decoded(o.amount, o.address),
+            ))
+        })
+        .collect()
+}
+
 #[derive(Error, Debug)]
-enum DeserializeError {
+pub enum DeserializeError {
     #[error("Leftover data after parsing: {0:?}")]
     LeftoverData(Vec<u8>),
+    #[error("Transaction has no inputs")]
+    NoInputs,
+    #[error("Transaction has no outputs")]
+    NoOutputs,
+    #[error("Transaction ends unexpectedly")]
+    UnexpectedEnd,
+    #[error("Invalid hex: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
 }
 
 impl TryFrom<Vec<u8>> for Transaction {
-    type Error = anyhow::Error;
+    type Error = DeserializeError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, DeserializeError> {
+        Self::decode(&value)
+    }
+}
 
-    fn try_from(value: Vec<u8>) -> Result<Self> {
-        let version = u32::from_le_bytes(value[..4].try_into()?);
+impl Transaction {
+    fn decode(value: &[u8]) -> Result<Self, DeserializeError> {
+        let mut reader = Reader(value);
+        let version = reader.read_u32()?;
 
-        let mut transaction = value[4..].to_vec();
-        let input_count = read_var_int(&mut transaction)?;
+        let input_count = reader.read_var_int()?;
+        if input_count == 0 {
+            return Err(DeserializeError::NoInputs);
+        }
 
         let mut inputs = vec![];
         for _ in 0..input_count {
-            let tx_hash: Vec<_> = transaction.drain(0..32).rev().collect();
-            let index: Vec<_> = transaction.drain(0..4).collect();
-            let index = u32::from_le_bytes(index[..].try_into()?);
-            let script_len = read_var_int(&mut transaction)? as usize;
-            let script_sig: Vec<_> = transaction.drain(0..script_len).collect();
-
-            let sequence: Vec<_> = transaction.drain(0..4).collect();
-            let sequence = u32::from_le_bytes(sequence[..].try_into()?);
+            let tx_hash = reader.take(32)?.iter().rev().copied().collect();
+            let index = reader.read_u32()?;
+            let script_len = reader.read_var_int()?;
+            let script_sig = reader.take(script_len)?.to_vec();
+            let sequence = reader.read_u32()?;
             inputs.push(Input {
-                //address,
                 tx_hash,
                 index,
                 script_sig,
-                //sig_type,
                 sequence,
             })
         }
 
-        let output_count = read_var_int(&mut transaction)?;
+        let output_count = reader.read_var_int()?;
+        if output_count == 0 {
+            return Err(DeserializeError::NoOutputs);
+        }
         let mut outputs = vec![];
         for _ in 0..output_count {
-            let amount: Vec<_> = transaction.drain(0..8).collect();
-            let amount = u64::from_le_bytes(amount[..].try_into()?);
-
-            let script_len = read_var_int(&mut transaction)? as usize;
-            let script: Vec<_> = transaction.drain(0..script_len).collect();
+            let amount = Satoshis(u64::from_le_bytes(reader.read_array()?));
+            let script_len = reader.read_var_int()?;
+            let script = reader.take(script_len)?.to_vec();
 
             outputs.push(Output { amount, script })
         }
-        let locktime: Vec<_> = transaction.drain(0..4).collect();
-        let locktime = u32::from_le_bytes(locktime[..].try_into()?);
+        let locktime = reader.read_u32()?;
 
-        if !transaction.is_empty() {
-            return Err(DeserializeError::LeftoverData(transaction).into());
+        if !reader.0.is_empty() {
+            return Err(DeserializeError::LeftoverData(reader.0.to_vec()));
         }
 
         Ok(Transaction {
@@ -516,25 +1680,37 @@ impl TryFrom<Vec<u8>> for Transaction {
     }
 }
 
-fn read_var_int(input: &mut Vec<u8>) -> Result<u64> {
-    Ok(match input.remove(0) {
-        0xFD => {
-            let count = u16::from_le_bytes(input[..2].try_into()?);
-            input.drain(0..2);
-            count as u64
-        }
-        0xFE => {
-            let count = u32::from_le_bytes(input[..4].try_into()?);
-            input.drain(0..4);
-            count as u64
-        }
-        0xFF => {
-            let count = u64::from_le_bytes(input[..8].try_into()?);
-            input.drain(0..8);
-            count
-        }
-        value => value as u64,
-    })
+/// Consumes a serialized transaction from the front, failing instead of
+/// panicking when it ends early, e.g. on truncated pasted hex.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: u64) -> Result<&'a [u8], DeserializeError> {
+        let count = usize::try_from(count).map_err(|_| DeserializeError::UnexpectedEnd)?;
+        let (taken, rest) = self
+            .0
+            .split_at_checked(count)
+            .ok_or(DeserializeError::UnexpectedEnd)?;
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DeserializeError> {
+        Ok(self.take(N as u64)?.try_into().expect("Took N bytes"))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DeserializeError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_var_int(&mut self) -> Result<u64, DeserializeError> {
+        Ok(match self.read_array::<1>()?[0] {
+            0xFD => u16::from_le_bytes(self.read_array()?) as u64,
+            0xFE => u32::from_le_bytes(self.read_array()?) as u64,
+            0xFF => u64::from_le_bytes(self.read_array()?),
+            value => value as u64,
+        })
+    }
 }
 
 fn encode_compact_size(input: u64) -> Vec<u8> {
@@ -557,9 +1733,10 @@ fn encode_compact_size(input: u64) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, str::FromStr};
+    use std::{collections::HashSet, fs::File, str::FromStr};
 
     use anyhow::Result;
+    use serde::Deserialize;
 
     use super::*;
 
@@ -569,9 +1746,9 @@ mod tests {
         transaction.add_input(Input::new_decoded(
             hex::decode("3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b")?,
             0,
-        ));
+        )?);
         transaction.add_output(Output {
-            amount: 4999990000,
+            amount: Satoshis(4999990000),
             script: hex::decode("76a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac")?,
         });
 
@@ -579,7 +1756,142 @@ mod tests {
             hex::decode("01000000017b1eabe0209b1fe794124575ef807057c77ada2138ae4fa8d6c4de0398a14f3f0000000000ffffffff01f0ca052a010000001976a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac00000000")?,
             Vec::from(&transaction),
         );
+        assert_eq!(
+            "c80b343d2ce2b5d829c2de9854c7c8d423c0e33bda264c40138d834aab4c0638",
+            transaction.txid()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_zero_inputs() -> Result<()> {
+        let raw_tx = hex::decode("010000000001f0ca052a010000001976a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac00000000")?;
+
+        let error = Transaction::try_from(raw_tx).unwrap_err();
+
+        assert!(matches!(error, DeserializeError::NoInputs));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_zero_outputs() -> Result<()> {
+        let raw_tx = hex::decode("01000000017b1eabe0209b1fe794124575ef807057c77ada2138ae4fa8d6c4de0398a14f3f0000000000ffffffff0000000000")?;
+
+        let error = Transaction::try_from(raw_tx).unwrap_err();
+
+        assert!(matches!(error, DeserializeError::NoOutputs));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_truncated_hex() {
+        let valid = "01000000017b1eabe0209b1fe794124575ef807057c77ada2138ae4fa8d6c4de0398a14f3f0000000000ffffffff01f0ca052a010000001976a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac00000000";
+        assert!(Transaction::from_hex(valid).is_ok());
+
+        for truncated in [
+            "",
+            "01",
+            "0100000001",
+            "01000000fd",
+            &valid[..valid.len() - 2],
+        ] {
+            assert!(
+                matches!(
+                    Transaction::from_hex(truncated),
+                    Err(DeserializeError::UnexpectedEnd)
+                ),
+                "{truncated}"
+            );
+        }
+    }
+
+    #[test]
+    fn standardness_rejects_oversized() -> Result<()> {
+        let mut transaction = Transaction::default();
+        for index in 0..2_500 {
+            transaction.add_input(Input::new_decoded(vec![0x3f; 32], index)?);
+        }
+        transaction.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+
+        assert!(matches!(
+            transaction.check_standardness(),
+            Err(StandardnessError::TooLarge(_, MAX_STANDARD_TX_SIZE))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn standardness_rejects_dust_output() -> Result<()> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(vec![0x3f; 32], 0)?);
+        transaction.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+        assert_eq!(Ok(()), transaction.check_standardness());
+
+        transaction.add_output(Output::new(Satoshis(100), CHANGE, Network::Mainnet)?);
+
+        assert_eq!(
+            Err(StandardnessError::DustOutput(1, Satoshis(100))),
+            transaction.check_standardness()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn standardness_requires_empty_data_outputs() -> Result<()> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(vec![0x3f; 32], 0)?);
+        transaction.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+        let data = Output::new_op_return(b"hello")?;
+        assert_eq!(Satoshis(0), data.amount());
+        assert_eq!(ScriptType::OpReturn, data.script_type());
+        transaction.add_output(data.clone());
+        assert_eq!(Ok(()), transaction.check_standardness());
+
+        transaction.outputs[1] = Output::from_script(Satoshis(1_000), data.script().to_vec());
+
+        assert_eq!(
+            Err(StandardnessError::ValuedDataOutput(1, Satoshis(1_000))),
+            transaction.check_standardness()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn send_warnings_flag_high_fee() -> Result<()> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(vec![0x3f; 32], 0)?);
+        transaction.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+
+        assert!(transaction
+            .send_warnings(Satoshis(10_000), Satoshis(1_000))
+            .is_empty());
+        assert_eq!(
+            vec![SendWarning::HighFee(Satoshis(1_001), Satoshis(10_000))],
+            transaction.send_warnings(Satoshis(10_000), Satoshis(1_001))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fee_percent_of_tiny_send() {
+        assert_eq!(Some(10.0), fee_percent(Satoshis(1_000), Satoshis(10_000)));
+        // A send just above dust pays more in fees than it moves
+        assert_eq!(Some(150.0), fee_percent(Satoshis(1_500), Satoshis(1_000)));
+        assert_eq!(None, fee_percent(Satoshis(100), Satoshis(0)));
+    }
+
+    #[test]
+    fn send_warnings_flag_near_dust_outputs() -> Result<()> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(vec![0x3f; 32], 0)?);
+        transaction.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+        transaction.add_output(Output::new(Satoshis(600), CHANGE, Network::Mainnet)?);
 
+        assert_eq!(
+            vec![SendWarning::NearDust(1, Satoshis(600))],
+            transaction.send_warnings(Satoshis(10_000), Satoshis(200))
+        );
         Ok(())
     }
 
@@ -597,15 +1909,23 @@ mod tests {
         )
     }
 
+    type PreviousOutputs = HashMap<(Vec<u8>, u32), Output>;
+
     #[test]
     fn verify_signature_fork() -> Result<()> {
+        let (transaction, inputs) = fork_vector()?;
+
+        transaction.verify(&inputs)?;
+        Ok(())
+    }
+
+    fn fork_vector() -> Result<(Transaction, PreviousOutputs)> {
         let input = "0100000002b9d7f35e43d40ba1fb7a03ce78c0f882f8fb41bc5d544347565673e62dad6739010000006a4730440220693afd8f6d09b88489c66e2084ce95e5d4be122d4e4056b7e5f17e0072baee4c022063eb4c064e6cec56746ffa29db694bb16d4439beddfff39dfa0b0b86340057dd412103eb73f67c22a83656d96b4a355360699042ac185d474474ec8805f4735178050affffffff73c3335f056dff3f95d2a279893a5904416581f63dc8157fa035085c1c423eba010000006a47304402207b94740f3d4357feab803a40708c7dacbdf9d9e7200364cfd71ab96a56d634cf02202070fc09e1dadd645dd133addbdd27e5c0b814f179bfed08b7a466808b19cb54412103aa0837bbdd4fa56c4c34c0e0407d4a9c58a5de8df57393764207acec0067e95bffffffff02808d5b00000000001976a9141e9c2e4b2427952f5e92b1be245aa71a3f7e133888acac920700000000001976a914bc9bdd6c9945529b57e645b65a5fcea198ecdbf688ac00000000";
 
-        let raw_tx = hex::decode(input)?;
-        let transaction: Transaction = raw_tx.try_into()?;
+        let transaction = Transaction::from_hex(input)?;
 
-        let serialized = hex::encode(Vec::from(&transaction)).to_string();
-        assert_eq!(input, serialized);
+        assert_eq!(input, transaction.to_hex());
+        assert_eq!(transaction, Transaction::from_hex(&transaction.to_hex())?);
 
         let mut inputs = HashMap::new();
         inputs.insert(
@@ -614,7 +1934,7 @@ mod tests {
                 1,
             ),
             Output {
-                amount: 1222064,
+                amount: Satoshis(1222064),
                 script: hex::decode("76a9140b16eb01af7a0f6fa56ee8183ca84a27cf4151e988ac")?,
             },
         );
@@ -624,54 +1944,270 @@ mod tests {
                 1,
             ),
             Output {
-                amount: 5274723,
+                amount: Satoshis(5274723),
                 script: hex::decode("76a9140c6a3b21b00ddc232da8a62bb24aa031e0a93be188ac")?,
             },
         );
 
-        transaction.verify(&inputs)
+        Ok((transaction, inputs))
     }
 
     #[test]
-    fn verify_signature_no_fork() -> Result<()> {
-        let input = "0200000001c44c3bae60810fd288c11ec8682eaf88de396b2d53aae6ee3d5824e2f3dc3e96050000006a473044022005c396c208844da838467f05545862c63391f84dc07e02792d52784ae52cb32f022074ec4622b45fbd1accd5f59767f969aafc339367f18dca9162d2d122f75523b3012102be0aa60c89ce7ebe35418a79284bfb2fef25a3fac9262afb6ff6e9c546e9cd5bfeffffff01435d320000000000160014bf1bafa3caa7fb41eeb66218ce0cdb4f4b3b95e398010c00";
+    fn verify_reports_failing_input() -> Result<()> {
+        let (transaction, inputs) = fork_vector()?;
+        let signature_length = transaction.inputs[1].script_sig[0] as usize;
+
+        let mut corrupt_signature = transaction.clone();
+        corrupt_signature.inputs[1].script_sig[1] = 0x00;
+        let mut corrupt_key = transaction.clone();
+        corrupt_key.inputs[1].script_sig[signature_length + 2] = 0x05;
+        let mut wrong_signature = transaction.clone();
+        wrong_signature.inputs[1].script_sig[10] ^= 0x01;
+
+        assert!(matches!(
+            corrupt_signature.verify(&inputs),
+            Err(SignatureError::InvalidSignature(1))
+        ));
+        assert!(matches!(
+            corrupt_key.verify(&inputs),
+            Err(SignatureError::InvalidPublicKey(1))
+        ));
+        assert!(matches!(
+            wrong_signature.verify(&inputs),
+            Err(SignatureError::VerificationFailed(1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_with_prevouts_reports_each_input() -> Result<()> {
+        let (transaction, inputs) = fork_vector()?;
+        assert!(transaction.verify_with_prevouts(&inputs).is_valid());
+
+        let mut tampered = transaction.clone();
+        tampered.inputs[0].script_sig[10] ^= 0x01;
+        let report = tampered.verify_with_prevouts(&inputs);
+
+        assert!(!report.is_valid());
+        assert!(report.inputs[0].is_err());
+        assert!(report.inputs[1].is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn size_breakdown_sums_to_serialized_length() -> Result<()> {
+        let transaction = Transaction::from_hex("0200000001c44c3bae60810fd288c11ec8682eaf88de396b2d53aae6ee3d5824e2f3dc3e96050000006a473044022005c396c208844da838467f05545862c63391f84dc07e02792d52784ae52cb32f022074ec4622b45fbd1accd5f59767f969aafc339367f18dca9162d2d122f75523b3012102be0aa60c89ce7ebe35418a79284bfb2fef25a3fac9262afb6ff6e9c546e9cd5bfeffffff01435d320000000000160014bf1bafa3caa7fb41eeb66218ce0cdb4f4b3b95e398010c00")?;
 
-        let raw_tx = hex::decode(input)?;
-        let transaction: Transaction = raw_tx.try_into()?;
+        let breakdown = transaction.size_breakdown();
 
-        let serialized = hex::encode(Vec::from(&transaction)).to_string();
-        assert_eq!(input, serialized);
+        assert_eq!(
+            SizeBreakdown {
+                base: 10,
+                inputs: 147,
+                outputs: 31,
+                total: 188,
+            },
+            breakdown
+        );
+        assert_eq!(Vec::from(&transaction).len() as u64, breakdown.total);
+        Ok(())
+    }
 
+    #[test]
+    fn verify_with_prevouts_checks_third_party_transaction() -> Result<()> {
+        // A transaction signed without the fork id, by keys unrelated to ours
+        let transaction = Transaction::from_hex("0200000001c44c3bae60810fd288c11ec8682eaf88de396b2d53aae6ee3d5824e2f3dc3e96050000006a473044022005c396c208844da838467f05545862c63391f84dc07e02792d52784ae52cb32f022074ec4622b45fbd1accd5f59767f969aafc339367f18dca9162d2d122f75523b3012102be0aa60c89ce7ebe35418a79284bfb2fef25a3fac9262afb6ff6e9c546e9cd5bfeffffff01435d320000000000160014bf1bafa3caa7fb41eeb66218ce0cdb4f4b3b95e398010c00")?;
+        let previous_output = Output {
+            amount: Satoshis(3303000),
+            script: hex::decode("76a914152fc05ea22a712eb8227e57dbd8d79451ea0e3e88ac")?,
+        };
         let mut inputs = HashMap::new();
         inputs.insert(
             (
                 hex::decode("963edcf3e224583deee6aa532d6b39de88af2e68c81ec188d20f8160ae3b4cc4")?,
                 5,
             ),
-            Output {
-                amount: 3303000,
-                script: hex::decode("76a914152fc05ea22a712eb8227e57dbd8d79451ea0e3e88ac")?,
-            },
+            previous_output.clone(),
         );
 
-        transaction.verify(&inputs)
+        let report = transaction.verify_with_prevouts(&inputs);
+
+        assert!(report.is_valid());
+        assert_eq!(
+            transaction.signature_hash(0, &previous_output)?,
+            transaction.sighash(0, &previous_output, 0x41)?
+        );
+        assert!(transaction.sighash(1, &previous_output, 0x01).is_err());
+        assert!(!transaction.verify_with_prevouts(&HashMap::new()).is_valid());
+        Ok(())
     }
 
-    #[test]
-    fn verify_sighash_generation() -> Result<()> {
-        type TestInput = (String, String, usize, i32, String, String);
+    /// Re-encodes a signature with S replaced by `n - S`, which is just as
+    /// valid mathematically.
+    fn high_s(signature: &Signature) -> Vec<u8> {
+        const ORDER: [u8; 32] = [
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFE, 0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C,
+            0xD0, 0x36, 0x41, 0x41,
+        ];
+        let compact = signature.serialize_compact();
+        let mut s = [0u8; 32];
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let difference = ORDER[i] as i16 - compact[32 + i] as i16 - borrow;
+            borrow = (difference < 0) as i16;
+            s[i] = difference.rem_euclid(256) as u8;
+        }
 
-        let json_file = File::open("../tests/sigtest.json")?;
-        let inputs: Vec<TestInput> = serde_json::from_reader(json_file)?;
+        let integer = |value: &[u8]| {
+            let value = &value[value.iter().position(|b| *b != 0).unwrap_or(31)..];
+            let mut encoded = vec![0x02];
+            if value[0] & 0x80 != 0 {
+                encoded.push(value.len() as u8 + 1);
+                encoded.push(0x00);
+            } else {
+                encoded.push(value.len() as u8);
+            }
+            encoded.extend(value);
+            encoded
+        };
+        let body: Vec<u8> = integer(&compact[..32])
+            .into_iter()
+            .chain(integer(&s))
+            .collect();
+        let mut der = vec![0x30, body.len() as u8];
+        der.extend(body);
+        der
+    }
 
-        for input in inputs {
-            let (raw_tx, raw_script, index, sig_hash, sig_hash_reg_hex, sig_hash_old_hex) = input;
+    /// Re-encodes a signature with R padded by a redundant leading zero, which
+    /// BER allows but strict DER does not.
+    fn ber_padded(signature: &Signature) -> Vec<u8> {
+        let der = signature.serialize_der();
+        let mut padded = vec![0x30, der[1] + 1, 0x02, der[3] + 1, 0x00];
+        padded.extend(&der[4..]);
+        padded
+    }
 
-            let sig_hash: SigHash = sig_hash.into();
+    /// The fork vector with its first signature replaced by the high S form.
+    fn high_s_vector() -> Result<(Transaction, PreviousOutputs)> {
+        reencoded_vector(high_s)
+    }
 
-            let transaction: Transaction = hex::decode(raw_tx)?.try_into()?;
+    /// The fork vector with its first signature re-encoded by `encode`.
+    fn reencoded_vector(
+        encode: fn(&Signature) -> Vec<u8>,
+    ) -> Result<(Transaction, PreviousOutputs)> {
+        let (transaction, inputs) = fork_vector()?;
+        let script_sig = &transaction.inputs[0].script_sig;
+        let signature_length = script_sig[0] as usize;
+        let signature = Signature::from_der(&script_sig[1..signature_length])?;
+
+        let mut malleated = encode(&signature);
+        malleated.push(script_sig[signature_length]);
+        let mut new_script_sig = vec![malleated.len() as u8];
+        new_script_sig.extend(malleated);
+        new_script_sig.extend(&script_sig[signature_length + 1..]);
+        let mut tampered = transaction.clone();
+        tampered.inputs[0].script_sig = new_script_sig;
+        Ok((tampered, inputs))
+    }
 
-            let script = hex::decode(raw_script)?;
+    #[test]
+    fn verify_flags_high_s_signature() -> Result<()> {
+        let (tampered, inputs) = high_s_vector()?;
+
+        let report = tampered.verify_with_prevouts(&inputs);
+        assert!(!report.is_valid());
+        assert_eq!(
+            SignatureError::HighS(0).to_string(),
+            report.inputs[0].as_ref().unwrap_err().to_string()
+        );
+        assert!(report.inputs[1].is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_verify_accepts_high_s_signature() -> Result<()> {
+        let (tampered, inputs) = high_s_vector()?;
+
+        assert!(!tampered.verify_with_policy(&inputs, true).is_valid());
+        assert!(tampered.verify_with_policy(&inputs, false).is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn verify_flags_ber_padded_signature() -> Result<()> {
+        let (tampered, inputs) = reencoded_vector(ber_padded)?;
+        let script_sig = &tampered.inputs[0].script_sig;
+        let der = &script_sig[1..script_sig[0] as usize];
+        assert!(Signature::from_der(der).is_err());
+        assert!(Signature::from_der_lax(der).is_ok());
+
+        // Strict DER is required whether or not high S is tolerated
+        for require_low_s in [true, false] {
+            let report = tampered.verify_with_policy(&inputs, require_low_s);
+            assert_eq!(
+                SignatureError::NonCanonicalDer(0).to_string(),
+                report.inputs[0].as_ref().unwrap_err().to_string()
+            );
+            assert!(report.inputs[1].is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn signing_produces_low_s() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+        let transaction = send(&utxos, &keys, RECIPIENT, Satoshis(10_000))?;
+
+        let script_sig = &transaction.inputs[0].script_sig;
+        let signature = Signature::from_der(&script_sig[1..script_sig[0] as usize])?;
+        let mut normalized = signature;
+        normalized.normalize_s();
+        assert_eq!(normalized, signature);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_signature_no_fork() -> Result<()> {
+        let input = "0200000001c44c3bae60810fd288c11ec8682eaf88de396b2d53aae6ee3d5824e2f3dc3e96050000006a473044022005c396c208844da838467f05545862c63391f84dc07e02792d52784ae52cb32f022074ec4622b45fbd1accd5f59767f969aafc339367f18dca9162d2d122f75523b3012102be0aa60c89ce7ebe35418a79284bfb2fef25a3fac9262afb6ff6e9c546e9cd5bfeffffff01435d320000000000160014bf1bafa3caa7fb41eeb66218ce0cdb4f4b3b95e398010c00";
+
+        let transaction = Transaction::from_hex(input)?;
+
+        assert_eq!(input, transaction.to_hex());
+
+        let mut inputs = HashMap::new();
+        inputs.insert(
+            (
+                hex::decode("963edcf3e224583deee6aa532d6b39de88af2e68c81ec188d20f8160ae3b4cc4")?,
+                5,
+            ),
+            Output {
+                amount: Satoshis(3303000),
+                script: hex::decode("76a914152fc05ea22a712eb8227e57dbd8d79451ea0e3e88ac")?,
+            },
+        );
+
+        transaction.verify(&inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn verify_sighash_generation() -> Result<()> {
+        type TestInput = (String, String, usize, i32, String, String);
+
+        let json_file = File::open("../tests/sigtest.json")?;
+        let inputs: Vec<TestInput> = serde_json::from_reader(json_file)?;
+
+        for input in inputs {
+            let (raw_tx, raw_script, index, sig_hash, sig_hash_reg_hex, sig_hash_old_hex) = input;
+
+            let sig_hash: SigHash = sig_hash.into();
+
+            let transaction = Transaction::from_hex(&raw_tx)?;
+
+            let script = hex::decode(raw_script)?;
             let sig_hash_regular =
                 signature_hash(&transaction, index, &script, &sig_hash, 0, true)?;
             assert_eq!(
@@ -689,6 +2225,117 @@ mod tests {
         Ok(())
     }
 
+    #[derive(Deserialize)]
+    struct SignVector {
+        description: String,
+        /// Unsigned transaction, hex encoded.
+        transaction: String,
+        sighash: String,
+        anyone_can_pay: bool,
+        /// Secret keys, hex encoded. Each may own compressed and uncompressed
+        /// key outputs.
+        keys: Vec<String>,
+        prevouts: Vec<SignVectorPrevout>,
+        /// Expected signed transaction, hex encoded.
+        signed: String,
+    }
+
+    #[derive(Deserialize)]
+    struct SignVectorPrevout {
+        tx_hash: String,
+        index: u32,
+        amount: Satoshis,
+        script: String,
+    }
+
+    #[test]
+    fn sign_inputs_matches_vectors() -> Result<()> {
+        let json_file = File::open("../tests/signtest.json")?;
+        let vectors: Vec<SignVector> = serde_json::from_reader(json_file)?;
+
+        for vector in vectors {
+            let mut transaction = Transaction::from_hex(&vector.transaction)?;
+            let mut prev_outs = HashMap::new();
+            for prevout in vector.prevouts {
+                prev_outs.insert(
+                    (hex::decode(prevout.tx_hash)?, prevout.index),
+                    Output {
+                        amount: prevout.amount,
+                        script: hex::decode(prevout.script)?,
+                    },
+                );
+            }
+            let mut address_keys = HashMap::new();
+            for key in vector.keys {
+                let sk = SecretKey::from_str(&key)?;
+                let pk = PublicKey::from_secret_key_global(&sk);
+                address_keys.insert(ripemd160(&sha256(&pk.serialize())), (sk, pk));
+                address_keys.insert(ripemd160(&sha256(&pk.serialize_uncompressed())), (sk, pk));
+            }
+            let sighash_type = SigHashType {
+                base: vector.sighash.parse()?,
+                anyone_can_pay: vector.anyone_can_pay,
+            };
+
+            transaction.sign_inputs_with(&prev_outs, &address_keys, sighash_type)?;
+
+            assert_eq!(
+                vector.signed,
+                transaction.to_hex(),
+                "{}",
+                vector.description
+            );
+            transaction.verify(&prev_outs)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_fork_commits_to_amount() -> Result<()> {
+        let raw_tx = "01000000017b1eabe0209b1fe794124575ef807057c77ada2138ae4fa8d6c4de0398a14f3f0000000000ffffffff01f0ca052a010000001976a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac00000000";
+        let transaction = Transaction::from_hex(raw_tx)?;
+        let script = hex::decode("76a914cbc20a7664f2f69e5355aa427045bc15e7c6c77288ac")?;
+        let sig_hash = SigHash::default();
+
+        let correct = transaction.hash_fork(0, &script, &sig_hash, 5000000000)?;
+        let mismatched = transaction.hash_fork(0, &script, &sig_hash, 4999990000)?;
+
+        assert_ne!(correct, mismatched);
+        assert!(transaction
+            .hash_fork(0, &script, &sig_hash, MAX_MONEY.0 + 1)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fork_preimage_matches_bip143_vector() -> Result<()> {
+        // Native P2WPKH example of BIP143, whose algorithm the fork id uses
+        let raw_tx = "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000";
+        let transaction = Transaction::from_hex(raw_tx)?;
+        let script = hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac")?;
+        let sig_hash = SigHash { value: 0x01 };
+
+        let preimage = transaction.fork_preimage(1, &script, &sig_hash, 600_000_000)?;
+
+        assert_eq!(
+            "0100000096b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd3752b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3bef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a010000001976a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac0046c32300000000ffffffff863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e51100000001000000",
+            hex::encode(&preimage)
+        );
+        assert_eq!(
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670",
+            hex::encode(double_sha256(&preimage))
+        );
+
+        let previous_output = Output::from_script(Satoshis(600_000_000), script);
+        let forked = transaction.sighash_preimage(1, &previous_output, 0x41)?;
+        assert_eq!(
+            hex::encode(transaction.sighash(1, &previous_output, 0x41)?),
+            hex::encode(double_sha256(&hex::decode(forked)?))
+        );
+        Ok(())
+    }
+
     fn signature_hash(
         transaction: &Transaction,
         index: usize,
@@ -696,7 +2343,7 @@ mod tests {
         sig_hash: &SigHash,
         amount: u64,
         enable_fork: bool,
-    ) -> Result<[u8; 32]> {
+    ) -> Result<[u8; 32], SignatureError> {
         if enable_fork {
             transaction.hash_fork(index, script, sig_hash, amount)
         } else {
@@ -710,18 +2357,13 @@ mod tests {
         transaction.add_input(Input::new_decoded(
             hex::decode("ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373")?,
             1,
-        ));
+        )?);
         transaction.add_output(Output {
-            amount: 5274723,
+            amount: Satoshis(5274723),
             script: hex::decode("76a9140c6a3b21b00ddc232da8a62bb24aa031e0a93be188ac")?,
         });
 
-        let sk = SecretKey::from_str(
-            "2e7d8617942ef7cb24aae1ab35dfa39e5e3d7f4fc3060ca5247acf375a8ec456",
-        )?;
-        let pk = PublicKey::from_str(
-            "03209b1875a86a7dbc7a8b65965b5df44a97d5010725c920a28869ed740ff5852e",
-        )?;
+        let (sk, pk) = change_key()?;
 
         let mut address_keys = HashMap::new();
         address_keys.insert(
@@ -739,13 +2381,855 @@ mod tests {
                 1,
             ),
             Output {
-                amount: 5274723,
+                amount: Satoshis(5274723),
                 script: hex::decode("76a9140c6a3b21b00ddc232da8a62bb24aa031e0a93be188ac")?,
             },
         );
 
         transaction.sign_inputs(&prev_outs, &address_keys)?;
 
-        transaction.verify(&prev_outs)
+        transaction.verify(&prev_outs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sign_uses_uncompressed_key_for_uncompressed_address() -> Result<()> {
+        let tx_hash =
+            hex::decode("ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373")?;
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(tx_hash.clone(), 1)?);
+        transaction.add_output(Output::new(Satoshis(5274000), CHANGE, Network::Mainnet)?);
+
+        let (sk, pk) = change_key()?;
+        let uncompressed = ripemd160(&sha256(&pk.serialize_uncompressed()));
+
+        let mut prev_outs = HashMap::new();
+        prev_outs.insert(
+            (tx_hash, 1),
+            Output::new_from_decoded(Satoshis(5274723), uncompressed),
+        );
+        let address_keys = HashMap::from([(uncompressed, (sk, pk))]);
+
+        transaction.sign_inputs(&prev_outs, &address_keys)?;
+
+        assert!(transaction.inputs[0]
+            .script_sig
+            .ends_with(&pk.serialize_uncompressed()));
+        transaction.verify(&prev_outs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn signs_derived_and_imported_inputs_together() -> Result<()> {
+        let xprv: crate::bip32::XPrv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".parse()?;
+        let derived = xprv.derive_normal(0)?.derive_normal(0)?.to_keypair();
+        let derived_address = ripemd160(&sha256(&derived.1.serialize()));
+        let imported: crate::wif::PrivateKey =
+            "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ".parse()?;
+        let (imported_address, imported_keys) = imported.address_key();
+        let utxos: Vec<_> = utxos()
+            .into_iter()
+            .zip([derived_address, imported_address])
+            .map(|(utxo, address)| RichOutput { address, ..utxo })
+            .collect();
+        let keys = HashMap::from([
+            (derived_address, derived),
+            (imported_address, imported_keys),
+        ]);
+
+        // Needs both coins, send verifies every input's signature
+        let transaction = send(&utxos, &keys, RECIPIENT, Satoshis(60_000))?;
+
+        assert_eq!(2, transaction.inputs.len());
+        assert_eq!(
+            imported.address(Network::Mainnet),
+            crate::util::to_address(imported_address, Network::Mainnet)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bump_fee_spends_same_coins_at_higher_rate() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000, 50_000])?;
+        let previous_outputs = previous_outputs(&utxos)?;
+        // Change goes to a fresh address, not the one of the spent coins
+        let change = [0x0c; 20];
+        let recipients = [(RECIPIENT.to_owned(), Satoshis(30_000))];
+        let original = prepare_and_sign(
+            &recipients,
+            &utxos,
+            &crate::util::to_address(change, Network::Mainnet),
+            Network::Mainnet,
+            &keys,
+            1,
+            &SendOptions::default(),
+        )?;
+        let paid = original.actual_fee(&previous_outputs)?;
+        let is_change = |hash: &[u8; 20]| *hash == change;
+
+        let bumped = original.bump_fee(&previous_outputs, &keys, is_change, 5)?;
+
+        bumped.verify(&previous_outputs)?;
+        assert_eq!(original.outpoints(), bumped.outpoints());
+        assert_ne!(original.txid(), bumped.txid());
+        let fee = bumped.actual_fee(&previous_outputs)?;
+        assert_eq!(Satoshis(paid.0 * 5), fee);
+        assert!(bumped.outputs.iter().any(|o| o.amount == Satoshis(30_000)));
+
+        assert!(matches!(
+            bumped.bump_fee(&previous_outputs, &keys, is_change, 5),
+            Err(Error::Sending(SendingError::FeeNotHigher(..)))
+        ));
+        assert!(matches!(
+            original.bump_fee(&previous_outputs, &keys, is_change, 1_000),
+            Err(Error::Sending(SendingError::InsufficientFee(_)))
+        ));
+        assert!(matches!(
+            original.bump_fee(&previous_outputs, &keys, |_| false, 5),
+            Err(Error::Sending(SendingError::NoChange))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn sign_with_selected_sighash_type() -> Result<()> {
+        let tx_hash =
+            hex::decode("ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373")?;
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(tx_hash.clone(), 1)?);
+        transaction.add_input(Input::new_decoded(tx_hash.clone(), 2)?);
+        transaction.add_output(Output::new(Satoshis(10_000), CHANGE, Network::Mainnet)?);
+
+        let (sk, pk) = change_key()?;
+        let address = ripemd160(&sha256(&pk.serialize()));
+        let prev_outs = HashMap::from([
+            (
+                (tx_hash.clone(), 1),
+                Output::new_from_decoded(Satoshis(6_000), address),
+            ),
+            (
+                (tx_hash, 2),
+                Output::new_from_decoded(Satoshis(6_000), address),
+            ),
+        ]);
+        let address_keys = HashMap::from([(address, (sk, pk))]);
+
+        let single = SigHashType {
+            base: SigHashBase::Single,
+            anyone_can_pay: false,
+        };
+        let error = transaction
+            .clone()
+            .sign_inputs_with(&prev_outs, &address_keys, single)
+            .unwrap_err();
+        assert!(matches!(error, SignatureError::SingleWithoutOutput(1, 2)));
+
+        let none_anyone_can_pay = SigHashType {
+            base: "NONE".parse()?,
+            anyone_can_pay: true,
+        };
+        transaction.sign_inputs_with(&prev_outs, &address_keys, none_anyone_can_pay)?;
+
+        let signature_length = transaction.inputs[0].script_sig[0] as usize;
+        assert_eq!(0xC2, transaction.inputs[0].script_sig[signature_length]);
+        transaction.verify(&prev_outs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sighash_type_warns_unless_default() -> Result<()> {
+        assert_eq!(None, SigHashType::default().warning());
+        assert_eq!(0x41, SigHashType::default().sig_hash().value);
+
+        for base in SigHashBase::ALL {
+            assert_eq!(base, base.name().parse()?);
+            let anyone_can_pay = SigHashType {
+                base,
+                anyone_can_pay: true,
+            };
+            assert!(anyone_can_pay.warning().is_some());
+        }
+        assert!(SigHashType {
+            base: SigHashBase::Single,
+            anyone_can_pay: false,
+        }
+        .warning()
+        .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn sign_raw_transaction_signs_owned_inputs() -> Result<()> {
+        let owned_hash = "ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373";
+        let foreign_hash = "3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b";
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new_decoded(hex::decode(owned_hash)?, 1)?);
+        transaction.add_input(Input::new_decoded(hex::decode(foreign_hash)?, 0)?);
+        transaction.add_output(Output::new(
+            Satoshis(5_000_000),
+            RECIPIENT,
+            Network::Mainnet,
+        )?);
+        let raw = transaction.to_hex();
+
+        let (sk, pk) = change_key()?;
+        let address_keys = HashMap::from([(crate::util::address_bytes(CHANGE)?, (sk, pk))]);
+        let mut prev_outs = HashMap::from([
+            (
+                (hex::decode(owned_hash)?, 1),
+                Output::from_script(
+                    Satoshis(5274723),
+                    hex::decode("76a9140c6a3b21b00ddc232da8a62bb24aa031e0a93be188ac")?,
+                ),
+            ),
+            (
+                (hex::decode(foreign_hash)?, 0),
+                Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?,
+            ),
+        ]);
+
+        let summary = transaction.signing_summary(&prev_outs, Network::Mainnet);
+        assert_eq!(
+            vec![(RECIPIENT.to_owned(), Satoshis(5_000_000))],
+            summary.outputs
+        );
+        assert_eq!(Some(Satoshis(284_723)), summary.fee);
+        prev_outs.remove(&(hex::decode(foreign_hash)?, 0));
+        assert_eq!(
+            None,
+            transaction
+                .signing_summary(&prev_outs, Network::Mainnet)
+                .fee
+        );
+
+        let signed = sign_raw_transaction(&raw, &prev_outs, &address_keys)?;
+
+        assert_eq!(vec![(foreign_hash.to_owned(), 0)], signed.unsigned_inputs);
+        let signed = Transaction::from_hex(&signed.raw)?;
+        assert!(!signed.inputs[0].script_sig.is_empty());
+        assert!(signed.inputs[1].script_sig.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn sign_spends_cltv_output() -> Result<()> {
+        let hash = "3967ad2de67356564743545dbc41fbf882f8c078ce037afba10bd4435ef3d7b9";
+        let mut locked = vec![];
+        script::push_data(&mut locked, &[0x00, 0x35, 0x0c]);
+        locked.extend([script::OP_CHECKLOCKTIMEVERIFY, script::OP_DROP]);
+        locked.extend(Output::new(Satoshis(0), CHANGE, Network::Mainnet)?.script);
+        let prev_outs = HashMap::from([(
+            (hex::decode(hash)?, 0),
+            Output::from_script(Satoshis(10_000), locked),
+        )]);
+        let (sk, pk) = change_key()?;
+        let address_keys = HashMap::from([(crate::util::address_bytes(CHANGE)?, (sk, pk))]);
+
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(hash.to_owned(), 0)?);
+        transaction.add_output(Output::new(Satoshis(9_000), RECIPIENT, Network::Mainnet)?);
+        let mut raw = transaction.clone();
+        transaction.sign_inputs(&prev_outs, &address_keys)?;
+
+        // Signing only the owned inputs of a raw transaction sets them up too
+        assert!(raw.sign_owned_inputs(&prev_outs, &address_keys)?.is_empty());
+        raw.verify(&prev_outs)?;
+
+        assert_eq!(800_000, transaction.locktime);
+        assert_eq!(SEQUENCE_FINAL - 1, transaction.inputs[0].sequence);
+        transaction.verify(&prev_outs)?;
+
+        let mut early = transaction.clone();
+        early.locktime = 799_999;
+        assert!(matches!(
+            early.verify(&prev_outs),
+            Err(SignatureError::Locked(0, 800_000))
+        ));
+        Ok(())
+    }
+
+    fn rich_output(tx_hash: &str, amount: u64) -> RichOutput {
+        RichOutput {
+            tx_pos: 1,
+            tx_hash: tx_hash.to_owned(),
+            amount: Satoshis(amount),
+            address: [0x0c; 20],
+            height: Some(800_000),
+            key_path: None,
+        }
+    }
+
+    fn utxos() -> Vec<RichOutput> {
+        vec![
+            rich_output(
+                "3967ad2de67356564743545dbc41fbf882f8c078ce037afba10bd4435ef3d7b9",
+                20_000,
+            ),
+            rich_output(
+                "ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373",
+                50_000,
+            ),
+            rich_output(
+                "3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b",
+                30_000,
+            ),
+        ]
+    }
+
+    const RECIPIENT: &str = "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr";
+    const CHANGE: &str = "128eNMtCawUATwdCdxrRSQqVa4rsHjWrpj";
+
+    #[test]
+    fn build_with_manual_inputs_uses_selection() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(25_000), RECIPIENT, Network::Mainnet)?);
+        builder.use_inputs(vec![utxos[2].clone()]);
+
+        let transaction = builder.build_unsigned(&utxos)?.0;
+
+        assert_eq!(1, transaction.inputs.len());
+        assert_eq!(
+            hex::decode("3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b")?,
+            transaction.inputs[0].tx_hash
+        );
+        assert_eq!(2, transaction.outputs.len());
+        let fee = Satoshis(30_000) - transaction.outputs.iter().map(|o| o.amount).sum();
+        assert!(fee > Satoshis(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_unsigned_reports_fee_without_signing() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(75_000), RECIPIENT, Network::Mainnet)?);
+        builder.set_fee_rate(2);
+
+        let (transaction, breakdown) = builder.build_unsigned(&utxos)?;
+
+        assert_eq!(3, transaction.inputs.len());
+        assert!(transaction.inputs.iter().all(|i| i.script_sig.is_empty()));
+        assert_eq!(
+            vec![
+                Output::new(Satoshis(75_000), RECIPIENT, Network::Mainnet)?,
+                Output::new(breakdown.change, CHANGE, Network::Mainnet)?
+            ],
+            transaction.outputs
+        );
+        assert_eq!(Satoshis(100_000), breakdown.inputs);
+        assert_eq!(Satoshis(75_000), breakdown.sent);
+        assert_eq!(Satoshis(25_000), breakdown.change + breakdown.fee);
+        assert_eq!(
+            breakdown.fee,
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shuffled_outputs_sign_and_verify() -> Result<()> {
+        let (utxos, address_keys) = owned_utxos(&[20_000, 50_000, 30_000])?;
+        let build = |seed| -> Result<Transaction> {
+            let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+            builder.add_output(Output::new(Satoshis(25_000), RECIPIENT, Network::Mainnet)?);
+            builder.add_output(Output::new(Satoshis(15_000), CHANGE, Network::Mainnet)?);
+            if let Some(seed) = seed {
+                builder.shuffle_outputs(seed);
+            }
+            Ok(builder.build_unsigned(&utxos)?.0)
+        };
+        let sorted = |transaction: &Transaction| {
+            let mut outputs: Vec<_> = transaction.outputs.iter().map(Vec::from).collect();
+            outputs.sort();
+            outputs
+        };
+        let ordered = build(None)?;
+
+        let mut change_positions = HashSet::new();
+        for seed in 0..16 {
+            let mut shuffled = build(Some(seed))?;
+            shuffled.sign_inputs(&previous_outputs(&utxos)?, &address_keys)?;
+
+            shuffled.verify(&previous_outputs(&utxos)?)?;
+            assert_eq!(sorted(&ordered), sorted(&shuffled));
+            change_positions.extend(
+                shuffled
+                    .outputs
+                    .iter()
+                    .position(|o| o == ordered.outputs.last().unwrap()),
+            );
+        }
+        assert_eq!(3, change_positions.len());
+        Ok(())
+    }
+
+    type Keys = HashMap<[u8; 20], (SecretKey, PublicKey)>;
+
+    /// Coins owned by the `CHANGE` address and its key.
+    fn owned_utxos(amounts: &[u64]) -> Result<(Vec<RichOutput>, Keys)> {
+        let owner = crate::util::address_bytes(CHANGE)?;
+        let utxos = amounts
+            .iter()
+            .zip(utxos())
+            .map(|(&amount, utxo)| RichOutput {
+                amount: Satoshis(amount),
+                address: owner,
+                ..utxo
+            })
+            .collect();
+        Ok((utxos, HashMap::from([(owner, change_key()?)])))
+    }
+
+    /// The key of the `CHANGE` address.
+    fn change_key() -> Result<(SecretKey, PublicKey)> {
+        let sk = SecretKey::from_str(
+            "2e7d8617942ef7cb24aae1ab35dfa39e5e3d7f4fc3060ca5247acf375a8ec456",
+        )?;
+        Ok((sk, PublicKey::from_secret_key_global(&sk)))
+    }
+
+    /// Fee estimated for spending one coin to one address at 1 sat/byte.
+    fn single_input_fee() -> Result<Satoshis> {
+        Ok(single_input_transaction()?.suggested_fee(1))
+    }
+
+    fn single_input_transaction() -> Result<Transaction> {
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(utxos()[0].tx_hash.clone(), 1)?);
+        transaction.add_output(Output::new(Satoshis(0), RECIPIENT, Network::Mainnet)?);
+        Ok(transaction)
+    }
+
+    fn send(
+        utxos: &[RichOutput],
+        keys: &Keys,
+        address: &str,
+        amount: Satoshis,
+    ) -> Result<Transaction, Error> {
+        let recipients = [(address.to_owned(), amount)];
+        let transaction = prepare_and_sign(
+            &recipients,
+            utxos,
+            CHANGE,
+            Network::Mainnet,
+            keys,
+            1,
+            &SendOptions::default(),
+        )?;
+        transaction.verify(&previous_outputs(utxos)?)?;
+        Ok(transaction)
+    }
+
+    #[test]
+    fn prepare_and_sign_only_signs_all() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+        let recipients = [(RECIPIENT.to_owned(), Satoshis(10_000))];
+        for sighash_type in [
+            SigHashType {
+                base: SigHashBase::None,
+                anyone_can_pay: false,
+            },
+            SigHashType {
+                base: SigHashBase::All,
+                anyone_can_pay: true,
+            },
+        ] {
+            let options = SendOptions {
+                sighash_type,
+                ..SendOptions::default()
+            };
+
+            assert!(matches!(
+                prepare_and_sign(
+                    &recipients,
+                    &utxos,
+                    CHANGE,
+                    Network::Mainnet,
+                    &keys,
+                    1,
+                    &options
+                ),
+                Err(Error::Sending(SendingError::CustomSigHash))
+            ));
+            // Preparing one to share for co-signing still works
+            assert!(prepare(&recipients, &utxos, CHANGE, Network::Mainnet, 1, &options).is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_and_sign_exact_change() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+        let amount = Satoshis(20_000) - single_input_fee()?;
+
+        let transaction = send(&utxos, &keys, RECIPIENT, amount)?;
+
+        assert_eq!(
+            vec![Output::new(amount, RECIPIENT, Network::Mainnet)?],
+            transaction.outputs
+        );
+        assert_eq!(
+            single_input_fee()?,
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_and_sign_adds_input_for_fee() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000, 30_000])?;
+
+        let transaction = send(&utxos, &keys, RECIPIENT, Satoshis(20_000))?;
+
+        assert_eq!(2, transaction.inputs.len());
+        assert_eq!(2, transaction.outputs.len());
+        assert_eq!(
+            Output::new(Satoshis(20_000), RECIPIENT, Network::Mainnet)?,
+            transaction.outputs[0]
+        );
+        assert_eq!(
+            Output::new(Satoshis(0), CHANGE, Network::Mainnet)?.script,
+            transaction.outputs[1].script
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fee_includes_change_output_only_with_change() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+        let without_change = single_input_transaction()?.suggested_fee_without_change(1);
+        assert_eq!(single_input_fee()?, without_change + Satoshis(34));
+
+        let transaction = send(&utxos, &keys, RECIPIENT, Satoshis(10_000))?;
+        assert_eq!(2, transaction.outputs.len());
+        assert_eq!(
+            single_input_fee()?,
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+
+        // Affordable only because no change output is paid for
+        let amount = Satoshis(20_000) - without_change;
+        let transaction = send(&utxos, &keys, RECIPIENT, amount)?;
+        assert_eq!(1, transaction.outputs.len());
+        assert_eq!(
+            without_change,
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_and_sign_reports_insufficient_funds() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000, 30_000])?;
+
+        let error = send(&utxos, &keys, RECIPIENT, Satoshis(60_000)).unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::Sending(SendingError::InsufficientBalance(Satoshis(10_000)))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_and_sign_gives_dust_change_to_fee() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+        let dust_change = DUST_LIMIT - Satoshis(1);
+        let amount = Satoshis(20_000) - single_input_fee()? - dust_change;
+
+        let transaction = send(&utxos, &keys, RECIPIENT, amount)?;
+
+        assert_eq!(1, transaction.outputs.len());
+        assert_eq!(
+            single_input_fee()? + dust_change,
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_and_sign_rejects_invalid_address() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000])?;
+
+        let error = send(
+            &utxos,
+            &keys,
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQH0",
+            Satoshis(1_000),
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, Error::Address(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn signing_is_deterministic() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000, 30_000])?;
+
+        let first = send(&utxos, &keys, RECIPIENT, Satoshis(20_000))?;
+        let second = send(&utxos, &keys, RECIPIENT, Satoshis(20_000))?;
+
+        assert_eq!(
+            first
+                .inputs
+                .iter()
+                .map(|i| &i.script_sig)
+                .collect::<Vec<_>>(),
+            second
+                .inputs
+                .iter()
+                .map(|i| &i.script_sig)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(first.txid(), second.txid());
+        Ok(())
+    }
+
+    #[test]
+    fn sweep_warning_only_for_unintended_sweeps() -> Result<()> {
+        let (utxos, _) = owned_utxos(&[20_000, 30_000])?;
+        let options = SendOptions::default();
+        let prepare_amount = |amount| {
+            let recipients = [(RECIPIENT.to_owned(), amount)];
+            prepare(&recipients, &utxos, CHANGE, Network::Mainnet, 1, &options)
+        };
+
+        let max = max_sendable(&utxos, 1);
+        let (transaction, breakdown) = prepare_amount(max)?;
+        assert_eq!(None, sweep_warning(&transaction, &breakdown, &utxos, true));
+        assert_eq!(
+            Some(SendWarning::EmptiesWallet),
+            sweep_warning(&transaction, &breakdown, &utxos, false)
+        );
+
+        let (transaction, breakdown) = prepare_amount(Satoshis(40_000))?;
+        assert_eq!(None, sweep_warning(&transaction, &breakdown, &utxos, false));
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_checks_addresses_against_network() -> Result<()> {
+        let (utxos, _) = owned_utxos(&[20_000])?;
+        let testnet = |address| {
+            crate::util::address_bytes(address).map(|hash| util::to_address(hash, Network::Testnet))
+        };
+        let recipients = [(testnet(RECIPIENT)?, Satoshis(10_000))];
+        let change = testnet(CHANGE)?;
+        let options = SendOptions::default();
+
+        let (transaction, _) =
+            prepare(&recipients, &utxos, &change, Network::Testnet, 1, &options)?;
+        assert_eq!(
+            Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?.script,
+            transaction.outputs[0].script
+        );
+        let error =
+            prepare(&recipients, &utxos, &change, Network::Mainnet, 1, &options).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Address(AddressError::WrongNetwork {
+                found: Network::Testnet,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn send_summary_matches_broadcast() -> Result<()> {
+        let (utxos, keys) = owned_utxos(&[20_000, 30_000])?;
+        let transaction = send(&utxos, &keys, RECIPIENT, Satoshis(20_000))?;
+
+        let summary = SendSummary::new(
+            &transaction,
+            &utxos,
+            |address| address == CHANGE,
+            Network::Mainnet,
+        )?;
+
+        assert_eq!(
+            SendSummary {
+                txid: transaction.txid(),
+                amount: Satoshis(20_000),
+                fee: transaction.actual_fee(&previous_outputs(&utxos)?)?,
+                recipients: vec![SummaryRecipient {
+                    address: RECIPIENT.to_owned(),
+                    amount: Satoshis(20_000),
+                }],
+            },
+            summary
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn build_with_insufficient_manual_inputs_reports_shortfall() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(25_000), RECIPIENT, Network::Mainnet)?);
+        builder.use_inputs(vec![utxos[0].clone()]);
+
+        let error = builder.build_unsigned(&utxos).unwrap_err();
+
+        match error {
+            Error::Sending(SendingError::InsufficientSelection(missing)) => {
+                assert!(missing > Satoshis(5_000))
+            }
+            _ => panic!("Unexpected error: {error:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn consolidate_merges_small_outputs() -> Result<()> {
+        let utxos = utxos();
+
+        let (transaction, fee) = consolidate(
+            &utxos,
+            Some(Satoshis(40_000)),
+            CHANGE,
+            Network::Mainnet,
+            DEFAULT_FEE_RATE,
+        )?;
+
+        assert_eq!(2, transaction.inputs.len());
+        assert_eq!(1, transaction.outputs.len());
+        assert_eq!(Satoshis(50_000) - fee, transaction.outputs[0].amount);
+        assert_eq!(
+            Output::new(Satoshis(0), CHANGE, Network::Mainnet)?.script,
+            transaction.outputs[0].script
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn consolidate_rejects_fee_above_value() {
+        let utxos = vec![
+            rich_output(
+                "3967ad2de67356564743545dbc41fbf882f8c078ce037afba10bd4435ef3d7b9",
+                100,
+            ),
+            rich_output(
+                "ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373",
+                100,
+            ),
+        ];
+
+        let error =
+            consolidate(&utxos, None, CHANGE, Network::Mainnet, DEFAULT_FEE_RATE).unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<SendingError>(),
+            Some(SendingError::FeeExceedsValue(_, Satoshis(200)))
+        ));
+    }
+
+    #[test]
+    fn consolidate_rejects_dust_output() {
+        let utxos = vec![
+            rich_output(
+                "3967ad2de67356564743545dbc41fbf882f8c078ce037afba10bd4435ef3d7b9",
+                400,
+            ),
+            rich_output(
+                "ba3e421c5c0835a07f15c83df681654104593a8979a2d2953fff6d055f33c373",
+                400,
+            ),
+        ];
+
+        let error =
+            consolidate(&utxos, None, CHANGE, Network::Mainnet, DEFAULT_FEE_RATE).unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<SendingError>(),
+            Some(SendingError::BelowDust(_, DUST_LIMIT))
+        ));
+    }
+
+    #[test]
+    fn consolidate_applies_fee_rate() -> Result<()> {
+        let utxos = utxos();
+
+        let (_, default_fee) =
+            consolidate(&utxos, None, CHANGE, Network::Mainnet, DEFAULT_FEE_RATE)?;
+        let (transaction, fee) = consolidate(&utxos, None, CHANGE, Network::Mainnet, 3)?;
+
+        assert_eq!(Satoshis(default_fee.0 * 3), fee);
+        assert_eq!(Satoshis(100_000) - fee, transaction.outputs[0].amount);
+        Ok(())
+    }
+
+    #[test]
+    fn build_applies_fee_rate() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+        builder.set_fee_rate(3);
+
+        let transaction = builder.build_unsigned(&utxos)?.0;
+        let fee = transaction.actual_fee(&previous_outputs(&utxos)?)?;
+
+        assert_eq!(
+            Satoshis(3 * (Vec::from(&transaction).len() as u64 + 107)),
+            fee
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn build_drops_dust_change() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(10_000), RECIPIENT, Network::Mainnet)?);
+        builder.use_inputs(vec![utxos[0].clone()]);
+        let with_change = builder.build_unsigned(&utxos)?.0;
+        let change = with_change.outputs[1].amount;
+        let fee = with_change.actual_fee(&previous_outputs(&utxos)?)?;
+
+        let amount = Satoshis(10_000) + change - DUST_LIMIT + Satoshis(1);
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(amount, RECIPIENT, Network::Mainnet)?);
+        builder.use_inputs(vec![utxos[0].clone()]);
+        let transaction = builder.build_unsigned(&utxos)?.0;
+
+        assert_eq!(1, transaction.outputs.len());
+        assert_eq!(amount, transaction.outputs[0].amount);
+        assert_eq!(
+            fee + DUST_LIMIT - Satoshis(1),
+            transaction.actual_fee(&previous_outputs(&utxos)?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_send_amount_enforces_minimum_and_dust() {
+        let error = |amount, dust_limit| check_send_amount(amount, dust_limit).unwrap_err();
+
+        assert!(matches!(
+            error(Satoshis(0), Satoshis(0)),
+            SendingError::AmountTooSmall
+        ));
+        assert!(check_send_amount(Satoshis(1), Satoshis(0)).is_ok());
+        assert!(check_send_amount(Satoshis(1), Satoshis(1)).is_ok());
+        assert!(matches!(
+            error(Satoshis(1), DUST_LIMIT),
+            SendingError::BelowDust(Satoshis(1), DUST_LIMIT)
+        ));
+        assert!(check_send_amount(DUST_LIMIT, DUST_LIMIT).is_ok());
+    }
+
+    #[test]
+    fn build_selects_coins_in_order() -> Result<()> {
+        let utxos = utxos();
+        let mut builder = TransactionBuilder::new(CHANGE, Network::Mainnet);
+        builder.add_output(Output::new(Satoshis(25_000), RECIPIENT, Network::Mainnet)?);
+
+        let transaction = builder.build_unsigned(&utxos)?.0;
+
+        assert_eq!(2, transaction.inputs.len());
+        Ok(())
     }
 }
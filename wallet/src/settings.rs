@@ -0,0 +1,368 @@
+use std::ops::RangeInclusive;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::sending::DEFAULT_FEE_RATE;
+use crate::storage::{self, StorageKey};
+use crate::transactions::DEFAULT_TIMEOUT_MS;
+use crate::util::{Network, Unit};
+
+const CONFIG_FORMAT: &str = "beesv-config";
+pub const CONFIG_FILE_NAME: &str = "beesv-config.json";
+pub const DEFAULT_ENDPOINT: &str = "https://api.whatsonchain.com/v1/bsv/main";
+const TESTNET_ENDPOINT: &str = "https://api.whatsonchain.com/v1/bsv/test";
+pub const GAP_LIMITS: RangeInclusive<usize> = 1..=100;
+const FEE_RATES: RangeInclusive<u64> = 1..=1_000;
+const SYNC_INTERVALS_MS: RangeInclusive<u32> = 1_000..=3_600_000;
+const REQUEST_TIMEOUTS_MS: RangeInclusive<u32> = 1_000..=120_000;
+/// Highest index a forced scan may reach, each 20 addresses cost a request.
+const MAX_SCAN_TO_INDEX: u32 = 10_000;
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Gap limit must be between {} and {}, was {0}", GAP_LIMITS.start(), GAP_LIMITS.end())]
+    GapLimit(usize),
+    #[error("Fee rate must be between {} and {} sat/byte, was {0}", FEE_RATES.start(), FEE_RATES.end())]
+    FeeRate(u64),
+    #[error("Endpoint must be an https URL: {0}")]
+    Endpoint(String),
+    #[error("Endpoint {0} is not for {1}")]
+    EndpointNetwork(String, Network),
+    #[error("Sync interval must be between {} and {} ms, was {0}", SYNC_INTERVALS_MS.start(), SYNC_INTERVALS_MS.end())]
+    SyncInterval(u32),
+    #[error("Request timeout must be between {} and {} ms, was {0}", REQUEST_TIMEOUTS_MS.start(), REQUEST_TIMEOUTS_MS.end())]
+    RequestTimeout(u32),
+    #[error("Scan index must be at most {MAX_SCAN_TO_INDEX}, was {0}")]
+    ScanToIndex(u32),
+    #[error("Sends can only be announced to an https origin like https://example.com: {0}")]
+    AnnounceOrigin(String),
+    #[error("Not a BeeSV settings backup")]
+    NotAConfig,
+    #[error("Settings are for {found}, this wallet is on {expected}")]
+    WrongNetwork { found: Network, expected: Network },
+}
+
+/// User tunable behavior. Fields missing from stored settings, e.g. ones
+/// added after they were saved, take their default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Consecutive unused addresses after which scanning a chain stops.
+    pub gap_limit: usize,
+    /// Satoshis per byte to pay when no fee quote could be fetched.
+    pub fee_rate: u64,
+    /// Base URL of the WhatsOnChain compatible API.
+    pub endpoint: String,
+    pub sync_interval_ms: u32,
+    /// How long a network request may take before it fails as timed out.
+    pub request_timeout_ms: u32,
+    /// Index up to which addresses are scanned regardless of gaps, for
+    /// wallets used by software that skipped many addresses.
+    pub scan_to_index: Option<u32>,
+    /// Puts the change output at a random position when sending.
+    pub shuffle_outputs: bool,
+    /// Origin whose open pages get a `beesv-sent` event after each
+    /// broadcast, see [`SendSummary`](crate::sending::SendSummary).
+    pub announce_origin: Option<String>,
+    /// Unit amounts are shown and entered in.
+    pub unit: Unit,
+    /// Network addresses are derived, validated and looked up on.
+    pub network: Network,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            gap_limit: 20,
+            fee_rate: DEFAULT_FEE_RATE,
+            endpoint: DEFAULT_ENDPOINT.to_owned(),
+            sync_interval_ms: 5_000,
+            request_timeout_ms: DEFAULT_TIMEOUT_MS,
+            scan_to_index: None,
+            shuffle_outputs: true,
+            announce_origin: None,
+            unit: Unit::default(),
+            network: Network::default(),
+        }
+    }
+}
+
+/// WhatsOnChain's endpoint for `network`.
+pub fn default_endpoint(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => DEFAULT_ENDPOINT,
+        Network::Testnet => TESTNET_ENDPOINT,
+    }
+}
+
+/// Whether `origin` is a scheme and host only, which is all a tab match
+/// pattern can be built from.
+fn is_https_origin(origin: &str) -> bool {
+    origin.strip_prefix("https://").is_some_and(|host| {
+        !host.is_empty()
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    })
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !GAP_LIMITS.contains(&self.gap_limit) {
+            return Err(SettingsError::GapLimit(self.gap_limit));
+        }
+        if !FEE_RATES.contains(&self.fee_rate) {
+            return Err(SettingsError::FeeRate(self.fee_rate));
+        }
+        if !self.endpoint.starts_with("https://") {
+            return Err(SettingsError::Endpoint(self.endpoint.clone()));
+        }
+        // Only WhatsOnChain's endpoints are known to be for one network
+        let other_network = Network::ALL.into_iter().find(|&n| n != self.network);
+        if other_network.is_some_and(|n| self.endpoint.trim_end_matches('/') == default_endpoint(n))
+        {
+            return Err(SettingsError::EndpointNetwork(
+                self.endpoint.clone(),
+                self.network,
+            ));
+        }
+        if !SYNC_INTERVALS_MS.contains(&self.sync_interval_ms) {
+            return Err(SettingsError::SyncInterval(self.sync_interval_ms));
+        }
+        if !REQUEST_TIMEOUTS_MS.contains(&self.request_timeout_ms) {
+            return Err(SettingsError::RequestTimeout(self.request_timeout_ms));
+        }
+        if let Some(index) = self.scan_to_index.filter(|&i| i > MAX_SCAN_TO_INDEX) {
+            return Err(SettingsError::ScanToIndex(index));
+        }
+        if let Some(origin) = self
+            .announce_origin
+            .as_ref()
+            .filter(|o| !is_https_origin(o))
+        {
+            return Err(SettingsError::AnnounceOrigin(origin.clone()));
+        }
+        Ok(())
+    }
+
+    /// `path` appended to the configured endpoint.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.endpoint.trim_end_matches('/'))
+    }
+
+    pub async fn load() -> Result<Self> {
+        Ok(Self::from_stored(
+            storage::load(StorageKey::Settings).await?,
+        ))
+    }
+
+    /// Stored settings that no longer validate, e.g. after a range was
+    /// narrowed, are replaced by the defaults.
+    fn from_stored(stored: Option<Settings>) -> Self {
+        stored
+            .filter(|settings| settings.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        self.validate()?;
+        storage::save(StorageKey::Settings, self).await
+    }
+}
+
+/// Settings as exported for moving them to another device. It never holds
+/// keys, the seed is backed up separately.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WalletConfig {
+    format: String,
+    network: Network,
+    settings: Settings,
+}
+
+impl WalletConfig {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            format: CONFIG_FORMAT.to_owned(),
+            network: settings.network,
+            settings,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Restored settings are validated like ones entered in the form, and
+    /// have to be for the `network` the wallet is on.
+    pub fn from_json(json: &str, network: Network) -> Result<Settings> {
+        let config: WalletConfig = serde_json::from_str(json)?;
+        if config.format != CONFIG_FORMAT {
+            return Err(SettingsError::NotAConfig.into());
+        }
+        if config.network != network || config.settings.network != network {
+            return Err(SettingsError::WrongNetwork {
+                found: config.network,
+                expected: network,
+            }
+            .into());
+        }
+        config.settings.validate()?;
+        Ok(config.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_checks_ranges() {
+        assert!(Settings::default().validate().is_ok());
+
+        let settings = |gap_limit| Settings {
+            gap_limit,
+            ..Settings::default()
+        };
+        assert!(settings(1).validate().is_ok());
+        assert!(settings(100).validate().is_ok());
+        assert!(matches!(
+            settings(0).validate(),
+            Err(SettingsError::GapLimit(0))
+        ));
+        assert!(matches!(
+            settings(101).validate(),
+            Err(SettingsError::GapLimit(101))
+        ));
+
+        let insecure = Settings {
+            endpoint: "http://api.whatsonchain.com/v1/bsv/main".to_owned(),
+            ..Settings::default()
+        };
+        assert!(matches!(
+            insecure.validate(),
+            Err(SettingsError::Endpoint(_))
+        ));
+
+        let testnet = Settings {
+            network: Network::Testnet,
+            ..Settings::default()
+        };
+        assert!(matches!(
+            testnet.validate(),
+            Err(SettingsError::EndpointNetwork(_, Network::Testnet))
+        ));
+        let testnet = Settings {
+            endpoint: default_endpoint(Network::Testnet).to_owned(),
+            ..testnet
+        };
+        assert!(testnet.validate().is_ok());
+
+        let far = Settings {
+            scan_to_index: Some(MAX_SCAN_TO_INDEX + 1),
+            ..Settings::default()
+        };
+        assert!(matches!(far.validate(), Err(SettingsError::ScanToIndex(_))));
+
+        let impatient = Settings {
+            request_timeout_ms: 0,
+            ..Settings::default()
+        };
+        assert!(matches!(
+            impatient.validate(),
+            Err(SettingsError::RequestTimeout(0))
+        ));
+
+        for origin in [
+            "http://shop.example",
+            "https://",
+            "https://shop.example/checkout",
+            "https://*.example",
+            "https://shop.example:8443",
+        ] {
+            let announcing = Settings {
+                announce_origin: Some(origin.to_owned()),
+                ..Settings::default()
+            };
+            assert!(
+                matches!(announcing.validate(), Err(SettingsError::AnnounceOrigin(_))),
+                "{origin}"
+            );
+        }
+        let announcing = Settings {
+            announce_origin: Some("https://shop.example".to_owned()),
+            ..Settings::default()
+        };
+        assert!(announcing.validate().is_ok());
+    }
+
+    #[test]
+    fn stored_settings_fill_defaults() -> Result<()> {
+        let stored: Settings = serde_json::from_str(r#"{"gap_limit": 50}"#)?;
+        assert_eq!(
+            Settings {
+                gap_limit: 50,
+                ..Settings::default()
+            },
+            Settings::from_stored(Some(stored.clone()))
+        );
+
+        let json = serde_json::to_string(&stored)?;
+        assert_eq!(stored, serde_json::from_str(&json)?);
+
+        let invalid = Settings {
+            fee_rate: 0,
+            ..Settings::default()
+        };
+        assert_eq!(Settings::default(), Settings::from_stored(Some(invalid)));
+        assert_eq!(Settings::default(), Settings::from_stored(None));
+        Ok(())
+    }
+
+    #[test]
+    fn config_round_trips() -> Result<()> {
+        let settings = Settings {
+            gap_limit: 40,
+            endpoint: "https://example.com/api".to_owned(),
+            scan_to_index: Some(500),
+            unit: Unit::Satoshis,
+            ..Settings::default()
+        };
+        let json = WalletConfig::new(settings.clone()).to_json()?;
+
+        assert_eq!(
+            WalletConfig::new(settings.clone()),
+            serde_json::from_str(&json)?
+        );
+        assert_eq!(settings, WalletConfig::from_json(&json, Network::Mainnet)?);
+
+        let testnet = json.replace("mainnet", "testnet");
+        let error = WalletConfig::from_json(&testnet, Network::Mainnet).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SettingsError::WrongNetwork { .. })
+        ));
+        assert_eq!(
+            Network::Testnet,
+            WalletConfig::from_json(&testnet, Network::Testnet)?.network
+        );
+        let foreign = json.replace(CONFIG_FORMAT, "other");
+        let error = WalletConfig::from_json(&foreign, Network::Mainnet).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SettingsError::NotAConfig)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn url_joins_endpoint() {
+        let settings = Settings {
+            endpoint: "https://example.com/api/".to_owned(),
+            ..Settings::default()
+        };
+
+        assert_eq!("https://example.com/api/tx/raw", settings.url("tx/raw"));
+    }
+}
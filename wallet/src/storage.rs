@@ -0,0 +1,254 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::util;
+
+const NAMESPACE: &str = "beesv";
+/// Version of the stored layout, bumped along with a step in `migrate_layout`.
+const SCHEMA_VERSION: u64 = 2;
+
+#[derive(Debug, Error)]
+enum StorageError {
+    #[error("Stored data has unsupported schema version {0}")]
+    UnsupportedVersion(u64),
+    #[error("Invalid schema version: {0}")]
+    InvalidVersion(Value),
+    #[error("Storage is read only")]
+    ReadOnly,
+}
+
+thread_local! {
+    static READ_ONLY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes every later write fail without touching the stored data, for
+/// sessions that must leave no trace such as the demo.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.with(|flag| flag.set(read_only));
+}
+
+fn check_writable() -> Result<(), StorageError> {
+    if READ_ONLY.with(Cell::get) {
+        return Err(StorageError::ReadOnly);
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+pub enum StorageKey {
+    Xprv,
+    EncryptedXprv,
+    Settings,
+}
+
+impl StorageKey {
+    const ALL: [StorageKey; 3] = [
+        StorageKey::Xprv,
+        StorageKey::EncryptedXprv,
+        StorageKey::Settings,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            StorageKey::Xprv => "xprv",
+            StorageKey::EncryptedXprv => "encrypted_xprv",
+            StorageKey::Settings => "settings",
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{NAMESPACE}.{}", self.name())
+    }
+}
+
+fn version_key() -> String {
+    format!("{NAMESPACE}.schema_version")
+}
+
+pub async fn load<T: DeserializeOwned>(key: StorageKey) -> Result<Option<T>> {
+    util::store_load(&key.key()).await
+}
+
+pub async fn save<T: Serialize + ?Sized>(key: StorageKey, value: &T) -> Result<()> {
+    check_writable()?;
+    util::store_save(&key.key(), value).await
+}
+
+pub async fn remove(key: StorageKey) -> Result<()> {
+    check_writable()?;
+    util::store_remove(&key.key()).await
+}
+
+/// Upgrades data stored by older versions to the current layout.
+pub async fn migrate() -> Result<()> {
+    let mut keys = vec![version_key()];
+    for key in StorageKey::ALL {
+        keys.push(key.name().to_owned());
+        keys.push(key.key());
+    }
+
+    let mut stored = HashMap::new();
+    for key in keys {
+        if let Some(value) = util::store_load::<Value>(&key).await? {
+            stored.insert(key, value);
+        }
+    }
+
+    let mut migrated = stored.clone();
+    migrate_layout(&mut migrated)?;
+    if migrated != stored {
+        check_writable()?;
+    }
+
+    for step in migration_steps(&stored, &migrated) {
+        match step {
+            MigrationStep::Save(key, value) => util::store_save(key, value).await?,
+            MigrationStep::Remove(key) => util::store_remove(key).await?,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum MigrationStep<'a> {
+    Save(&'a str, &'a Value),
+    Remove(&'a str),
+}
+
+/// The writes turning `stored` into `migrated`. Everything is written before
+/// removing anything so no data is lost if this is interrupted, and the
+/// version goes last so the next run picks up where it stopped, legacy keys
+/// included.
+fn migration_steps<'a>(
+    stored: &'a HashMap<String, Value>,
+    migrated: &'a HashMap<String, Value>,
+) -> Vec<MigrationStep<'a>> {
+    let version_key = version_key();
+    let mut steps: Vec<_> = migrated
+        .iter()
+        .filter(|(key, value)| **key != version_key && stored.get(*key) != Some(value))
+        .map(|(key, value)| MigrationStep::Save(key, value))
+        .collect();
+    steps.extend(
+        stored
+            .keys()
+            .filter(|key| !migrated.contains_key(*key))
+            .map(|key| MigrationStep::Remove(key)),
+    );
+    if let Some((key, value)) = migrated.get_key_value(&version_key) {
+        if stored.get(key) != Some(value) {
+            steps.push(MigrationStep::Save(key, value));
+        }
+    }
+    steps
+}
+
+fn migrate_layout(layout: &mut HashMap<String, Value>) -> Result<()> {
+    // Version 1 predates the version key
+    let version = match layout.get(&version_key()) {
+        None => 1,
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| StorageError::InvalidVersion(value.clone()))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedVersion(version).into());
+    }
+
+    if version < 2 {
+        for key in StorageKey::ALL {
+            if let Some(value) = layout.remove(key.name()) {
+                layout.insert(key.key(), value);
+            }
+        }
+    }
+
+    layout.insert(version_key(), SCHEMA_VERSION.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_layout_namespaces_v1_keys() -> Result<()> {
+        let mut layout = HashMap::from([
+            ("xprv".to_owned(), Value::from("xprv9s21Zr")),
+            ("unrelated".to_owned(), Value::from(1)),
+        ]);
+
+        migrate_layout(&mut layout)?;
+
+        assert_eq!(
+            HashMap::from([
+                ("beesv.xprv".to_owned(), Value::from("xprv9s21Zr")),
+                ("beesv.schema_version".to_owned(), Value::from(2)),
+                ("unrelated".to_owned(), Value::from(1)),
+            ]),
+            layout
+        );
+
+        let migrated = layout.clone();
+        migrate_layout(&mut layout)?;
+        assert_eq!(migrated, layout);
+        Ok(())
+    }
+
+    #[test]
+    fn migration_writes_version_last() -> Result<()> {
+        let stored = HashMap::from([
+            ("xprv".to_owned(), Value::from("xprv9s21Zr")),
+            ("settings".to_owned(), Value::from("{}")),
+        ]);
+        let mut migrated = stored.clone();
+        migrate_layout(&mut migrated)?;
+
+        let steps = migration_steps(&stored, &migrated);
+
+        let version = Value::from(SCHEMA_VERSION);
+        assert_eq!(
+            Some(&MigrationStep::Save("beesv.schema_version", &version)),
+            steps.last()
+        );
+        let first_remove = steps
+            .iter()
+            .position(|step| matches!(step, MigrationStep::Remove(_)))
+            .unwrap();
+        assert!(steps[first_remove..steps.len() - 1]
+            .iter()
+            .all(|step| matches!(step, MigrationStep::Remove(_))));
+        assert!(steps.contains(&MigrationStep::Remove("xprv")));
+        assert_eq!(5, steps.len());
+
+        assert!(migration_steps(&migrated, &migrated).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_storage_rejects_writes() {
+        set_read_only(true);
+        let saved = futures::executor::block_on(save(StorageKey::Settings, "{}"));
+        let removed = futures::executor::block_on(remove(StorageKey::Xprv));
+        set_read_only(false);
+
+        for result in [saved, removed] {
+            assert!(matches!(
+                result.unwrap_err().downcast::<StorageError>(),
+                Ok(StorageError::ReadOnly)
+            ));
+        }
+    }
+
+    #[test]
+    fn migrate_layout_rejects_newer_versions() {
+        let mut layout = HashMap::from([(version_key(), Value::from(SCHEMA_VERSION + 1))]);
+
+        assert!(migrate_layout(&mut layout).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::util::Network;
+
+/// A sync running longer than this is considered hung and may be replaced.
+const SYNC_TIMEOUT_MS: f64 = 30_000.0;
+
+/// Tracks the running sync so that a newer one can supersede it, in which case
+/// the result of the older one is discarded once it arrives.
+#[derive(Default)]
+pub struct SyncTracker {
+    generation: u64,
+    started_at: Option<f64>,
+    /// Network the wallet is on, results synced for another one are dropped.
+    network: Network,
+}
+
+impl SyncTracker {
+    /// Returns the generation of the new sync, or `None` if one is already
+    /// running and neither `force`d nor timed out.
+    pub fn start(&mut self, now: f64, force: bool) -> Option<u64> {
+        if let Some(started_at) = self.started_at {
+            if !force && now - started_at < SYNC_TIMEOUT_MS {
+                return None;
+            }
+        }
+
+        self.generation += 1;
+        self.started_at = Some(now);
+        Some(self.generation)
+    }
+
+    /// Switches to `network`, discarding the result of a sync running for
+    /// the previous one.
+    pub fn set_network(&mut self, network: Network) {
+        if network != self.network {
+            self.network = network;
+            self.generation += 1;
+            self.started_at = None;
+        }
+    }
+
+    /// Marks the sync on `network` as done, returning whether its result is
+    /// still current.
+    pub fn finish(&mut self, generation: u64, network: Network) -> bool {
+        if generation != self.generation || network != self.network {
+            return false;
+        }
+        self.started_at = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_sync_supersedes_hung_one() {
+        let mut tracker = SyncTracker::default();
+
+        let hung = tracker.start(0.0, false).unwrap();
+        assert_eq!(None, tracker.start(1_000.0, false));
+
+        let forced = tracker.start(2_000.0, true).unwrap();
+        assert!(!tracker.finish(hung, Network::Mainnet));
+        assert_eq!(None, tracker.start(3_000.0, false));
+        assert!(tracker.finish(forced, Network::Mainnet));
+        assert!(tracker.start(4_000.0, false).is_some());
+    }
+
+    #[test]
+    fn network_switch_discards_running_sync() {
+        let mut tracker = SyncTracker::default();
+        let mainnet = tracker.start(0.0, false).unwrap();
+
+        tracker.set_network(Network::Testnet);
+
+        assert!(!tracker.finish(mainnet, Network::Mainnet));
+        let testnet = tracker.start(1_000.0, false).unwrap();
+        tracker.set_network(Network::Testnet);
+        // A sync started before the switch reached the tracker
+        assert!(!tracker.finish(testnet, Network::Mainnet));
+        assert!(tracker.finish(testnet, Network::Testnet));
+    }
+
+    #[test]
+    fn sync_times_out() {
+        let mut tracker = SyncTracker::default();
+
+        tracker.start(0.0, false);
+
+        assert_eq!(None, tracker.start(SYNC_TIMEOUT_MS - 1.0, false));
+        assert_eq!(Some(2), tracker.start(SYNC_TIMEOUT_MS, false));
+    }
+}
@@ -1,157 +1,830 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    future::Future,
+    pin::pin,
+};
 
 use anyhow::Result;
+use futures::future::{self, Either};
 use gloo_net::http::Request;
+use gloo_timers::future::TimeoutFuture;
 use secp256k1::{PublicKey, SecretKey};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::{bip32::XPrv, ratelimit::RateLimiter, sending::Transaction, util};
+use crate::{
+    bip32::{WalletKey, XPub},
+    ratelimit::RateLimiter,
+    sending::{self, Transaction, DUST_LIMIT},
+    settings::{Settings, GAP_LIMITS},
+    util::{self, Network, Satoshis},
+};
 
-#[derive(Default)]
+/// Addresses queried per request, also the largest batch the API accepts.
+const ADDRESS_BATCH_SIZE: usize = 20;
+/// Transactions after which a wallet counts as busy, see [`gap_limit_for`].
+const BUSY_WALLET_TRANSACTIONS: usize = 100;
+/// How long a request may take before it is abandoned, unless configured.
+pub const DEFAULT_TIMEOUT_MS: u32 = 15_000;
+
+#[derive(Debug, Error)]
+enum NetworkError {
+    #[error("Request timed out after {0} ms")]
+    Timeout(u32),
+}
+
+#[derive(Debug, Error)]
+enum WalletError {
+    #[error("No chain {0}, only receive (0) and change (1) are scanned")]
+    UnknownChain(u8),
+    #[error("{0} is not an address of this wallet")]
+    NotOwnAddress(String),
+    #[error("Invalid transaction id: {0}")]
+    InvalidTxid(String),
+    #[error("Addresses are derived once the wallet has synced")]
+    NotSynced,
+    #[error("Watch-only wallets can't sign")]
+    WatchOnly,
+}
+
+#[derive(Clone, Default)]
 pub struct WalletState {
     main: FetchingState,
     change: FetchingState,
-    pub balance: u64,
+    pub balance: Satoshis,
     pub unspent_outputs: Vec<RichOutput>,
+    /// Height of the chain tip as of the sync, `None` if it couldn't be
+    /// fetched.
+    pub tip_height: Option<u32>,
+    /// Sent transactions applied locally that no sync has returned yet.
+    pub pending_txids: Vec<String>,
+    /// Network the wallet's addresses are encoded for.
+    pub network: Network,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct RichOutput {
     pub tx_pos: u32,
     pub tx_hash: String,
-    pub amount: u64,
+    pub amount: Satoshis,
     pub address: [u8; 20],
+    /// Block the output was mined in, `None` while it is in the mempool.
+    pub height: Option<u32>,
+    /// Chain and index of the key paying to `address`, `None` for outputs
+    /// not belonging to this wallet.
+    pub key_path: Option<KeyPath>,
+}
+
+/// Where a key of the wallet's account lies, so it can be derived again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPath {
+    /// 0 for receive and 1 for change.
+    pub chain: u8,
+    pub index: u32,
+}
+
+impl Display for KeyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.chain, self.index)
+    }
+}
+
+/// What a send is built from, all taken from one `WalletState`. The send
+/// form keeps using it while syncs replace the state in the background, so
+/// the coins, keys and change address it signs with always belong together.
+#[derive(Clone, Default, PartialEq)]
+pub struct SendSnapshot {
+    pub outputs: Vec<RichOutput>,
+    pub selected: Vec<RichOutput>,
+    pub change_address: String,
+    pub keys: ChainKeys,
+}
+
+/// Keys of both chains, from which the keys spending coins are derived when
+/// signing rather than whenever the coins are shown.
+#[derive(Clone, Default, PartialEq)]
+pub struct ChainKeys {
+    main: Option<WalletKey>,
+    change: Option<WalletKey>,
+}
+
+impl ChainKeys {
+    /// Keys spending `outputs` by address hash.
+    pub fn signing_keys(
+        &self,
+        outputs: &[RichOutput],
+    ) -> HashMap<[u8; 20], (SecretKey, PublicKey)> {
+        outputs
+            .iter()
+            .filter_map(|output| {
+                let key_pair = self.signing_key(output.key_path?).ok()?;
+                Some((output.address, key_pair))
+            })
+            .collect()
+    }
+
+    /// The key at `key_path`.
+    pub fn signing_key(&self, key_path: KeyPath) -> Result<(SecretKey, PublicKey)> {
+        let chain_key = match key_path.chain {
+            0 => &self.main,
+            1 => &self.change,
+            chain => return Err(WalletError::UnknownChain(chain).into()),
+        };
+        let chain_xprv = chain_key
+            .as_ref()
+            .ok_or(WalletError::NotSynced)?
+            .private()
+            .ok_or(WalletError::WatchOnly)?;
+        Ok(chain_xprv.derive_normal(key_path.index)?.to_keypair())
+    }
+}
+
+impl SendSnapshot {
+    /// Whether every coin `transaction` spends is still unspent in `current`,
+    /// i.e. no sync since the snapshot was taken has seen any of them spent.
+    pub fn is_current(&self, transaction: &Transaction, current: &[RichOutput]) -> bool {
+        let unspent: HashSet<_> = current
+            .iter()
+            .filter_map(|o| Some((hex::decode(&o.tx_hash).ok()?, o.tx_pos)))
+            .collect();
+        transaction
+            .outpoints()
+            .iter()
+            .all(|outpoint| unspent.contains(outpoint))
+    }
+}
+
+/// How far a sync scanned one chain.
+#[derive(Debug, PartialEq)]
+pub struct GapReport {
+    pub highest_used: Option<u32>,
+    pub next_unused: u32,
+    /// Addresses scanned before the gap limit ended the scan.
+    pub scanned: u32,
+}
+
+impl RichOutput {
+    pub fn is_spendable(&self) -> bool {
+        self.amount >= DUST_LIMIT
+    }
+
+    /// Whether both are the same coin, regardless of its confirmation state.
+    pub fn same_outpoint(&self, other: &RichOutput) -> bool {
+        self.tx_hash == other.tx_hash && self.tx_pos == other.tx_pos
+    }
+
+    pub fn confirmations(&self, tip_height: Option<u32>) -> Option<u32> {
+        confirmations(self.height, tip_height)
+    }
+
+    /// Whether the output has at least `min_confirmations`, a mined one
+    /// counting as one confirmation while the tip height is unknown.
+    pub fn has_confirmations(&self, tip_height: Option<u32>, min_confirmations: u32) -> bool {
+        self.confirmations(tip_height).unwrap_or(1) >= min_confirmations
+    }
+}
+
+/// Blocks mined on top of and including `height`, zero for the mempool and
+/// unknown for a mined transaction while the tip is.
+fn confirmations(height: Option<u32>, tip_height: Option<u32>) -> Option<u32> {
+    match height {
+        Some(height) => tip_height.map(|tip| (tip + 1).saturating_sub(height)),
+        None => Some(0),
+    }
 }
 
 impl WalletState {
+    /// The state of the wallet `key` before its first sync, knowing its keys
+    /// but none of its coins, so coins can be imported and spent offline.
+    pub fn new(key: &WalletKey, network: Network) -> Result<Self> {
+        let chain = |chain| -> Result<FetchingState> {
+            Ok(FetchingState {
+                key: Some(key.derive_normal(chain)?),
+                ..Default::default()
+            })
+        };
+        Ok(Self {
+            main: chain(0)?,
+            change: chain(1)?,
+            network,
+            ..Default::default()
+        })
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.unspent_outputs.len()
+    }
+
+    /// Outputs above the dust limit with at least `min_confirmations`.
+    pub fn spendable_output_count(&self, min_confirmations: u32) -> usize {
+        self.unspent_outputs
+            .iter()
+            .filter(|o| o.is_spendable() && o.has_confirmations(self.tip_height, min_confirmations))
+            .count()
+    }
+
+    /// Balance held on the receive chain.
+    pub fn main_balance(&self) -> Satoshis {
+        self.chain_balance(&self.main)
+    }
+
+    /// Balance held on the change chain.
+    pub fn change_balance(&self) -> Satoshis {
+        self.chain_balance(&self.change)
+    }
+
+    fn chain_balance(&self, chain: &FetchingState) -> Satoshis {
+        self.unspent_outputs
+            .iter()
+            .filter(|o| chain.lookup.contains_key(&o.address))
+            .map(|o| o.amount)
+            .sum()
+    }
+
+    /// The receive address at `index`, derived without scanning.
+    pub fn receive_address_at(&self, index: u32) -> Result<String> {
+        Ok(self
+            .main
+            .chain_key()?
+            .derive_normal(index)?
+            .derive_public()
+            .to_address(self.network))
+    }
+
+    /// Hex of the receive public key at `index`, e.g. for multisig setups.
+    pub fn receive_key_at(&self, index: u32) -> Result<String> {
+        Ok(self
+            .main
+            .chain_key()?
+            .derive_normal(index)?
+            .derive_public()
+            .public_key_hex())
+    }
+
+    /// The change address at `index`, derived without scanning.
+    pub fn change_address_at(&self, index: u32) -> Result<String> {
+        Ok(self
+            .change
+            .chain_key()?
+            .derive_normal(index)?
+            .derive_public()
+            .to_address(self.network))
+    }
+
+    /// `count` receive addresses with their indices, starting `skip` past the
+    /// first unused one so addresses shown before aren't handed out again.
+    /// None lie `gap_limit` or more past it, a scan would stop before them
+    /// and never find what they received.
+    pub fn unused_receive_addresses(
+        &self,
+        skip: u32,
+        count: u32,
+        gap_limit: u32,
+    ) -> Result<Vec<(u32, String)>> {
+        // Which addresses are unused is only known after a sync
+        if self.main.next_address.is_empty() {
+            return Err(WalletError::NotSynced.into());
+        }
+        let start = self.main.last_index + skip.min(gap_limit);
+        let end = self.main.last_index + (skip + count).min(gap_limit);
+        (start..end)
+            .map(|index| Ok((index, self.receive_address_at(index)?)))
+            .collect()
+    }
+
+    /// Distinct transactions touching either chain as of the last sync.
+    pub fn transaction_count(&self) -> usize {
+        self.main
+            .transactions
+            .iter()
+            .chain(&self.change.transactions)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Distinct transactions touching either chain as of the last sync with
+    /// their confirmations, the most recent first.
+    pub fn history(&self) -> Vec<(String, Option<u32>)> {
+        let heights: HashMap<_, _> = self
+            .main
+            .heights
+            .iter()
+            .chain(&self.change.heights)
+            .collect();
+        let mut history: Vec<_> = self
+            .main
+            .transactions
+            .iter()
+            .chain(&self.change.transactions)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|txid| (txid, heights.get(txid).map(|&&height| height)))
+            .collect();
+        // Unconfirmed transactions have no height and are the most recent
+        history.sort_by_key(|&(txid, height)| (Reverse(height.unwrap_or(u32::MAX)), txid));
+        history
+            .into_iter()
+            .map(|(txid, height)| (txid.clone(), confirmations(height, self.tip_height)))
+            .collect()
+    }
+
+    /// A synced wallet that never received anything, nothing can be done
+    /// with it before funding it.
+    pub fn needs_funding(&self) -> bool {
+        !self.main.next_address.is_empty()
+            && self.main.transactions.is_empty()
+            && self.change.transactions.is_empty()
+            && self.unspent_outputs.is_empty()
+            && self.pending_txids.is_empty()
+    }
+
+    /// The first receive address after the last used one, empty until synced.
+    pub fn receive_address(&self) -> String {
+        self.main.next_address.clone()
+    }
+
     pub fn change_address(&self) -> String {
         self.change.next_address.clone()
     }
 
-    pub fn address_keys(&self) -> HashMap<[u8; 20], (SecretKey, PublicKey)> {
-        let mut keys = HashMap::new();
-        keys.extend(self.main.lookup.clone());
-        keys.extend(self.change.lookup.clone());
-        keys
+    /// Scan results of the receive and change chain.
+    pub fn gap_report(&self) -> [(&'static str, GapReport); 2] {
+        [("Receive", &self.main), ("Change", &self.change)].map(|(name, chain)| {
+            let report = GapReport {
+                highest_used: chain.last_index.checked_sub(1),
+                next_unused: chain.last_index,
+                scanned: chain.scanned(),
+            };
+            (name, report)
+        })
+    }
+
+    pub fn send_snapshot(&self, selected: &[RichOutput]) -> SendSnapshot {
+        SendSnapshot {
+            outputs: self.unspent_outputs.clone(),
+            selected: selected.to_vec(),
+            change_address: self.change_address(),
+            keys: self.chain_keys(),
+        }
+    }
+
+    /// Keys of both chains, for deriving signing keys later.
+    pub fn chain_keys(&self) -> ChainKeys {
+        ChainKeys {
+            main: self.main.key.clone(),
+            change: self.change.key.clone(),
+        }
+    }
+
+    /// Keys spending `outputs` by address hash, derived only when needed as
+    /// scanning doesn't derive private keys.
+    pub fn signing_keys(
+        &self,
+        outputs: &[RichOutput],
+    ) -> HashMap<[u8; 20], (SecretKey, PublicKey)> {
+        self.chain_keys().signing_keys(outputs)
+    }
+
+    /// Keys of the scanned addresses among `hashes`, e.g. this wallet's share
+    /// of the keys in a multisig script.
+    pub fn keys_for_hashes(
+        &self,
+        hashes: &[[u8; 20]],
+    ) -> HashMap<[u8; 20], (SecretKey, PublicKey)> {
+        hashes
+            .iter()
+            .filter_map(|hash| {
+                let key_pair = self.signing_key(self.key_path(hash)?).ok()?;
+                Some((*hash, key_pair))
+            })
+            .collect()
+    }
+
+    /// The key at `key_path`.
+    pub fn signing_key(&self, key_path: KeyPath) -> Result<(SecretKey, PublicKey)> {
+        self.chain_keys().signing_key(key_path)
+    }
+
+    /// Chain and index of the scanned address hashing to `hash`.
+    fn key_path(&self, hash: &[u8; 20]) -> Option<KeyPath> {
+        key_path(&self.main, &self.change, hash)
+    }
+
+    /// Whether `hash` is the address of a scanned change key, e.g. to tell the
+    /// change of a sent transaction apart from the payment.
+    pub fn is_change(&self, hash: &[u8; 20]) -> bool {
+        self.key_path(hash).is_some_and(|path| path.chain == 1)
+    }
+
+    /// Whether `address` is one of the scanned addresses of either chain.
+    pub fn is_own_address(&self, address: &str) -> bool {
+        util::address_bytes(address).is_ok_and(|hash| self.key_path(&hash).is_some())
+    }
+
+    /// The chain name and index of a scanned `address`.
+    pub fn locate_address(&self, address: &str) -> Option<(&'static str, u32)> {
+        let path = self.key_path(&util::address_bytes(address).ok()?)?;
+        Some((
+            if path.chain == 0 { "receive" } else { "change" },
+            path.index,
+        ))
+    }
+
+    /// Whether the spendable coins cover sending `amount` at `fee_rate`.
+    pub fn can_afford(&self, amount: u64, fee_rate: u64) -> bool {
+        sending::can_fund(&self.unspent_outputs, Satoshis(amount), fee_rate)
+    }
+
+    /// What can be sent in one transaction at `fee_rate`, lower than the
+    /// balance by the fee and by dust coins not worth spending.
+    pub fn max_sendable(&self, fee_rate: u64) -> Satoshis {
+        sending::max_sendable(&self.unspent_outputs, fee_rate)
+    }
+
+    /// Drops the coins a sent transaction created, once it was replaced by
+    /// another spending the same coins.
+    pub fn forget_sent(&mut self, txid: &str) {
+        self.unspent_outputs.retain(|output| output.tx_hash != txid);
+        self.pending_txids.retain(|pending| pending != txid);
+        self.balance = self.unspent_outputs.iter().map(|o| o.amount).sum();
+    }
+
+    /// Reflects a just broadcast transaction without waiting for a sync: the
+    /// coins it spends are dropped and its outputs paying this wallet are
+    /// added. The next sync replaces the result with the network's view.
+    pub fn apply_sent(&mut self, transaction: &Transaction) {
+        let spent: HashSet<_> = transaction.outpoints().into_iter().collect();
+        self.unspent_outputs.retain(|output| {
+            hex::decode(&output.tx_hash)
+                .map_or(true, |tx_hash| !spent.contains(&(tx_hash, output.tx_pos)))
+        });
+
+        let txid = transaction.txid();
+        for (index, output) in transaction.outputs().iter().enumerate() {
+            let Ok(address) = output.address() else {
+                continue;
+            };
+            if let Some(key_path) = self.key_path(&address) {
+                self.unspent_outputs.push(RichOutput {
+                    tx_pos: index as u32,
+                    tx_hash: txid.clone(),
+                    amount: output.amount(),
+                    address,
+                    height: None,
+                    key_path: Some(key_path),
+                });
+            }
+        }
+
+        self.balance = self.unspent_outputs.iter().map(|o| o.amount).sum();
+        self.pending_txids.push(txid);
+    }
+
+    /// Fetches the coins of one scanned `address`, quicker than a full sync
+    /// when checking for a specific payment.
+    pub async fn refresh_address(
+        &mut self,
+        address: &str,
+        settings: &Settings,
+        timeout_ms: u32,
+    ) -> Result<()> {
+        if !self.is_own_address(address) {
+            return Err(WalletError::NotOwnAddress(address.to_owned()).into());
+        }
+        let utxos = fetch_unspent_outputs(&[address.to_owned()], settings, timeout_ms).await?;
+        self.merge_unspent(utxos)
+    }
+
+    /// Adds coins given as a JSON list of `txid`, `vout`, `amount` and
+    /// `address`, so a send can be built offline. Every coin has to pay to an
+    /// address a sync with `settings` would scan, which are derived here when
+    /// no sync scanned them yet. Returns how many coins weren't known yet.
+    pub fn import_utxos(&mut self, json: &str, settings: &Settings) -> Result<usize> {
+        let imported: Vec<ImportedUtxo> = serde_json::from_str(json)?;
+        let derived = [
+            self.main.derive_lookup(settings)?,
+            self.change.derive_lookup(settings)?,
+        ];
+        let mut outputs: Vec<RichOutput> = vec![];
+        for utxo in imported {
+            let address = util::address_bytes(&utxo.address)?;
+            let key_path = self
+                .key_path(&address)
+                .or_else(|| {
+                    (0..).zip(&derived).find_map(|(chain, lookup)| {
+                        lookup.get(&address).map(|&index| KeyPath { chain, index })
+                    })
+                })
+                .ok_or_else(|| WalletError::NotOwnAddress(utxo.address.clone()))?;
+            if hex::decode(&utxo.txid).map_or(true, |hash| hash.len() != 32) {
+                return Err(WalletError::InvalidTxid(utxo.txid).into());
+            }
+            let output = RichOutput {
+                tx_pos: utxo.vout,
+                tx_hash: utxo.txid,
+                amount: utxo.amount,
+                address,
+                height: None,
+                key_path: Some(key_path),
+            };
+            let known = self.unspent_outputs.iter().chain(&outputs);
+            if !known.into_iter().any(|o| o.same_outpoint(&output)) {
+                outputs.push(output);
+            }
+        }
+
+        let count = outputs.len();
+        self.unspent_outputs.extend(outputs);
+        self.balance = self.unspent_outputs.iter().map(|o| o.amount).sum();
+        Ok(count)
+    }
+
+    /// Replaces the coins of each responding address with those it reports.
+    fn merge_unspent(&mut self, responses: Vec<UtxoResponse>) -> Result<()> {
+        let refreshed = responses
+            .iter()
+            .map(|r| util::address_bytes(&r.address))
+            .collect::<Result<HashSet<_>>>()?;
+        let fetched = collect_unspent(responses, &self.main, &self.change)?;
+        self.unspent_outputs
+            .retain(|output| !refreshed.contains(&output.address));
+        self.unspent_outputs.extend(fetched);
+        self.balance = self.unspent_outputs.iter().map(|o| o.amount).sum();
+        Ok(())
     }
 }
 
-pub async fn fetch_for_address(xprv: &XPrv, rate_limiter: &mut RateLimiter) -> Result<WalletState> {
-    let xprv_main = xprv.derive(0);
-    let xprv_change = xprv.derive(1);
+/// Gap limit to scan with given how many transactions the wallet had. Busy
+/// wallets are more likely to have handed out addresses that weren't paid
+/// yet, so they are scanned twice as far past the last used address, but
+/// never further than the highest gap limit the settings allow.
+pub fn gap_limit_for(gap_limit: usize, transaction_count: usize) -> usize {
+    if transaction_count >= BUSY_WALLET_TRANSACTIONS {
+        (gap_limit * 2).min(*GAP_LIMITS.end())
+    } else {
+        gap_limit
+    }
+}
 
-    let main = fetch_used_data(xprv_main, rate_limiter).await?;
-    let change = fetch_used_data(xprv_change, rate_limiter).await?;
+pub async fn fetch_for_address(
+    key: &WalletKey,
+    rate_limiter: &RateLimiter,
+    settings: &Settings,
+    timeout_ms: u32,
+    progress: &dyn Fn(usize),
+) -> Result<WalletState> {
+    let main = fetch_used_data(key, 0, rate_limiter, settings, timeout_ms, progress).await?;
+    // Progress counts the addresses of both chains
+    let receive_scanned = main.scanned();
+    let change_progress = |count| progress(receive_scanned as usize + count);
+    let change =
+        fetch_used_data(key, 1, rate_limiter, settings, timeout_ms, &change_progress).await?;
 
     let active_addresses: Vec<_> = main
-        .addresses()
-        .iter()
-        .cloned()
-        .chain(change.addresses().iter().cloned())
+        .addresses(settings.network)
+        .into_iter()
+        .chain(change.addresses(settings.network))
         .collect();
 
-    let mut balance = 0u64;
+    rate_limiter.take().await;
+    // Only needed to count confirmations, the sync goes on without it
+    let tip_height = fetch_chain_info(settings, timeout_ms)
+        .await
+        .ok()
+        .map(|info| info.blocks);
+
     let mut unspent_outputs = vec![];
-    for chunk in active_addresses.chunks(20) {
+    for chunk in active_addresses.chunks(ADDRESS_BATCH_SIZE) {
         rate_limiter.take().await;
-        let utxos = fetch_unspent_outputs(chunk).await?;
-        balance += utxos
-            .iter()
-            .flat_map(|r| r.unspent.iter())
-            .map(|o| o.value)
-            .sum::<u64>();
-        let rich_outputs: Result<Vec<_>> = utxos
-            .into_iter()
-            .flat_map(|r| r.unspent.into_iter().map(move |u| (r.address.clone(), u)))
-            .map(|(address, unspent)| {
-                Ok(RichOutput {
-                    tx_pos: unspent.tx_pos,
-                    tx_hash: unspent.tx_hash,
-                    amount: unspent.value,
-                    address: util::address_bytes(&address)?,
-                })
-            })
-            .collect();
-
-        unspent_outputs.extend(rich_outputs?);
+        let utxos = fetch_unspent_outputs(chunk, settings, timeout_ms).await?;
+        unspent_outputs.extend(collect_unspent(utxos, &main, &change)?);
     }
+    // The history scan ran earlier and may list coins spent since, only the
+    // UTXO pass counts towards the balance
+    let balance = unspent_outputs.iter().map(|o| o.amount).sum();
 
     Ok(WalletState {
         main,
         change,
         balance,
         unspent_outputs,
+        tip_height,
+        pending_txids: vec![],
+        network: settings.network,
     })
 }
 
+#[derive(Clone, Default)]
 struct FetchingState {
-    xprv: XPrv,
+    /// Key of the chain, `None` until the first sync derives it.
+    key: Option<WalletKey>,
     last_index: u32,
-    lookup: HashMap<[u8; 20], (SecretKey, PublicKey)>,
+    /// Index of every scanned key, under the hashes of both its encodings.
+    lookup: HashMap<[u8; 20], u32>,
     transactions: Vec<String>,
+    /// Block heights of the confirmed `transactions`.
+    heights: HashMap<String, u32>,
     next_address: String,
 }
 
 impl FetchingState {
-    fn addresses(&self) -> Vec<String> {
-        self.lookup.keys().cloned().map(util::to_address).collect()
+    fn chain_key(&self) -> Result<&WalletKey> {
+        Ok(self.key.as_ref().ok_or(WalletError::NotSynced)?)
     }
-}
 
-impl Default for FetchingState {
-    fn default() -> Self {
-        Self {
-            xprv: XPrv::empty(),
-            last_index: 0,
-            lookup: HashMap::new(),
-            transactions: vec![],
-            next_address: String::default(),
-        }
+    /// Lookup of the addresses a sync with `settings` would scan at least,
+    /// derived offline up to the gap limit past the last used address or
+    /// `scan_to_index`, whichever is further.
+    fn derive_lookup(&self, settings: &Settings) -> Result<HashMap<[u8; 20], u32>> {
+        let count = settings
+            .scan_to_index
+            .map_or(0, |index| index + 1)
+            .max(self.last_index + settings.gap_limit as u32);
+        let chain_xpub = self.chain_key()?.derive_public();
+        let keys = (0..count)
+            .map(|index| chain_xpub.derive(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(address_batch(&keys, 0, settings.network)
+            .1
+            .into_iter()
+            .collect())
+    }
+
+    fn addresses(&self, network: Network) -> Vec<String> {
+        self.lookup
+            .keys()
+            .map(|&hash| util::to_address(hash, network))
+            .collect()
+    }
+
+    fn scanned(&self) -> u32 {
+        // Each scanned key is in the lookup under both of its addresses
+        (self.lookup.len() / 2) as u32
     }
 }
 
-async fn fetch_used_data(xprv: XPrv, rate_limiter: &mut RateLimiter) -> Result<FetchingState> {
-    let mut last_index: u32 = 0;
-    let mut transactions = vec![];
+async fn fetch_used_data(
+    key: &WalletKey,
+    chain: u32,
+    rate_limiter: &RateLimiter,
+    settings: &Settings,
+    timeout_ms: u32,
+    progress: &dyn Fn(usize),
+) -> Result<FetchingState> {
+    let mut keys = key.derive_public().key_iter(chain);
+    let mut scan = ChainScan::default();
+    // Built from the keys derived for the scan rather than deriving them again
     let mut lookup = HashMap::new();
-    let next_address: String;
-    loop {
+    while !scan.is_done(settings.gap_limit, settings.scan_to_index) {
         rate_limiter.take().await;
-        let addresses_lookup: HashMap<_, _> = (last_index..last_index + 20)
-            .map(|i| {
-                let key = xprv.derive(i);
-                let key_pair = key.to_keypair();
-                (key.derive_public().to_address(), key_pair)
-            })
-            .collect();
-        let addresses: Vec<_> = addresses_lookup.keys().cloned().collect();
-        let address_lookup: Result<HashMap<_, _>> = addresses_lookup
-            .into_iter()
-            .map(|(address, keys)| Ok((util::address_bytes(&address)?, keys)))
-            .collect();
-        lookup.extend(address_lookup?);
-        let history = fetch_transactions_for_addresses(&addresses).await?;
-        history
+        let batch = keys
+            .by_ref()
+            .take(ADDRESS_BATCH_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (batch, entries) = address_batch(&batch, scan.addresses.len(), settings.network);
+        lookup.extend(entries);
+        let history =
+            fetch_transactions_for_addresses(&batch, rate_limiter, settings, timeout_ms).await?;
+        scan.add_batch(batch, &history);
+        progress(scan.addresses.len());
+    }
+
+    scan.into_state(key, chain, lookup, settings.network)
+}
+
+/// Addresses of `keys`, the first of which is at `first_index`, along with
+/// their lookup entries. Funds may have been received at either the
+/// compressed or the uncompressed key's address, so both hashes map to the
+/// same index. The compressed key is hashed once for its address and entry.
+fn address_batch(
+    keys: &[XPub],
+    first_index: usize,
+    network: Network,
+) -> (Vec<String>, Vec<([u8; 20], u32)>) {
+    let mut addresses = Vec::with_capacity(keys.len());
+    let mut lookup = Vec::with_capacity(keys.len() * 2);
+    for (offset, key) in keys.iter().enumerate() {
+        let index = (first_index + offset) as u32;
+        let public_key = key.public_key();
+        let hash = util::hash160(&public_key.serialize());
+        addresses.push(util::to_address(hash, network));
+        lookup.push((hash, index));
+        lookup.push((util::hash160(&public_key.serialize_uncompressed()), index));
+    }
+    (addresses, lookup)
+}
+
+/// Progress of scanning one chain's addresses in order.
+#[derive(Default)]
+struct ChainScan {
+    addresses: Vec<String>,
+    transactions: Vec<String>,
+    heights: HashMap<String, u32>,
+    /// Index after the last address with history, the next one to hand out.
+    next_index: usize,
+}
+
+impl ChainScan {
+    /// Whether `gap_limit` unused addresses follow the last used one and
+    /// `scan_to_index`, if any, was reached.
+    fn is_done(&self, gap_limit: usize, scan_to_index: Option<u32>) -> bool {
+        let past_ceiling = scan_to_index.is_none_or(|index| self.addresses.len() > index as usize);
+        self.addresses.len() - self.next_index >= gap_limit && past_ceiling
+    }
+
+    /// Addresses missing from `history` have none, entries for addresses
+    /// that weren't requested or were already listed are ignored.
+    fn add_batch(&mut self, batch: Vec<String>, history: &[AddressHistory]) {
+        let mut pending: HashSet<_> = batch.iter().map(String::as_str).collect();
+        let history: Vec<_> = history
             .iter()
-            .flat_map(|a| a.history.iter())
-            .map(|t| t.tx_hash.to_owned())
-            .for_each(|t| transactions.push(t));
-
-        last_index += last_tx_address(&addresses, &history);
-        if last_index == 0 || last_index % 20 != 0 {
-            next_address = addresses[last_index as usize + 1].clone();
-            break;
+            .filter(|entry| pending.remove(entry.address.as_str()))
+            .collect();
+        let infos = history.iter().flat_map(|a| a.history.iter());
+        self.transactions
+            .extend(infos.clone().map(|t| t.tx_hash.to_owned()));
+        self.heights.extend(
+            infos
+                .filter(|t| t.height > 0)
+                .map(|t| (t.tx_hash.to_owned(), t.height)),
+        );
+        if let Some(used) = last_used(&batch, history) {
+            self.next_index = self.addresses.len() + used + 1;
         }
+        self.addresses.extend(batch);
+    }
+
+    /// `lookup` has to hold the entries of every scanned address.
+    fn into_state(
+        self,
+        key: &WalletKey,
+        chain: u32,
+        lookup: HashMap<[u8; 20], u32>,
+        network: Network,
+    ) -> Result<FetchingState> {
+        let chain_key = key.derive_normal(chain)?;
+        // Derived rather than looked up, the last batch may have ended right
+        // on the last used address
+        let next_address = chain_key
+            .derive_normal(self.next_index as u32)?
+            .derive_public()
+            .to_address(network);
+        Ok(FetchingState {
+            key: Some(chain_key),
+            last_index: self.next_index as u32,
+            lookup,
+            transactions: self.transactions,
+            heights: self.heights,
+            next_address,
+        })
     }
-    Ok(FetchingState {
-        xprv,
-        last_index,
-        lookup,
-        transactions,
-        next_address,
+}
+
+/// The coins of `responses`, addresses with history but nothing unspent
+/// contributing none.
+fn collect_unspent(
+    responses: Vec<UtxoResponse>,
+    main: &FetchingState,
+    change: &FetchingState,
+) -> Result<Vec<RichOutput>> {
+    responses
+        .into_iter()
+        .flat_map(|r| r.unspent.into_iter().map(move |u| (r.address.clone(), u)))
+        .map(|(address, unspent)| rich_output(&address, unspent, main, change))
+        .collect()
+}
+
+/// An unspent output found on `address`, one of the scanned addresses.
+fn rich_output(
+    address: &str,
+    unspent: UnspentOutput,
+    main: &FetchingState,
+    change: &FetchingState,
+) -> Result<RichOutput> {
+    let address = util::address_bytes(address)?;
+    Ok(RichOutput {
+        tx_pos: unspent.tx_pos,
+        tx_hash: unspent.tx_hash,
+        amount: unspent.value,
+        address,
+        // Unconfirmed outputs are reported at height 0
+        height: Some(unspent.height).filter(|&height| height > 0),
+        key_path: key_path(main, change, &address),
     })
 }
 
+/// The chains are derived from the distinct children `0` and `1`, so their
+/// addresses can only coincide on a hash collision.
+fn key_path(main: &FetchingState, change: &FetchingState, hash: &[u8; 20]) -> Option<KeyPath> {
+    let path = |chain| move |&index| KeyPath { chain, index };
+    main.lookup
+        .get(hash)
+        .map(path(0))
+        .or_else(|| change.lookup.get(hash).map(path(1)))
+}
+
 #[derive(Serialize)]
 struct AddressRequest {
     addresses: Vec<String>,
@@ -161,47 +834,93 @@ struct AddressRequest {
 struct AddressHistory {
     address: String,
     history: Vec<TransactionInfo>,
+    /// Set when the address has more history than fits in one response.
+    #[serde(default, rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryPage {
+    result: Vec<TransactionInfo>,
+    #[serde(default, rename = "nextPageToken")]
+    next_page_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct TransactionInfo {
     tx_hash: String,
+    /// Zero while in the mempool.
+    #[serde(default)]
+    height: u32,
 }
 
-async fn fetch_transactions_for_addresses(chunk: &[String]) -> Result<Vec<AddressHistory>> {
+async fn fetch_transactions_for_addresses(
+    chunk: &[String],
+    rate_limiter: &RateLimiter,
+    settings: &Settings,
+    timeout_ms: u32,
+) -> Result<Vec<AddressHistory>> {
     let body = serde_json::to_string(&AddressRequest {
         addresses: chunk.to_vec(),
     })?;
-    Request::post("https://api.whatsonchain.com/v1/bsv/main/addresses/history")
-        .body(body)
-        .send()
-        .await?
-        .json()
-        .await
-        .map_err(|e| e.into())
-}
+    let mut histories: Vec<AddressHistory> =
+        post_json(&settings.url("addresses/history"), body, timeout_ms).await?;
 
-fn last_tx_address(chunk: &[String], transactions: &[AddressHistory]) -> u32 {
-    let transactions_by_address: HashMap<String, Vec<String>> = transactions
-        .iter()
-        .map(|entry| {
-            (
-                entry.address.to_string(),
-                entry
-                    .history
-                    .iter()
-                    .map(|h| h.tx_hash.to_string())
-                    .collect(),
-            )
+    for history in &mut histories {
+        collect_pages(history, rate_limiter, |address, token| {
+            fetch_history_page(settings, address, token, timeout_ms)
         })
-        .collect();
-    for i in 0..chunk.len() {
-        if transactions_by_address[&chunk[i]].is_empty() {
-            return i as u32;
-        }
+        .await?;
+    }
+    Ok(histories)
+}
+
+async fn fetch_history_page(
+    settings: &Settings,
+    address: String,
+    token: String,
+    timeout_ms: u32,
+) -> Result<HistoryPage> {
+    let url = settings.url(&format!(
+        "address/{address}/confirmed/history?token={token}"
+    ));
+    let request = async { Ok(Request::get(&url).send().await?.json().await?) };
+
+    race_timeout(request, TimeoutFuture::new(timeout_ms), timeout_ms).await
+}
+
+/// Follows the page tokens of `history` until all of it has been fetched.
+async fn collect_pages<F, Fut>(
+    history: &mut AddressHistory,
+    rate_limiter: &RateLimiter,
+    mut fetch_page: F,
+) -> Result<()>
+where
+    F: FnMut(String, String) -> Fut,
+    Fut: Future<Output = Result<HistoryPage>>,
+{
+    while let Some(token) = history.next_page_token.take() {
+        rate_limiter.take().await;
+        let page = fetch_page(history.address.clone(), token).await?;
+        history.history.extend(page.result);
+        history.next_page_token = page.next_page_token;
     }
+    Ok(())
+}
 
-    chunk.len() as u32
+/// Position in `chunk` of the last address that has any history.
+fn last_used<'a>(
+    chunk: &[String],
+    transactions: impl IntoIterator<Item = &'a AddressHistory>,
+) -> Option<usize> {
+    let used: HashSet<_> = transactions
+        .into_iter()
+        .filter(|entry| !entry.history.is_empty())
+        .map(|entry| entry.address.as_str())
+        .collect();
+    chunk
+        .iter()
+        .rposition(|address| used.contains(address.as_str()))
 }
 
 #[derive(Serialize)]
@@ -215,25 +934,56 @@ struct UtxoResponse {
     unspent: Vec<UnspentOutput>,
 }
 
+#[derive(Deserialize)]
+struct ImportedUtxo {
+    txid: String,
+    vout: u32,
+    amount: Satoshis,
+    address: String,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct UnspentOutput {
     pub tx_pos: u32,
     pub tx_hash: String,
-    pub value: u64,
+    pub value: Satoshis,
+    #[serde(default)]
+    pub height: u32,
+}
+
+#[derive(Deserialize)]
+struct ChainInfo {
+    blocks: u32,
+}
+
+async fn fetch_chain_info(settings: &Settings, timeout_ms: u32) -> Result<ChainInfo> {
+    let url = settings.url("chain/info");
+    let request = async { Ok(Request::get(&url).send().await?.json().await?) };
+
+    race_timeout(request, TimeoutFuture::new(timeout_ms), timeout_ms).await
+}
+
+/// Coins on an address outside the wallet, e.g. that of an imported key.
+pub async fn fetch_foreign_unspent(
+    address: &str,
+    settings: &Settings,
+    timeout_ms: u32,
+) -> Result<Vec<RichOutput>> {
+    let utxos = fetch_unspent_outputs(&[address.to_owned()], settings, timeout_ms).await?;
+    let unowned = FetchingState::default();
+    collect_unspent(utxos, &unowned, &unowned)
 }
 
-async fn fetch_unspent_outputs(addresses: &[String]) -> Result<Vec<UtxoResponse>> {
+async fn fetch_unspent_outputs(
+    addresses: &[String],
+    settings: &Settings,
+    timeout_ms: u32,
+) -> Result<Vec<UtxoResponse>> {
     let body = serde_json::to_string(&AddressRequest {
         addresses: addresses.to_vec(),
     })?;
 
-    Request::post("https://api.whatsonchain.com/v1/bsv/main/addresses/unspent")
-        .body(body)
-        .send()
-        .await?
-        .json()
-        .await
-        .map_err(|e| e.into())
+    post_json(&settings.url("addresses/unspent"), body, timeout_ms).await
 }
 
 #[derive(Serialize)]
@@ -241,17 +991,1116 @@ struct PostTransactionRequest {
     txhex: String,
 }
 
-pub async fn publish_transaction(transaction: &Transaction) -> Result<String> {
-    let raw_transaction = Vec::from(transaction);
-    let txhex = hex::encode(raw_transaction);
+pub async fn publish_transaction(
+    transaction: &Transaction,
+    settings: &Settings,
+    timeout_ms: u32,
+) -> Result<String> {
+    transaction.check_standardness()?;
+    let txhex = transaction.to_hex();
 
     let request = serde_json::to_string(&PostTransactionRequest { txhex })?;
 
-    Request::post("https://api.whatsonchain.com/v1/bsv/main/tx/raw")
-        .body(request)
-        .send()
-        .await?
-        .json()
-        .await
-        .map_err(|e| e.into())
+    post_json(&settings.url("tx/raw"), request, timeout_ms).await
+}
+
+async fn post_json<T: DeserializeOwned>(url: &str, body: String, timeout_ms: u32) -> Result<T> {
+    let request = async {
+        Request::post(url)
+            .body(body)
+            .send()
+            .await?
+            .json()
+            .await
+            .map_err(|e| e.into())
+    };
+
+    race_timeout(request, TimeoutFuture::new(timeout_ms), timeout_ms).await
+}
+
+/// Resolves to the request's result, unless `timer` fires first.
+pub async fn race_timeout<T>(
+    request: impl Future<Output = Result<T>>,
+    timer: impl Future<Output = ()>,
+    timeout_ms: u32,
+) -> Result<T> {
+    match future::select(pin!(request), pin!(timer)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(NetworkError::Timeout(timeout_ms).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use secp256k1::{ecdsa::Signature, Message};
+
+    use super::*;
+    use crate::bip32::XPrv;
+    use crate::psbt::PartialTransaction;
+    use crate::script;
+    use crate::sending::{Input, Output, TransactionBuilder};
+    use crate::util::{ripemd160, sha256};
+
+    /// Indices of the first `count` addresses of `chain`.
+    fn chain_lookup(xpub: &XPub, chain: u32, count: usize) -> Result<HashMap<[u8; 20], u32>> {
+        Ok(xpub
+            .key_iter(chain)
+            .take(count)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, key)| lookup_entries(key.public_key(), index as u32))
+            .collect())
+    }
+
+    fn lookup_entries(public_key: PublicKey, index: u32) -> [([u8; 20], u32); 2] {
+        [
+            (util::hash160(&public_key.serialize()), index),
+            (util::hash160(&public_key.serialize_uncompressed()), index),
+        ]
+    }
+
+    /// The master key of BIP32 test vector 1.
+    fn test_xprv() -> Result<XPrv> {
+        Ok("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".parse()?)
+    }
+
+    /// The state of a finished `scan`, its lookup derived separately.
+    fn scanned_state(scan: ChainScan, xprv: &XPrv, chain: u32) -> Result<FetchingState> {
+        let lookup = chain_lookup(&xprv.derive_public(), chain, scan.addresses.len())?;
+        scan.into_state(
+            &WalletKey::Private(xprv.clone()),
+            chain,
+            lookup,
+            Network::Mainnet,
+        )
+    }
+
+    fn output(amount: u64) -> RichOutput {
+        RichOutput {
+            tx_pos: 0,
+            tx_hash: "3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b".to_owned(),
+            amount: Satoshis(amount),
+            address: [0u8; 20],
+            height: Some(800_000),
+            key_path: None,
+        }
+    }
+
+    #[test]
+    fn confirmations_count_from_tip() {
+        assert_eq!(Some(1), confirmations(Some(800_000), Some(800_000)));
+        assert_eq!(Some(6), confirmations(Some(799_995), Some(800_000)));
+        assert_eq!(Some(0), confirmations(None, Some(800_000)));
+        // The tip may lag behind the output's block between requests
+        assert_eq!(Some(0), confirmations(Some(800_001), Some(800_000)));
+
+        assert_eq!(None, confirmations(Some(800_000), None));
+        assert_eq!(Some(0), confirmations(None, None));
+    }
+
+    #[test]
+    fn output_counts_exclude_dust_and_unconfirmed() {
+        let unconfirmed = |amount| RichOutput {
+            height: None,
+            ..output(amount)
+        };
+        let mut state = WalletState {
+            unspent_outputs: vec![
+                output(10_000),
+                output(1),
+                output(DUST_LIMIT.0),
+                output(DUST_LIMIT.0 - 1),
+                output(5_000_000),
+                unconfirmed(20_000),
+                unconfirmed(1),
+            ],
+            tip_height: Some(800_005),
+            ..Default::default()
+        };
+
+        assert_eq!(7, state.output_count());
+        assert_eq!(4, state.spendable_output_count(0));
+        assert_eq!(3, state.spendable_output_count(1));
+        assert_eq!(3, state.spendable_output_count(6));
+        assert_eq!(0, state.spendable_output_count(7));
+
+        state.tip_height = None;
+        assert_eq!(3, state.spendable_output_count(1));
+        assert_eq!(0, state.spendable_output_count(2));
+    }
+
+    #[test]
+    fn max_sendable_excludes_fee_and_dust() {
+        let outputs = vec![output(10_000), output(20_000), output(30_000), output(100)];
+        let state = WalletState {
+            balance: outputs.iter().map(|o| o.amount).sum(),
+            unspent_outputs: outputs,
+            ..Default::default()
+        };
+
+        let spendable = state.max_sendable(2);
+
+        assert_eq!(Satoshis(60_100), state.balance);
+        assert!(spendable < Satoshis(60_000));
+        assert!(state.can_afford(spendable.0, 2));
+        assert!(!state.can_afford(spendable.0 + 1, 2));
+        assert_eq!(Satoshis(0), WalletState::default().max_sendable(2));
+    }
+
+    #[test]
+    fn max_sendable_matches_send_all_transaction() -> Result<()> {
+        let outputs: Vec<_> = [10_000, 20_000, 30_000]
+            .into_iter()
+            .enumerate()
+            .map(|(i, amount)| RichOutput {
+                tx_pos: i as u32,
+                ..output(amount)
+            })
+            .collect();
+        let state = WalletState {
+            unspent_outputs: outputs.clone(),
+            ..Default::default()
+        };
+        let recipient = "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr";
+        let send_all = |amount| {
+            let mut builder = TransactionBuilder::new(recipient, Network::Mainnet);
+            builder.set_fee_rate(2);
+            builder.use_inputs(outputs.clone());
+            builder.add_output(Output::new(amount, recipient, Network::Mainnet)?);
+            builder.build_unsigned(&outputs)
+        };
+
+        let max = state.max_sendable(2);
+        let (transaction, breakdown) = send_all(max)?;
+
+        assert_eq!(1, transaction.outputs().len());
+        assert_eq!(max, transaction.outputs()[0].amount());
+        assert_eq!(Satoshis(60_000), breakdown.sent + breakdown.fee);
+        assert!(send_all(max + Satoshis(1)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn collect_pages_follows_tokens() -> Result<()> {
+        let mut history: AddressHistory = serde_json::from_str(
+            r#"{"address":"addr","history":[{"tx_hash":"a"}],"nextPageToken":"page2"}"#,
+        )?;
+        let mut requested = vec![];
+        let rate_limiter = RateLimiter::with_clock(10, || 0.0);
+
+        block_on(collect_pages(
+            &mut history,
+            &rate_limiter,
+            |address, token| {
+                requested.push((address, token));
+                future::ready(
+                    serde_json::from_str(r#"{"result":[{"tx_hash":"b"},{"tx_hash":"c"}]}"#)
+                        .map_err(Into::into),
+                )
+            },
+        ))?;
+
+        let txids: Vec<_> = history.history.iter().map(|t| t.tx_hash.as_str()).collect();
+        assert_eq!(vec!["a", "b", "c"], txids);
+        assert_eq!(vec![("addr".to_owned(), "page2".to_owned())], requested);
+        assert_eq!(None, history.next_page_token);
+        Ok(())
+    }
+
+    #[test]
+    fn last_used_skips_gaps() -> Result<()> {
+        let chunk: Vec<_> = ["a", "b", "c", "d"].map(String::from).into();
+        let history: Vec<AddressHistory> = serde_json::from_str(
+            r#"[
+                {"address":"a","history":[{"tx_hash":"1"}]},
+                {"address":"b","history":[]},
+                {"address":"c","history":[{"tx_hash":"2"}]},
+                {"address":"d","history":[]}
+            ]"#,
+        )?;
+
+        assert_eq!(Some(2), last_used(&chunk, &history));
+        assert_eq!(None, last_used(&chunk, &history[1..2]));
+        Ok(())
+    }
+
+    #[test]
+    fn add_batch_tolerates_missing_and_repeated_addresses() -> Result<()> {
+        let batch: Vec<_> = ["a", "b", "c"].map(String::from).into();
+        let history: Vec<AddressHistory> = serde_json::from_str(
+            r#"[
+                {"address":"b","history":[{"tx_hash":"1"}]},
+                {"address":"b","history":[{"tx_hash":"1"}]},
+                {"address":"x","history":[{"tx_hash":"2"}]}
+            ]"#,
+        )?;
+
+        let mut scan = ChainScan::default();
+        scan.add_batch(batch, &history);
+
+        assert_eq!(3, scan.addresses.len());
+        assert_eq!(2, scan.next_index);
+        assert_eq!(vec!["1".to_owned()], scan.transactions);
+        Ok(())
+    }
+
+    #[test]
+    fn slow_request_times_out() {
+        let slow = future::pending::<Result<()>>();
+        let result = block_on(race_timeout(slow, future::ready(()), 50));
+
+        assert!(matches!(
+            result.unwrap_err().downcast::<NetworkError>(),
+            Ok(NetworkError::Timeout(50))
+        ));
+
+        let fast = future::ready(Ok(5));
+        assert_eq!(
+            5,
+            block_on(race_timeout(fast, future::pending(), 50)).unwrap()
+        );
+    }
+
+    #[test]
+    fn balances_split_by_chain() -> Result<()> {
+        let key_pair = |byte| -> Result<_> {
+            let secret_key = SecretKey::from_slice(&[byte; 32])?;
+            Ok((secret_key, PublicKey::from_secret_key_global(&secret_key)))
+        };
+        let chain = |byte| -> Result<FetchingState> {
+            let (_, public_key) = key_pair(byte)?;
+            Ok(FetchingState {
+                lookup: lookup_entries(public_key, 0).into_iter().collect(),
+                ..Default::default()
+            })
+        };
+        let main = chain(1)?;
+        let change = chain(2)?;
+        let output_at = |chain: &FetchingState, amount| RichOutput {
+            address: *chain.lookup.keys().next().unwrap(),
+            ..output(amount)
+        };
+
+        let state = WalletState {
+            unspent_outputs: vec![
+                output_at(&main, 10_000),
+                output_at(&change, 2_000),
+                output_at(&main, 5_000),
+                output_at(&change, 700),
+            ],
+            main,
+            change,
+            ..Default::default()
+        };
+
+        assert_eq!(Satoshis(15_000), state.main_balance());
+        assert_eq!(Satoshis(2_700), state.change_balance());
+        Ok(())
+    }
+
+    #[test]
+    fn spent_address_contributes_no_balance() -> Result<()> {
+        let secret_key = SecretKey::from_slice(&[1; 32])?;
+        let public_key = PublicKey::from_secret_key_global(&secret_key);
+        let hash = ripemd160(&sha256(&public_key.serialize()));
+        // History still lists the coin's transaction, it was spent after the
+        // history scan
+        let mut state = WalletState {
+            main: FetchingState {
+                lookup: lookup_entries(public_key, 0).into_iter().collect(),
+                transactions: vec!["spent".to_owned()],
+                ..Default::default()
+            },
+            unspent_outputs: vec![
+                output(10_000),
+                RichOutput {
+                    tx_hash: "spent".to_owned(),
+                    address: hash,
+                    key_path: Some(KeyPath { chain: 0, index: 0 }),
+                    ..output(7_000)
+                },
+            ],
+            balance: Satoshis(17_000),
+            ..Default::default()
+        };
+
+        state.merge_unspent(vec![UtxoResponse {
+            address: util::to_address(hash, Network::Mainnet),
+            unspent: vec![],
+        }])?;
+
+        assert_eq!(vec!["spent".to_owned()], state.main.transactions);
+        assert_eq!(vec![output(10_000)], state.unspent_outputs);
+        assert_eq!(Satoshis(10_000), state.balance);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_unspent_replaces_address_coins() -> Result<()> {
+        let secret_key = SecretKey::from_slice(&[3; 32])?;
+        let public_key = PublicKey::from_secret_key_global(&secret_key);
+        let hash = ripemd160(&sha256(&public_key.serialize()));
+        let mut state = WalletState {
+            main: FetchingState {
+                lookup: lookup_entries(public_key, 4).into_iter().collect(),
+                ..Default::default()
+            },
+            unspent_outputs: vec![
+                output(10_000),
+                RichOutput {
+                    address: hash,
+                    key_path: Some(KeyPath { chain: 0, index: 4 }),
+                    ..output(2_000)
+                },
+            ],
+            balance: Satoshis(12_000),
+            ..Default::default()
+        };
+        let unspent = |tx_hash: &str, value| UnspentOutput {
+            tx_pos: 1,
+            tx_hash: tx_hash.to_owned(),
+            value: Satoshis(value),
+            height: 0,
+        };
+
+        state.merge_unspent(vec![UtxoResponse {
+            address: util::to_address(hash, Network::Mainnet),
+            unspent: vec![unspent("old", 2_000), unspent("new", 5_000)],
+        }])?;
+
+        assert_eq!(3, state.unspent_outputs.len());
+        assert_eq!(Satoshis(17_000), state.balance);
+        let refreshed = &state.unspent_outputs[2];
+        assert_eq!("new", refreshed.tx_hash);
+        assert_eq!(Some(KeyPath { chain: 0, index: 4 }), refreshed.key_path);
+        assert_eq!(None, refreshed.height);
+        Ok(())
+    }
+
+    #[test]
+    fn can_afford_at_exact_boundary() -> Result<()> {
+        let state = WalletState {
+            unspent_outputs: vec![output(20_000)],
+            ..Default::default()
+        };
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(output(0).tx_hash, 0)?);
+        transaction.add_output(Output::new_from_decoded(Satoshis(0), [0; 20]));
+        let affordable = 20_000 - transaction.suggested_fee_without_change(2).0;
+
+        assert!(state.can_afford(affordable, 2));
+        assert!(!state.can_afford(affordable + 1, 2));
+        assert!(state.can_afford(affordable + 1, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_sent_updates_pending_balance() -> Result<()> {
+        let secret_key = SecretKey::from_slice(&[2; 32])?;
+        let change = FetchingState {
+            lookup: lookup_entries(PublicKey::from_secret_key_global(&secret_key), 0)
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let change_address = *change.lookup.keys().next().unwrap();
+        let spent = output(20_000);
+        let kept = RichOutput {
+            tx_pos: 1,
+            ..output(5_000)
+        };
+        let mut state = WalletState {
+            change,
+            balance: Satoshis(25_000),
+            unspent_outputs: vec![spent.clone(), kept.clone()],
+            ..Default::default()
+        };
+
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(spent.tx_hash, spent.tx_pos)?);
+        transaction.add_output(Output::new(
+            Satoshis(15_000),
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+            Network::Mainnet,
+        )?);
+        transaction.add_output(Output::new_from_decoded(Satoshis(4_800), change_address));
+        state.apply_sent(&transaction);
+
+        assert_eq!(Satoshis(9_800), state.balance);
+        assert_eq!(
+            vec![
+                kept,
+                RichOutput {
+                    tx_pos: 1,
+                    tx_hash: transaction.txid(),
+                    amount: Satoshis(4_800),
+                    address: change_address,
+                    height: None,
+                    key_path: Some(KeyPath { chain: 1, index: 0 }),
+                }
+            ],
+            state.unspent_outputs
+        );
+        assert_eq!(vec![transaction.txid()], state.pending_txids);
+        Ok(())
+    }
+
+    #[test]
+    fn needs_funding_until_coins_arrive() {
+        let synced = FetchingState {
+            next_address: "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr".to_owned(),
+            ..Default::default()
+        };
+        let mut state = WalletState::default();
+        assert!(!state.needs_funding());
+
+        state.main = synced;
+        assert!(state.needs_funding());
+
+        state.unspent_outputs.push(output(1_000));
+        assert!(!state.needs_funding());
+    }
+
+    #[test]
+    fn transaction_count_spans_both_chains() -> Result<()> {
+        let chain = |txids: &[&str]| -> Result<FetchingState> {
+            let history: AddressHistory = serde_json::from_value(serde_json::json!({
+                "address": "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+                "history": txids.iter().map(|t| serde_json::json!({"tx_hash": t})).collect::<Vec<_>>(),
+            }))?;
+            let mut scan = ChainScan::default();
+            scan.add_batch(vec![history.address.clone()], &[history]);
+            Ok(FetchingState {
+                transactions: scan.transactions,
+                ..Default::default()
+            })
+        };
+        // The second transaction paid to both chains, e.g. a send with change
+        let state = WalletState {
+            main: chain(&["a", "b"])?,
+            change: chain(&["b", "c"])?,
+            ..Default::default()
+        };
+
+        assert_eq!(3, state.transaction_count());
+        assert_eq!(0, WalletState::default().transaction_count());
+        assert_eq!(20, gap_limit_for(20, 99));
+        assert_eq!(40, gap_limit_for(20, 100));
+        assert_eq!(100, gap_limit_for(60, 100));
+        assert_eq!(100, gap_limit_for(100, 100));
+        Ok(())
+    }
+
+    #[test]
+    fn history_counts_confirmations() -> Result<()> {
+        let history: AddressHistory = serde_json::from_str(
+            r#"{
+                "address": "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+                "history": [
+                    {"tx_hash": "old", "height": 799995},
+                    {"tx_hash": "mempool", "height": 0},
+                    {"tx_hash": "new", "height": 800000}
+                ]
+            }"#,
+        )?;
+        let mut scan = ChainScan::default();
+        scan.add_batch(vec![history.address.clone()], &[history]);
+        let mut state = WalletState {
+            main: FetchingState {
+                transactions: scan.transactions,
+                heights: scan.heights,
+                ..Default::default()
+            },
+            tip_height: Some(800_000),
+            ..Default::default()
+        };
+
+        let entry = |txid: &str, confirmations| (txid.to_owned(), confirmations);
+        assert_eq!(
+            vec![
+                entry("mempool", Some(0)),
+                entry("new", Some(1)),
+                entry("old", Some(6))
+            ],
+            state.history()
+        );
+
+        // Without the tip only the unconfirmed count is known
+        state.tip_height = None;
+        assert_eq!(
+            vec![
+                entry("mempool", Some(0)),
+                entry("new", None),
+                entry("old", None)
+            ],
+            state.history()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn co_signs_multisig_with_scanned_key() -> Result<()> {
+        let xprv = test_xprv()?;
+        let state = WalletState {
+            main: FetchingState {
+                key: Some(WalletKey::Private(xprv.derive_normal(0)?)),
+                lookup: chain_lookup(&xprv.derive_public(), 0, 3)?,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let own = state.signing_key(KeyPath { chain: 0, index: 2 })?;
+        let other_secret = SecretKey::from_slice(&[7; 32])?;
+        let other = (
+            other_secret,
+            PublicKey::from_secret_key_global(&other_secret),
+        );
+
+        let mut script = vec![script::OP_1 + 1];
+        for (_, public_key) in [other, own] {
+            script::push_data(&mut script, &public_key.serialize());
+        }
+        script.extend([script::OP_1 + 1, script::OP_CHECKMULTISIG]);
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(output(0).tx_hash, 0)?);
+        transaction.add_output(Output::new(
+            Satoshis(9_000),
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+            Network::Mainnet,
+        )?);
+        let previous_outputs = HashMap::from([(
+            (hex::decode(output(0).tx_hash)?, 0),
+            Output::from_script(Satoshis(10_000), script),
+        )]);
+        let mut partial = PartialTransaction::new(&transaction, &previous_outputs)?;
+        let other_keys = HashMap::from([(ripemd160(&sha256(&other.1.serialize())), other)]);
+        assert_eq!(1, partial.sign_available(&other_keys)?);
+        assert!(partial.finalize().is_err());
+
+        let mut imported = PartialTransaction::from_json(&partial.to_json()?)?;
+        let keys = state.keys_for_hashes(&imported.key_hashes()?);
+        assert_eq!(1, keys.len());
+        assert_eq!(1, imported.sign_available(&keys)?);
+
+        // OP_0 followed by the signatures in the order of the script's keys
+        let finalized = Vec::from(&imported.finalize()?);
+        // Version, input count, outpoint, then the script_sig's length
+        let script_sig_start = 4 + 1 + 32 + 4 + 1;
+        let script_sig = &finalized
+            [script_sig_start..script_sig_start + finalized[script_sig_start - 1] as usize];
+        let parts: Vec<_> = script::disassemble(script_sig)
+            .split(' ')
+            .map(str::to_owned)
+            .collect();
+        assert_eq!(3, parts.len());
+        assert_eq!("OP_0", parts[0]);
+        let hash = transaction
+            .signature_hash(0, &previous_outputs[&(hex::decode(output(0).tx_hash)?, 0)])?;
+        for ((_, public_key), signature) in [other, own].into_iter().zip(&parts[1..]) {
+            let signature = hex::decode(signature)?;
+            let signature = Signature::from_der(&signature[..signature.len() - 1])?;
+            signature.verify(&Message::from_slice(&hash)?, &public_key)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn imported_utxos_fund_a_send() -> Result<()> {
+        let xprv = test_xprv()?;
+        // Never synced, ownership is derived offline
+        let mut state = WalletState::new(&WalletKey::Private(xprv.clone()), Network::Mainnet)?;
+        let settings = Settings::default();
+        let utxo = |txid: &str, address: String, amount: u64| {
+            serde_json::json!({
+                "txid": txid,
+                "vout": 1,
+                "amount": amount,
+                "address": address,
+            })
+        };
+        let json = serde_json::to_string(&[
+            utxo(&"3f".repeat(32), state.receive_address_at(0)?, 20_000),
+            utxo(&"ba".repeat(32), state.change_address_at(19)?, 30_000),
+        ])?;
+
+        assert_eq!(2, state.import_utxos(&json, &settings)?);
+        assert_eq!(0, state.import_utxos(&json, &settings)?);
+        assert_eq!(Satoshis(50_000), state.balance);
+        assert_eq!(
+            vec![
+                Some(KeyPath { chain: 0, index: 0 }),
+                Some(KeyPath {
+                    chain: 1,
+                    index: 19
+                })
+            ],
+            state
+                .unspent_outputs
+                .iter()
+                .map(|o| o.key_path)
+                .collect::<Vec<_>>()
+        );
+
+        // Past the gap limit unless scanned to
+        let beyond =
+            serde_json::to_string(&[utxo(&"c4".repeat(32), state.receive_address_at(20)?, 1_000)])?;
+        assert!(state.import_utxos(&beyond, &settings).is_err());
+        let scan_further = Settings {
+            scan_to_index: Some(20),
+            ..Settings::default()
+        };
+        assert_eq!(1, state.import_utxos(&beyond, &scan_further)?);
+
+        let foreign = serde_json::to_string(&[serde_json::json!({
+            "txid": "3f".repeat(32),
+            "vout": 2,
+            "amount": 1_000,
+            "address": "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr",
+        })])?;
+        assert!(matches!(
+            state
+                .import_utxos(&foreign, &settings)
+                .unwrap_err()
+                .downcast::<WalletError>(),
+            Ok(WalletError::NotOwnAddress(_))
+        ));
+
+        let recipients = [(
+            "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr".to_owned(),
+            Satoshis(40_000),
+        )];
+        let transaction = crate::sending::prepare_and_sign(
+            &recipients,
+            &state.unspent_outputs,
+            &state.receive_address_at(1)?,
+            Network::Mainnet,
+            &state.signing_keys(&state.unspent_outputs),
+            1,
+            &Default::default(),
+        )?;
+        transaction.verify(&crate::sending::previous_outputs(&state.unspent_outputs)?)?;
+        assert_eq!(2, transaction.input_count());
+        Ok(())
+    }
+
+    #[test]
+    fn next_address_follows_full_batch_of_used() -> Result<()> {
+        let xprv = test_xprv()?;
+        let batch = xprv
+            .address_iter(0, Network::Mainnet)
+            .take(ADDRESS_BATCH_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        let history = batch
+            .iter()
+            .map(|address| {
+                serde_json::from_value(serde_json::json!({
+                    "address": address,
+                    "history": [{"tx_hash": address}],
+                }))
+            })
+            .collect::<Result<Vec<AddressHistory>, _>>()?;
+        let mut scan = ChainScan::default();
+        scan.add_batch(batch, &history);
+        assert!(!scan.is_done(20, None));
+
+        let state = scanned_state(scan, &xprv, 0)?;
+
+        assert_eq!(20, state.last_index);
+        assert_eq!(
+            xprv.address_iter(0, Network::Mainnet).nth(20).unwrap()?,
+            state.next_address
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn address_batch_matches_address_iter_and_chain_lookup() -> Result<()> {
+        const COUNT: usize = 50;
+        let xprv = test_xprv()?;
+        let xpub = xprv.derive_public();
+
+        let keys = xpub
+            .key_iter(0)
+            .take(COUNT)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (addresses, lookup) = address_batch(&keys, 0, Network::Mainnet);
+
+        let expected = xpub
+            .address_iter(0, Network::Mainnet)
+            .take(COUNT)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(expected, addresses);
+        assert_eq!(
+            chain_lookup(&xpub, 0, COUNT)?,
+            lookup.into_iter().collect::<HashMap<_, _>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn receive_address_follows_last_used() -> Result<()> {
+        let xprv = test_xprv()?;
+        let mut addresses = xprv.address_iter(0, Network::Mainnet);
+        let mut scan = ChainScan::default();
+        while !scan.is_done(3, None) {
+            let batch = addresses.by_ref().take(4).collect::<Result<Vec<_>, _>>()?;
+            // Addresses 0 to 2 and 5 were used, 3 and 4 are a gap below the limit
+            let history = batch
+                .iter()
+                .enumerate()
+                .map(|(i, address)| {
+                    let used = matches!(scan.addresses.len() + i, 0..=2 | 5);
+                    serde_json::from_value(serde_json::json!({
+                        "address": address,
+                        "history": if used { vec![serde_json::json!({"tx_hash": address})] } else { vec![] },
+                    }))
+                })
+                .collect::<Result<Vec<AddressHistory>, _>>()?;
+            scan.add_batch(batch, &history);
+        }
+
+        let state = WalletState {
+            main: scanned_state(scan, &xprv, 0)?,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            xprv.address_iter(0, Network::Mainnet).nth(6).unwrap()?,
+            state.receive_address()
+        );
+        assert_eq!(12, state.main.scanned());
+        assert_eq!("", WalletState::default().receive_address());
+        Ok(())
+    }
+
+    #[test]
+    fn scan_to_index_passes_large_gaps() -> Result<()> {
+        let xprv = test_xprv()?;
+        let scan = |scan_to_index| -> Result<ChainScan> {
+            let mut addresses = xprv.address_iter(0, Network::Mainnet);
+            let mut scan = ChainScan::default();
+            while !scan.is_done(5, scan_to_index) {
+                let batch = addresses.by_ref().take(20).collect::<Result<Vec<_>, _>>()?;
+                // Only addresses 0 and 60 were used
+                let history = batch
+                    .iter()
+                    .enumerate()
+                    .map(|(i, address)| {
+                        let used = matches!(scan.addresses.len() + i, 0 | 60);
+                        let history: Vec<_> = used
+                            .then(|| serde_json::json!({"tx_hash": address}))
+                            .into_iter()
+                            .collect();
+                        serde_json::from_value(
+                            serde_json::json!({"address": address, "history": history}),
+                        )
+                    })
+                    .collect::<Result<Vec<AddressHistory>, _>>()?;
+                scan.add_batch(batch, &history);
+            }
+            Ok(scan)
+        };
+
+        let limited = scan(None)?;
+        assert_eq!((20, 1), (limited.addresses.len(), limited.next_index));
+
+        let forced = scan(Some(70))?;
+        assert_eq!((80, 61), (forced.addresses.len(), forced.next_index));
+        Ok(())
+    }
+
+    #[test]
+    fn gap_report_describes_scan() -> Result<()> {
+        let xprv = test_xprv()?;
+        // Addresses 0 to 4 used, scanned until 20 unused ones followed
+        let state = WalletState {
+            main: FetchingState {
+                last_index: 5,
+                lookup: chain_lookup(&xprv.derive_public(), 0, 40)?,
+                ..Default::default()
+            },
+            change: FetchingState {
+                lookup: chain_lookup(&xprv.derive_public(), 1, 20)?,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let [(_, main), (_, change)] = state.gap_report();
+
+        assert_eq!(
+            GapReport {
+                highest_used: Some(4),
+                next_unused: 5,
+                scanned: 40,
+            },
+            main
+        );
+        assert_eq!(
+            GapReport {
+                highest_used: None,
+                next_unused: 0,
+                scanned: 20,
+            },
+            change
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_survives_sync_midway_through_send() -> Result<()> {
+        let first = output(20_000);
+        let second = RichOutput {
+            tx_pos: 1,
+            ..output(30_000)
+        };
+        let mut state = WalletState {
+            unspent_outputs: vec![first.clone()],
+            ..Default::default()
+        };
+        let snapshot = state.send_snapshot(&[]);
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(first.tx_hash.clone(), first.tx_pos)?);
+
+        // A sync that only found new coins leaves the snapshot usable
+        state.unspent_outputs.push(second.clone());
+        assert_eq!(vec![first], snapshot.outputs);
+        assert!(snapshot.is_current(&transaction, &state.unspent_outputs));
+
+        // One that saw the coin spent elsewhere does not
+        state.unspent_outputs = vec![second];
+        assert!(!snapshot.is_current(&transaction, &state.unspent_outputs));
+        Ok(())
+    }
+
+    #[test]
+    fn address_at_index_matches_scanner() -> Result<()> {
+        let xprv = test_xprv()?;
+        let state = WalletState::new(&WalletKey::Private(xprv.clone()), Network::Mainnet)?;
+
+        assert_eq!(
+            xprv.address_iter(0, Network::Mainnet).nth(5).unwrap()?,
+            state.receive_address_at(5)?
+        );
+        assert_eq!(
+            xprv.address_iter(1, Network::Mainnet).nth(5).unwrap()?,
+            state.change_address_at(5)?
+        );
+        let testnet = WalletState::new(&WalletKey::Private(xprv.clone()), Network::Testnet)?;
+        assert_eq!(
+            xprv.address_iter(0, Network::Testnet).nth(5).unwrap()?,
+            testnet.receive_address_at(5)?
+        );
+
+        // Nothing is handed out before a sync derived the chain keys
+        let unsynced = WalletState::default();
+        assert!(unsynced.receive_address_at(5).is_err());
+        assert!(unsynced.change_address_at(5).is_err());
+        // Nor are unused addresses before a sync found which are used
+        assert!(matches!(
+            state
+                .unused_receive_addresses(0, 4, 20)
+                .unwrap_err()
+                .downcast::<WalletError>(),
+            Ok(WalletError::NotSynced)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn receive_key_is_derived_after_sync() -> Result<()> {
+        let xprv = test_xprv()?;
+        let state = WalletState {
+            main: FetchingState {
+                key: Some(WalletKey::Private(xprv.derive_normal(0)?)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            xprv.derive_normal(0)?
+                .derive_normal(3)?
+                .derive_public()
+                .public_key_hex(),
+            state.receive_key_at(3)?
+        );
+        assert!(WalletState::default().receive_key_at(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unused_receive_addresses_follow_last_used() -> Result<()> {
+        let xprv = test_xprv()?;
+        let mut scan = ChainScan::default();
+        let batch = xprv
+            .address_iter(0, Network::Mainnet)
+            .take(ADDRESS_BATCH_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        // The first three addresses were used
+        scan.addresses = batch.clone();
+        scan.next_index = 3;
+        let state = WalletState {
+            main: scanned_state(scan, &xprv, 0)?,
+            ..Default::default()
+        };
+
+        let addresses = state.unused_receive_addresses(0, 4, 20)?;
+        assert_eq!(
+            vec![3, 4, 5, 6],
+            addresses
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(state.receive_address(), addresses[0].1);
+        for (index, address) in &addresses {
+            assert_eq!(batch[*index as usize], *address);
+        }
+
+        let later = state.unused_receive_addresses(4, 2, 20)?;
+        assert_eq!(vec![(7, batch[7].clone()), (8, batch[8].clone())], later);
+
+        // Capped at the gap limit past the first unused address
+        let last = state.unused_receive_addresses(15, 5, 17)?;
+        assert_eq!(vec![(18, batch[18].clone()), (19, batch[19].clone())], last);
+        assert!(state.unused_receive_addresses(20, 5, 17)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn xpub_scanned_output_spendable_with_derived_key() -> Result<()> {
+        let xprv = test_xprv()?;
+        let mut scan = ChainScan::default();
+        let batch = xprv
+            .derive_public()
+            .address_iter(1, Network::Mainnet)
+            .take(ADDRESS_BATCH_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        scan.add_batch(batch, &[]);
+        let state = WalletState {
+            main: scanned_state(
+                ChainScan {
+                    addresses: vec![xprv.address_iter(0, Network::Mainnet).next().unwrap()?],
+                    ..Default::default()
+                },
+                &xprv,
+                0,
+            )?,
+            change: scanned_state(scan, &xprv, 1)?,
+            ..Default::default()
+        };
+        let funded = state.change_address_at(7)?;
+        let address = util::address_bytes(&funded)?;
+        let utxo = RichOutput {
+            address,
+            key_path: state.key_path(&address),
+            ..output(10_000)
+        };
+
+        assert_eq!(Some(KeyPath { chain: 1, index: 7 }), utxo.key_path);
+        let keys = state.signing_keys(std::slice::from_ref(&utxo));
+        assert_eq!(
+            Some(&xprv.derive_normal(1)?.derive_normal(7)?.to_keypair()),
+            keys.get(&address)
+        );
+
+        let mut transaction = Transaction::default();
+        transaction.add_input(Input::new(utxo.tx_hash.clone(), utxo.tx_pos)?);
+        transaction.add_output(Output::new(Satoshis(9_000), &funded, Network::Mainnet)?);
+        let previous_outputs = sending::previous_outputs(&[utxo])?;
+        transaction.sign_inputs(&previous_outputs, &keys)?;
+        transaction.verify(&previous_outputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rich_output_records_chain_and_index() -> Result<()> {
+        let xprv = test_xprv()?;
+        let chain = |chain| -> Result<FetchingState> {
+            Ok(FetchingState {
+                lookup: chain_lookup(&xprv.derive_public(), chain, 10)?,
+                ..Default::default()
+            })
+        };
+        let (main, change) = (chain(0)?, chain(1)?);
+        let unspent = UnspentOutput {
+            tx_pos: 0,
+            tx_hash: "3f4fa19803dec4d6a84fae3821da7ac7577080ef75451294e71f9b20e0ab1e7b".to_owned(),
+            value: Satoshis(10_000),
+            height: 0,
+        };
+
+        for (chain, index) in [(0, 0), (0, 9), (1, 4)] {
+            let address = xprv
+                .address_iter(chain as u32, Network::Mainnet)
+                .nth(index as usize)
+                .unwrap()?;
+            let output = rich_output(&address, unspent.clone(), &main, &change)?;
+            assert_eq!(Some(KeyPath { chain, index }), output.key_path);
+            assert_eq!(address, util::to_address(output.address, Network::Mainnet));
+        }
+        let foreign = rich_output(
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            unspent,
+            &main,
+            &change,
+        )?;
+        assert_eq!(None, foreign.key_path);
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_resolves_both_key_encodings() -> Result<()> {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let secret_key = SecretKey::from_slice(&secret)?;
+        let public_key = PublicKey::from_secret_key_global(&secret_key);
+        let state = WalletState {
+            main: FetchingState {
+                lookup: lookup_entries(public_key, 4).into_iter().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let compressed = "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH";
+        let uncompressed = "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm";
+        assert_eq!(Some(("receive", 4)), state.locate_address(compressed));
+        assert_eq!(Some(("receive", 4)), state.locate_address(uncompressed));
+        Ok(())
+    }
+
+    #[test]
+    fn own_address_found_by_chain_and_index() -> Result<()> {
+        let xprv = test_xprv()?;
+        let chain = |chain| -> Result<FetchingState> {
+            Ok(FetchingState {
+                key: Some(WalletKey::Private(xprv.derive_normal(chain)?)),
+                lookup: chain_lookup(&xprv.derive_public(), chain, 5)?,
+                ..Default::default()
+            })
+        };
+        let state = WalletState {
+            main: chain(0)?,
+            change: chain(1)?,
+            ..Default::default()
+        };
+
+        let receive = xprv.address_iter(0, Network::Mainnet).nth(3).unwrap()?;
+        assert!(state.is_own_address(&receive));
+        assert_eq!(Some(("receive", 3)), state.locate_address(&receive));
+        let change = state.change_address_at(4)?;
+        assert_eq!(Some(("change", 4)), state.locate_address(&change));
+        assert!(state.is_change(&util::address_bytes(&change)?));
+        assert!(!state.is_change(&util::address_bytes(&receive)?));
+
+        let foreign = "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH";
+        assert!(!state.is_own_address(foreign));
+        assert_eq!(None, state.locate_address(foreign));
+        assert!(!state.is_own_address("not an address"));
+        Ok(())
+    }
 }
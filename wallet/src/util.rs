@@ -1,14 +1,211 @@
+use std::fmt::Display;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::str::FromStr;
+
 use anyhow::Result;
-use js_sys::{Object, Reflect};
+use js_sys::{Date, Object, Reflect};
 use ripemd::Ripemd160;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 use wasm_bindgen::prelude::*;
-use web_sys::window;
+use web_sys::Performance;
 
 pub const SATOSHIS_PER_BSV: u64 = 100_000_000;
 
+/// An amount of BSV, counted in satoshis.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Satoshis(pub u64);
+
+#[derive(Debug, Error)]
+enum AmountError {
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("{1} amounts can have at most {2} decimal places: {0}")]
+    TooPrecise(String, &'static str, usize),
+    #[error("Amount is too large: {0}")]
+    Overflow(String),
+}
+
+/// Units amounts are shown and entered in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Unit {
+    #[default]
+    Bsv,
+    /// A millionth of a BSV, 100 satoshis.
+    Bits,
+    Satoshis,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 3] = [Unit::Bsv, Unit::Bits, Unit::Satoshis];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Bsv => "BSV",
+            Unit::Bits => "bits",
+            Unit::Satoshis => "satoshis",
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Bsv => "₿",
+            Unit::Bits => " bits",
+            Unit::Satoshis => " sat",
+        }
+    }
+
+    /// Decimal places down to a satoshi, the smallest amount.
+    fn decimals(&self) -> usize {
+        match self {
+            Unit::Bsv => 8,
+            Unit::Bits => 2,
+            Unit::Satoshis => 0,
+        }
+    }
+
+    fn satoshis(&self) -> u64 {
+        10u64.pow(self.decimals() as u32)
+    }
+}
+
+impl FromStr for Unit {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        Unit::ALL
+            .into_iter()
+            .find(|unit| unit.name() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown unit: {value}"))
+    }
+}
+
+impl Satoshis {
+    /// Parses a decimal amount of `unit` with integer math only.
+    pub fn parse_in(value: &str, unit: Unit) -> Result<Self> {
+        let value = value.trim();
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() && fraction.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(AmountError::InvalidAmount(value.to_owned()).into());
+        }
+        let decimals = unit.decimals();
+        if fraction.len() > decimals {
+            return Err(AmountError::TooPrecise(value.to_owned(), unit.name(), decimals).into());
+        }
+
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole
+                .parse()
+                .map_err(|_| AmountError::Overflow(value.to_owned()))?
+        };
+        let fraction: u64 = if decimals == 0 {
+            0
+        } else {
+            format!("{fraction:0<decimals$}").parse()?
+        };
+
+        whole
+            .checked_mul(unit.satoshis())
+            .and_then(|whole| whole.checked_add(fraction))
+            .map(Satoshis)
+            .ok_or_else(|| AmountError::Overflow(value.to_owned()).into())
+    }
+
+    /// The amount in `unit` with all its decimal places, without the unit.
+    pub fn format_in(self, unit: Unit) -> String {
+        match unit {
+            Unit::Bsv => satoshis_to_bsv_string(self),
+            Unit::Bits => satoshis_to_bits(self),
+            Unit::Satoshis => self.0.to_string(),
+        }
+    }
+
+    fn format_decimal(self, unit: Unit) -> String {
+        let decimals = unit.decimals();
+        format!(
+            "{}.{:0decimals$}",
+            self.0 / unit.satoshis(),
+            self.0 % unit.satoshis()
+        )
+    }
+
+    /// The amount in `unit`, followed by the unit.
+    pub fn display_in(self, unit: Unit) -> String {
+        format!("{}{}", self.format_in(unit), unit.symbol())
+    }
+
+    pub fn checked_sub(self, other: Satoshis) -> Option<Satoshis> {
+        self.0.checked_sub(other.0).map(Satoshis)
+    }
+
+    pub fn saturating_sub(self, other: Satoshis) -> Satoshis {
+        Satoshis(self.0.saturating_sub(other.0))
+    }
+}
+
+/// `amount` as a decimal BSV string, e.g. `"0.50000000"`.
+pub fn satoshis_to_bsv_string(amount: Satoshis) -> String {
+    amount.format_decimal(Unit::Bsv)
+}
+
+/// Parses a decimal BSV amount such as `"0.5"` without going through floats.
+pub fn bsv_string_to_satoshis(value: &str) -> Result<Satoshis> {
+    Satoshis::parse_in(value, Unit::Bsv)
+}
+
+/// `amount` in bits, 100 satoshis each, with the remainder as two decimals.
+pub fn satoshis_to_bits(amount: Satoshis) -> String {
+    amount.format_decimal(Unit::Bits)
+}
+
+impl Display for Satoshis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_in(Unit::Bsv))
+    }
+}
+
+impl Add for Satoshis {
+    type Output = Satoshis;
+
+    fn add(self, other: Satoshis) -> Satoshis {
+        Satoshis(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Satoshis {
+    fn add_assign(&mut self, other: Satoshis) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Satoshis {
+    type Output = Satoshis;
+
+    fn sub(self, other: Satoshis) -> Satoshis {
+        Satoshis(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Satoshis {
+    fn sub_assign(&mut self, other: Satoshis) {
+        self.0 -= other.0;
+    }
+}
+
+impl Sum for Satoshis {
+    fn sum<I: Iterator<Item = Satoshis>>(iter: I) -> Satoshis {
+        Satoshis(iter.map(|s| s.0).sum())
+    }
+}
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
@@ -19,6 +216,18 @@ extern "C" {
 
     #[wasm_bindgen(catch, js_namespace = ["chrome", "storage", "local"], js_name = get)]
     async fn storage_get(data: &JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["chrome", "storage", "local"], js_name = remove)]
+    async fn storage_remove(key: &str) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["navigator", "clipboard"], js_name = writeText)]
+    async fn clipboard_write_text(text: &str) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["chrome", "tabs"], js_name = query)]
+    async fn tabs_query(query: &JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["chrome", "tabs"], js_name = sendMessage)]
+    async fn tabs_send_message(tab_id: i32, message: &JsValue) -> Result<JsValue, JsValue>;
 }
 
 #[derive(Debug, Error)]
@@ -67,6 +276,62 @@ where
     })
 }
 
+pub async fn copy_to_clipboard(text: &str) -> Result<()> {
+    match clipboard_write_text(text).await {
+        Ok(_) => Ok(()),
+        Err(error) => Err(JsError::from(error).into()),
+    }
+}
+
+/// The tab query and message having the content script fire a `name` event
+/// with `detail` on the pages of `origin`. The script checks the origin again
+/// before firing, see `static/content.js`.
+fn event_delivery<T: Serialize>(
+    name: &str,
+    origin: &str,
+    detail: &T,
+) -> Result<(serde_json::Value, serde_json::Value)> {
+    let query = serde_json::json!({ "url": format!("{origin}/*") });
+    let message = serde_json::json!({
+        "event": name,
+        "origin": origin,
+        "detail": serde_json::to_value(detail)?,
+    });
+    Ok((query, message))
+}
+
+/// Has the content script of every tab showing `origin` fire a `name` event
+/// on its page with `detail` as its payload.
+pub async fn dispatch_event<T: Serialize>(name: &str, origin: &str, detail: &T) -> Result<()> {
+    let (query, message) = event_delivery(name, origin, detail)?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    let tabs = tabs_query(&query.serialize(&serializer)?)
+        .await
+        .map_err(JsError::from)?;
+    let message = message.serialize(&serializer)?;
+    let tab_ids: Vec<_> = js_sys::Array::from(&tabs)
+        .iter()
+        .filter_map(|tab| Reflect::get(&tab, &JsValue::from_str("id")).ok())
+        .filter_map(|id| id.as_f64())
+        .collect();
+    if tab_ids.is_empty() {
+        return Err(JsError::JsError(format!("No tab showing {origin}")).into());
+    }
+    for tab_id in tab_ids {
+        tabs_send_message(tab_id as i32, &message)
+            .await
+            .map_err(JsError::from)?;
+    }
+    Ok(())
+}
+
+pub async fn store_remove(key: &str) -> Result<()> {
+    match storage_remove(key).await {
+        Ok(_) => Ok(()),
+        Err(error) => Err(JsError::from(error).into()),
+    }
+}
+
 trait OrError<T> {
     fn context(self, message: &str) -> Result<T, JsValue>;
 }
@@ -80,13 +345,21 @@ impl<T> OrError<T> for Option<T> {
     }
 }
 
+/// Returns a millisecond timestamp, using `performance.now()` where available
+/// and falling back to `Date.now()` in contexts without it (e.g. background
+/// service workers).
 pub fn get_timestamp() -> f64 {
-    let window = window().expect("Unable to get window object");
-    let performance = window
-        .performance()
-        .expect("Unable to get performance object");
+    timestamp_from(performance_now, Date::now)
+}
 
-    performance.now()
+fn timestamp_from(performance_now: fn() -> Option<f64>, date_now: fn() -> f64) -> f64 {
+    performance_now().unwrap_or_else(date_now)
+}
+
+fn performance_now() -> Option<f64> {
+    let performance = Reflect::get(&js_sys::global(), &JsValue::from_str("performance")).ok()?;
+    let performance: Performance = performance.dyn_into().ok()?;
+    Some(performance.now())
 }
 
 pub fn double_sha256(data: &[u8]) -> [u8; 32] {
@@ -99,6 +372,11 @@ pub fn sha256(data: &[u8]) -> [u8; 32] {
     hash.finalize().into()
 }
 
+/// RIPEMD-160 of SHA-256, the hash a P2PKH address encodes.
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    ripemd160(&sha256(data))
+}
+
 pub fn ripemd160(data: &[u8]) -> [u8; 20] {
     let mut ripemd = Ripemd160::new();
     ripemd.update(data);
@@ -106,36 +384,431 @@ pub fn ripemd160(data: &[u8]) -> [u8; 20] {
 }
 
 #[derive(Debug, Error)]
-enum AddressError {
+pub enum AddressError {
+    #[error("Invalid address format: {0}")]
+    InvalidFormat(String),
     #[error("Invalid address: {0}")]
     InvalidAddress(String),
     #[error("Address checksum error")]
     ChecksumError,
+    #[error("{address} is a {found} address but the wallet is on {expected}")]
+    WrongNetwork {
+        address: String,
+        found: Network,
+        expected: Network,
+    },
+}
+
+/// Chain an address is meant for, told apart by its version byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    pub const ALL: [Network; 2] = [Network::Mainnet, Network::Testnet];
+
+    pub fn p2pkh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet => 0x6F,
+        }
+    }
+
+    /// The network of a P2PKH or P2SH address version byte.
+    fn from_version(version: u8) -> Option<Self> {
+        match version {
+            0x00 | 0x05 => Some(Network::Mainnet),
+            0x6F | 0xC4 => Some(Network::Testnet),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet => write!(f, "testnet"),
+        }
+    }
 }
 
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        Network::ALL
+            .into_iter()
+            .find(|network| network.to_string() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown network: {value}"))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PaymentUriError {
+    #[error("Payment request requires unsupported {0}")]
+    UnsupportedParameter(String),
+    #[error("Invalid percent encoding: {0}")]
+    InvalidEncoding(String),
+}
+
+/// URI schemes of payment requests, compared case insensitively.
+const PAYMENT_URI_SCHEMES: [&str; 2] = ["bitcoin:", "bsv:"];
+
+/// An address with the amount and label the payee asked for, if any.
+#[derive(Debug, Default, PartialEq)]
+pub struct PaymentRequest {
+    pub address: String,
+    pub amount: Option<Satoshis>,
+    pub label: Option<String>,
+}
+
+/// Reads a bare address or a BIP21 style `bitcoin:` or `bsv:` URI such as
+/// `bitcoin:<address>?amount=0.5&label=Shop`, paying to `network`.
+pub fn parse_payment_uri(text: &str, network: Network) -> Result<PaymentRequest> {
+    let text = text.trim();
+    let uri = PAYMENT_URI_SCHEMES
+        .iter()
+        .find_map(|scheme| {
+            text.get(..scheme.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+                .map(|_| &text[scheme.len()..])
+        })
+        .unwrap_or(text);
+    let (address, query) = uri.split_once('?').unwrap_or((uri, ""));
+    validate_address(address, network)?;
+
+    let mut request = PaymentRequest {
+        address: address.to_owned(),
+        ..Default::default()
+    };
+    for parameter in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        match key {
+            "amount" => request.amount = Some(bsv_string_to_satoshis(value)?),
+            "label" => request.label = Some(percent_decode(value)?),
+            // Paying without understanding these would not do what was asked
+            key if key.starts_with("req-") => {
+                return Err(PaymentUriError::UnsupportedParameter(key.to_owned()).into())
+            }
+            _ => {}
+        }
+    }
+    Ok(request)
+}
+
+fn percent_decode(value: &str) -> Result<String, PaymentUriError> {
+    let invalid = || PaymentUriError::InvalidEncoding(value.to_owned());
+    let mut bytes = vec![];
+    let mut rest = value.as_bytes();
+    while let Some((&byte, remaining)) = rest.split_first() {
+        rest = remaining;
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = rest.get(..2).ok_or_else(invalid)?;
+        let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        rest = &rest[2..];
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// The hash of a P2PKH address on either network, for addresses the wallet
+/// derived or the API returned for them.
 pub fn address_bytes(address: &str) -> Result<[u8; 20]> {
-    let decoded_address = bs58::decode(address).into_vec()?;
-    if decoded_address.len() != 25 || decoded_address[0] != 0 {
-        return Err(AddressError::InvalidAddress(address.to_owned()).into());
+    Ok(decode_address(address)?.1)
+}
+
+/// Checks that `address` is a P2PKH address on `network`, returning its hash.
+pub fn validate_address(address: &str, network: Network) -> Result<[u8; 20], AddressError> {
+    let (found, hash) = decode_address(address)?;
+    if found != network {
+        return Err(AddressError::WrongNetwork {
+            address: address.to_owned(),
+            found,
+            expected: network,
+        });
+    }
+    Ok(hash)
+}
+
+/// The network and hash of a P2PKH address.
+fn decode_address(address: &str) -> Result<(Network, [u8; 20]), AddressError> {
+    let decoded_address = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| AddressError::InvalidFormat(address.to_owned()))?;
+    if decoded_address.len() != 25 {
+        return Err(AddressError::InvalidAddress(address.to_owned()));
     }
 
-    let address: [u8; 20] = decoded_address[1..21]
-        .try_into()
-        .expect("Manual bounds set");
     let checksum = double_sha256(&decoded_address[..21]);
     if checksum[0..4] != decoded_address[21..] {
-        return Err(AddressError::ChecksumError.into());
+        return Err(AddressError::ChecksumError);
+    }
+    let network = Network::from_version(decoded_address[0])
+        .ok_or_else(|| AddressError::InvalidAddress(address.to_owned()))?;
+    // P2SH addresses share their network's version bytes but can't be paid
+    if decoded_address[0] != network.p2pkh_version() {
+        return Err(AddressError::InvalidAddress(address.to_owned()));
     }
-    Ok(address)
-}
 
-pub fn to_address(bytes: [u8; 20]) -> String {
-    let mut prefixed = Vec::with_capacity(21);
-    prefixed.push(0x00);
-    prefixed.extend(&bytes);
+    Ok((
+        network,
+        decoded_address[1..21]
+            .try_into()
+            .expect("Manual bounds set"),
+    ))
+}
 
-    let checksum = sha256(&sha256(&prefixed));
-    prefixed.extend(&checksum[..4]);
+/// The P2PKH address of `bytes` on `network`.
+pub fn to_address(bytes: [u8; 20], network: Network) -> String {
+    // Built on the stack, scanning encodes thousands of these
+    let mut prefixed = [0u8; 25];
+    prefixed[0] = network.p2pkh_version();
+    prefixed[1..21].copy_from_slice(&bytes);
+    let checksum = double_sha256(&prefixed[..21]);
+    prefixed[21..].copy_from_slice(&checksum[..4]);
 
     bs58::encode(prefixed).into_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn parse_payment_uri_reads_address_amount_and_label() -> Result<()> {
+        let address = "1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr";
+
+        assert_eq!(
+            PaymentRequest {
+                address: address.to_owned(),
+                ..Default::default()
+            },
+            parse_payment_uri(address, Network::Mainnet)?
+        );
+        assert_eq!(
+            PaymentRequest {
+                address: address.to_owned(),
+                amount: Some(Satoshis(50_000_000)),
+                label: Some("Coffee Shop".to_owned()),
+            },
+            parse_payment_uri(
+                &format!(" BITCOIN:{address}?amount=0.5&label=Coffee%20Shop "),
+                Network::Mainnet
+            )?
+        );
+        assert_eq!(
+            Some(Satoshis(1)),
+            parse_payment_uri(
+                &format!("bsv:{address}?amount=0.00000001"),
+                Network::Mainnet
+            )?
+            .amount
+        );
+        assert!(
+            parse_payment_uri(&format!("bitcoin:{address}?req-foo=1"), Network::Mainnet).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_payment_uri_rejects_invalid_address() {
+        let error = parse_payment_uri(
+            "bitcoin:1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQH0?amount=0.5",
+            Network::Mainnet,
+        )
+        .unwrap_err();
+
+        assert!(error.downcast_ref::<AddressError>().is_some());
+    }
+
+    #[test]
+    fn validate_address_reports_reason() {
+        let error = |address| validate_address(address, Network::Mainnet).unwrap_err();
+
+        assert!(validate_address("1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr", Network::Mainnet).is_ok());
+        assert!(matches!(
+            error("1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQH0"),
+            AddressError::InvalidFormat(_)
+        ));
+        assert!(matches!(
+            error("not an address"),
+            AddressError::InvalidFormat(_)
+        ));
+        assert!(matches!(
+            error("1BvgsfsZQVtkLS69NvGF8rw6NZW2S"),
+            AddressError::InvalidAddress(_)
+        ));
+        assert!(matches!(
+            error("1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHs"),
+            AddressError::ChecksumError
+        ));
+    }
+
+    #[test]
+    fn validate_address_names_wrong_network() {
+        let testnet = "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYD";
+        let error = validate_address(testnet, Network::Mainnet).unwrap_err();
+
+        assert_eq!(
+            format!("{testnet} is a testnet address but the wallet is on mainnet"),
+            error.to_string()
+        );
+        assert!(matches!(
+            error,
+            AddressError::WrongNetwork {
+                found: Network::Testnet,
+                expected: Network::Mainnet,
+                ..
+            }
+        ));
+        assert!(validate_address(testnet, Network::Testnet).is_ok());
+        assert!(matches!(
+            validate_address("1BvgsfsZQVtkLS69NvGF8rw6NZW2ShJQHr", Network::Testnet),
+            Err(AddressError::WrongNetwork {
+                found: Network::Mainnet,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn to_address_encodes_for_network() -> Result<()> {
+        let testnet = "mgebfQyBPxuRF46pMXpoGL3pS4TaA4ZGYD";
+        let hash = address_bytes(testnet)?;
+
+        assert_eq!(testnet, to_address(hash, Network::Testnet));
+        assert_eq!(hash, address_bytes(&to_address(hash, Network::Mainnet))?);
+        for network in Network::ALL {
+            assert_eq!(network, network.to_string().parse()?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn satoshis_parse_edge_values() -> Result<()> {
+        assert_eq!(Satoshis(0), bsv_string_to_satoshis("0")?);
+        assert_eq!(Satoshis(1), bsv_string_to_satoshis("0.00000001")?);
+        assert_eq!(Satoshis(50_000_000), bsv_string_to_satoshis(".5")?);
+        assert_eq!(Satoshis(300_000_000), bsv_string_to_satoshis(" 3. ")?);
+        assert_eq!(
+            Satoshis(2_099_999_999_999_999),
+            bsv_string_to_satoshis("20999999.99999999")?
+        );
+        assert_eq!(
+            Satoshis(u64::MAX),
+            bsv_string_to_satoshis("184467440737.09551615")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn satoshis_parse_keeps_precision_floats_lose() -> Result<()> {
+        // 2^24 + 1 satoshis, the first count an f32 can't hold
+        assert_ne!(16_777_217, (0.16777217f32 * SATOSHIS_PER_BSV as f32) as u64);
+        assert_eq!(Satoshis(16_777_217), bsv_string_to_satoshis("0.16777217")?);
+        assert_eq!(
+            Satoshis(1_234_567_890_123_457),
+            bsv_string_to_satoshis("12345678.90123457")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn satoshis_parse_rejects_invalid() {
+        for value in [
+            "",
+            ".",
+            "-1",
+            "1e5",
+            "1,5",
+            "0.000000001",
+            "184467440737.09551616",
+            "99999999999999999999",
+        ] {
+            assert!(bsv_string_to_satoshis(value).is_err(), "{value} parsed");
+        }
+    }
+
+    #[test]
+    fn satoshis_format_edge_values() {
+        assert_eq!("0.00000000", satoshis_to_bsv_string(Satoshis(0)));
+        assert_eq!("0.00000001", satoshis_to_bsv_string(Satoshis(1)));
+        assert_eq!(
+            "1.00000000",
+            satoshis_to_bsv_string(Satoshis(SATOSHIS_PER_BSV))
+        );
+        assert_eq!(
+            "184467440737.09551615",
+            satoshis_to_bsv_string(Satoshis(u64::MAX))
+        );
+        assert_eq!("0.50000000₿", Satoshis(50_000_000).to_string());
+    }
+
+    #[test]
+    fn satoshis_round_trip_units() -> Result<()> {
+        let amount = Satoshis::parse_in("123.45678901", Unit::Bsv)?;
+        assert_eq!(Satoshis(12_345_678_901), amount);
+        assert_eq!("123.45678901", amount.format_in(Unit::Bsv));
+
+        assert_eq!("123456789.01", amount.format_in(Unit::Bits));
+        assert_eq!(amount, Satoshis::parse_in("123456789.01", Unit::Bits)?);
+        assert_eq!("123456789.01 bits", amount.display_in(Unit::Bits));
+        assert_eq!(Satoshis(100), Satoshis::parse_in("1", Unit::Bits)?);
+        assert!(Satoshis::parse_in("0.001", Unit::Bits).is_err());
+
+        assert_eq!("12345678901", amount.format_in(Unit::Satoshis));
+        assert_eq!(amount, Satoshis::parse_in("12345678901", Unit::Satoshis)?);
+        assert!(Satoshis::parse_in("1.5", Unit::Satoshis).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn timestamp_falls_back_to_date() {
+        assert_eq!(12.5, timestamp_from(|| Some(12.5), || 1_000.0));
+        assert_eq!(1_000.0, timestamp_from(|| None, || 1_000.0));
+    }
+
+    #[test]
+    fn converts_between_units() -> Result<()> {
+        let amount = bsv_string_to_satoshis("123.45678901")?;
+        assert_eq!(Satoshis(12_345_678_901), amount);
+        assert_eq!("123.45678901", satoshis_to_bsv_string(amount));
+        assert!(bsv_string_to_satoshis("0.000000001").is_err());
+
+        assert_eq!("123456789.01", satoshis_to_bits(amount));
+        assert_eq!("1.00", satoshis_to_bits(Satoshis(100)));
+        assert_eq!("0.99", satoshis_to_bits(Satoshis(99)));
+        Ok(())
+    }
+
+    #[test]
+    fn event_delivery_targets_origin() -> Result<()> {
+        let detail = serde_json::json!({ "txid": "ab", "amount": 1000 });
+
+        let (query, message) = event_delivery("beesv-sent", "https://shop.example", &detail)?;
+
+        assert_eq!(
+            serde_json::json!({ "url": "https://shop.example/*" }),
+            query
+        );
+        assert_eq!(
+            serde_json::json!({
+                "event": "beesv-sent",
+                "origin": "https://shop.example",
+                "detail": { "txid": "ab", "amount": 1000 },
+            }),
+            message
+        );
+        Ok(())
+    }
+}
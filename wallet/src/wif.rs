@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use secp256k1::{PublicKey, SecretKey};
+use thiserror::Error;
+
+use crate::util::{double_sha256, ripemd160, sha256, to_address, Network};
+
+const MAINNET_VERSION: u8 = 0x80;
+const COMPRESSED_FLAG: u8 = 0x01;
+
+#[derive(Debug, Error)]
+pub enum WifError {
+    #[error("Invalid WIF length: {0}")]
+    InvalidLength(usize),
+    #[error("Invalid WIF version: {0:#04x}")]
+    InvalidVersion(u8),
+    #[error("Invalid compression flag: {0:#04x}")]
+    InvalidCompressionFlag(u8),
+    #[error("WIF checksum error")]
+    ChecksumError,
+    #[error("Invalid base58: {0}")]
+    InvalidBase58(#[from] bs58::decode::Error),
+    #[error("Invalid key: {0}")]
+    InvalidKey(#[from] secp256k1::Error),
+}
+
+/// A private key in wallet import format, which also records whether its
+/// public key is serialized compressed. Both encodings pay to different
+/// addresses, so the flag has to be honored to find the funds.
+pub struct PrivateKey {
+    secret_key: SecretKey,
+    compressed: bool,
+}
+
+impl PrivateKey {
+    pub fn public_key(&self) -> Vec<u8> {
+        let public_key = PublicKey::from_secret_key_global(&self.secret_key);
+        if self.compressed {
+            public_key.serialize().to_vec()
+        } else {
+            public_key.serialize_uncompressed().to_vec()
+        }
+    }
+
+    pub fn address(&self, network: Network) -> String {
+        to_address(self.address_hash(), network)
+    }
+
+    fn address_hash(&self) -> [u8; 20] {
+        ripemd160(&sha256(&self.public_key()))
+    }
+
+    /// An entry for the address keys used when signing, so coins sent to an
+    /// imported key can be spent next to the wallet's own.
+    pub fn address_key(&self) -> ([u8; 20], (SecretKey, PublicKey)) {
+        let public_key = PublicKey::from_secret_key_global(&self.secret_key);
+        (self.address_hash(), (self.secret_key, public_key))
+    }
+}
+
+impl FromStr for PrivateKey {
+    type Err = WifError;
+
+    fn from_str(s: &str) -> Result<Self, WifError> {
+        let decoded = bs58::decode(s.trim()).into_vec()?;
+        let (payload, checksum) = match decoded.len() {
+            37 | 38 => decoded.split_at(decoded.len() - 4),
+            length => return Err(WifError::InvalidLength(length)),
+        };
+        if double_sha256(payload)[..4] != *checksum {
+            return Err(WifError::ChecksumError);
+        }
+        if payload[0] != MAINNET_VERSION {
+            return Err(WifError::InvalidVersion(payload[0]));
+        }
+
+        let compressed = match payload.get(33) {
+            None => false,
+            Some(&COMPRESSED_FLAG) => true,
+            Some(&flag) => return Err(WifError::InvalidCompressionFlag(flag)),
+        };
+        Ok(Self {
+            secret_key: SecretKey::from_slice(&payload[1..33])?,
+            compressed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncompressed_wif_derives_uncompressed_address() -> Result<()> {
+        let key: PrivateKey = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ".parse()?;
+
+        assert!(!key.compressed);
+        assert_eq!(65, key.public_key().len());
+        assert_eq!(
+            "1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S",
+            key.address(Network::Mainnet)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_wif_derives_compressed_address() -> Result<()> {
+        let key: PrivateKey = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617".parse()?;
+
+        assert!(key.compressed);
+        assert_eq!(33, key.public_key().len());
+        assert_eq!(
+            "1LoVGDgRs9hTfTNJNuXKSpywcbdvwRXpmK",
+            key.address(Network::Mainnet)
+        );
+        Ok(())
+    }
+}